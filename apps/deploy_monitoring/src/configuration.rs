@@ -5,12 +5,19 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 
 use clap::{App, Arg};
+use regex::Regex;
+
+use crate::image::{DeployMonitoringContainer, Image};
+use crate::node::{OcamlNode, TezedgeNode};
 
 #[derive(Clone, Debug)]
 pub struct DeployMonitoringEnvironment {
     // logging level
     pub log_level: slog::Level,
 
+    // logging format
+    pub log_format: LogFormat,
+
     // interval in seconds to check for new remote image
     pub image_monitor_interval: Option<u64>,
 
@@ -20,9 +27,27 @@ pub struct DeployMonitoringEnvironment {
     // rpc server port
     pub rpc_port: u16,
 
+    // file to persist the resource measurements to, to survive restart
+    pub resource_history_file: Option<PathBuf>,
+
+    // max size in bytes of the resource history file, before it is rotated
+    pub resource_history_max_size: u64,
+
+    // prefix for the metrics exposed in prometheus format
+    pub metrics_prefix: String,
+
     // flag for sandbox mode
     pub is_sandbox: bool,
 
+    // flag to run the deploy (image) monitor, needs the image monitor interval set as well
+    pub enable_deploy_monitor: bool,
+
+    // flag to run the resource monitor
+    pub enable_resource_monitor: bool,
+
+    // flag to run the rpc server
+    pub enable_rpc_server: bool,
+
     // Path for the compose file needed to manage the deployed containers
     pub compose_file_path: PathBuf,
 
@@ -32,12 +57,78 @@ pub struct DeployMonitoringEnvironment {
     // Thresholds to alerts
     pub ocaml_alert_thresholds: AlertThresholds,
 
+    // Memory ceiling in bytes for all nodes (0 - disabled)
+    pub memory_ceiling: u64,
+
+    // Threshold in % for the average cpu usage of all nodes together (None - disabled)
+    pub combined_cpu_threshold: Option<u64>,
+
+    // Count of the last measurements, the combined cpu usage is averaged over
+    pub combined_cpu_window: usize,
+
+    // path to the node data directory, free space of its filesystem is monitored (None - disabled)
+    pub data_dir_path: Option<PathBuf>,
+
+    // Alert, when the free space on the data directory filesystem drops bellow this threshold
+    pub data_dir_free_space_threshold: FreeSpaceThreshold,
+
+    // Alert, when a container is restarted more than this times between two deploy monitor checks
+    pub restart_loop_threshold: u64,
+
+    // flag for self-heal mode, the exited/dead node container is restarted, not just reported
+    pub self_heal: bool,
+
+    // max count of the self-heal restarts of a container per hour
+    pub self_heal_max_restarts: usize,
+
     // flag for volume cleanup mode
     pub cleanup_volumes: bool,
 
+    // flag for dry-run mode, docker commands are just logged, not executed
+    pub dry_run: bool,
+
+    // timeout in seconds for the cleanup on shutdown, after which the app is forced to exit
+    pub shutdown_timeout: u64,
+
     pub slack_configuration: Option<SlackConfiguration>,
 
+    // routing key of the pagerduty Events API v2 integration, alerts are sent to pagerduty as well (None - disabled)
+    pub pagerduty_routing_key: Option<String>,
+
+    // signing secret of the slack app, used to verify the slash-command requests (None - disabled)
+    pub slack_signing_secret: Option<String>,
+
     pub tezedge_only: bool,
+
+    // name (or regex) of the tezedge node container to monitor
+    pub tezedge_container_name: String,
+
+    // name (or regex) of the ocaml node container to monitor
+    pub ocaml_container_name: String,
+
+    // additional tezedge node containers (name, rpc port) to monitor
+    pub tezedge_node_containers: Vec<(String, u16)>,
+
+    // images pinned by digest, mismatch is reported and pinned images are not updated
+    pub pinned_images: Vec<Image>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LogFormat {
+    Json,
+    Simple,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "simple" => Ok(LogFormat::Simple),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unsupported variant: {}", s)),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -61,6 +152,54 @@ impl fmt::Display for AlertThresholds {
     }
 }
 
+/// Min free space, either absolute or relative to the total space of the filesystem
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FreeSpaceThreshold {
+    Bytes(u64),
+    Percentage(u64),
+}
+
+impl FreeSpaceThreshold {
+    /// Min free bytes on a filesystem of `total` bytes
+    pub fn min_free_bytes(&self, total: u64) -> u64 {
+        match *self {
+            FreeSpaceThreshold::Bytes(bytes) => bytes,
+            FreeSpaceThreshold::Percentage(percentage) => total / 100 * percentage,
+        }
+    }
+}
+
+impl std::str::FromStr for FreeSpaceThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('%') {
+            Some(percentage) => match percentage.trim().parse::<u64>() {
+                Ok(percentage) if percentage <= 100 => {
+                    Ok(FreeSpaceThreshold::Percentage(percentage))
+                }
+                _ => Err(format!(
+                    "Expected percentage between 0% and 100%, got '{}'",
+                    s
+                )),
+            },
+            None => s
+                .parse::<u64>()
+                .map(FreeSpaceThreshold::Bytes)
+                .map_err(|_| format!("Expected number of bytes [u64] or percentage, got '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for FreeSpaceThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FreeSpaceThreshold::Bytes(bytes) => write!(f, "{}MB", bytes / 1024 / 1024),
+            FreeSpaceThreshold::Percentage(percentage) => write!(f, "{}%", percentage),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SlackConfiguration {
     // slack bot token
@@ -114,6 +253,14 @@ fn deploy_monitoring_app() -> App<'static, 'static> {
                 .possible_values(&["critical", "error", "warn", "info", "debug", "trace"])
                 .help("Set log level"),
         )
+        .arg(
+            Arg::with_name("log-format")
+                .long("log-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["json", "simple"])
+                .help("Set output format of the log (default simple)"),
+        )
         .arg(
             Arg::with_name("image-tag")
                 .long("image-tag")
@@ -142,19 +289,49 @@ fn deploy_monitoring_app() -> App<'static, 'static> {
                 .value_name("SLACK-CHANNEL-NAME")
                 .help("The slack url of the channel to send the messages to"),
         )
+        .arg(
+            Arg::with_name("slack-signing-secret")
+                .long("slack-signing-secret")
+                .takes_value(true)
+                .value_name("SLACK-SIGNING-SECRET")
+                .help("The signing secret of the slack app, enables the slash-command endpoint of the rpc server"),
+        )
+        .arg(
+            Arg::with_name("pagerduty-routing-key")
+                .long("pagerduty-routing-key")
+                .takes_value(true)
+                .value_name("PAGERDUTY-ROUTING-KEY")
+                .help("The routing (integration) key of the pagerduty service, critical resource alerts trigger pagerduty incidents (besides the slack messages)"),
+        )
         .arg(
             Arg::with_name("image-monitor-interval")
                 .long("image-monitor-interval")
                 .takes_value(true)
                 .value_name("IMAGE-MONITOR-INTERVAL")
-                .help("Interval in seconds to check for new remote images"),
+                .help("Interval in seconds to check for new remote images (min 1)")
+                .validator(validate_interval),
         )
         .arg(
             Arg::with_name("resource-monitor-interval")
                 .long("resource-monitor-interval")
                 .takes_value(true)
                 .value_name("RESOURCE-MONITOR-INTERVAL")
-                .help("Interval in seconds to take resource utilization measurements"),
+                .help("Interval in seconds to take resource utilization measurements (min 1, default 15)")
+                .validator(validate_interval),
+        )
+        .arg(
+            Arg::with_name("resource-history-file")
+                .long("resource-history-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Path to the file, where the resource utilization measurements are persisted (json lines) and reloaded from on start"),
+        )
+        .arg(
+            Arg::with_name("resource-history-max-size")
+                .long("resource-history-max-size")
+                .takes_value(true)
+                .value_name("RESOURCE-HISTORY-MAX-SIZE")
+                .help("Max size of the resource history file in megabytes, before it is rotated"),
         )
         .arg(
             Arg::with_name("rpc-port")
@@ -163,15 +340,67 @@ fn deploy_monitoring_app() -> App<'static, 'static> {
                 .value_name("RPC-PORT")
                 .help("Port number to open the monitoring rpc server on"),
         )
+        .arg(
+            Arg::with_name("metrics-prefix")
+                .long("metrics-prefix")
+                .takes_value(true)
+                .value_name("METRICS-PREFIX")
+                .help("Prefix for the metrics exposed by the rpc server in prometheus format"),
+        )
         .arg(
             Arg::with_name("sandbox")
                 .long("sandbox")
                 .help("Watch only the sandbox launcher and a debugger"),
         )
         .arg(
-            Arg::with_name("cleanup-volumes")
-                .long("cleanup-volumes")
-                .help("Enable and dissable volume cleanup"),
+            Arg::with_name("enable-deploy-monitor")
+                .long("enable-deploy-monitor")
+                .takes_value(true)
+                .value_name("BOOL")
+                .possible_values(&["true", "false"])
+                .help("Run the deploy monitor, which watches for new remote images and the container states (default true)"),
+        )
+        .arg(
+            Arg::with_name("enable-resource-monitor")
+                .long("enable-resource-monitor")
+                .takes_value(true)
+                .value_name("BOOL")
+                .possible_values(&["true", "false"])
+                .help("Run the resource monitor, which measures the resource utilization of the nodes and alerts (default true)"),
+        )
+        .arg(
+            Arg::with_name("enable-rpc-server")
+                .long("enable-rpc-server")
+                .takes_value(true)
+                .value_name("BOOL")
+                .possible_values(&["true", "false"])
+                .help("Run the rpc server exposing the resource measurements (default true)"),
+        )
+        .arg(
+            Arg::with_name("alert-combined-cpu-threshold")
+                .long("alert-combined-cpu-threshold")
+                .takes_value(true)
+                .value_name("ALERT-COMBINED-CPU-THRESHOLD")
+                .help("Thershold in % for critical alerts - average cpu usage of all nodes together over the window"),
+        )
+        .arg(
+            Arg::with_name("alert-combined-cpu-window")
+                .long("alert-combined-cpu-window")
+                .takes_value(true)
+                .value_name("ALERT-COMBINED-CPU-WINDOW")
+                .help("Count of the last measurements, the combined cpu usage is averaged over (default 10)"),
+        )
+        .arg(
+            Arg::with_name("alert-restart-loop-threshold")
+                .long("alert-restart-loop-threshold")
+                .takes_value(true)
+                .value_name("ALERT-RESTART-LOOP-THRESHOLD")
+                .help("Alert, when a container is restarted by docker more than this times within the image monitor interval"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("Only log the docker and docker-compose commands and the redeploys instead of executing them, slack messages are prefixed with [DRY-RUN]"),
         )
         .arg(
             Arg::with_name("tezedge-alert-threshold-disk")
@@ -229,19 +458,119 @@ fn deploy_monitoring_app() -> App<'static, 'static> {
                 .value_name("ALERT-THRESHOLD-SYNCHRONIZATION")
                 .help("Thershold in seconds for critical alerts - synchronization"),
         )
+        .arg(
+            Arg::with_name("alert-memory-ceiling")
+                .long("alert-memory-ceiling")
+                .takes_value(true)
+                .value_name("ALERT-MEMORY-CEILING")
+                .help("Memory ceiling in bytes for all nodes, exceeding it is reported once until it drops back (0 - disabled)"),
+        )
         .arg(
             Arg::with_name("cleanup-volumes")
                 .long("cleanup-volumes")
                 .help("Enable and dissable volume cleanup"),
         )
+        .arg(
+            Arg::with_name("data-dir-path")
+                .long("data-dir-path")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Path to the node data directory (on the host), the free space of its filesystem is measured and alerted on"),
+        )
+        .arg(
+            Arg::with_name("alert-data-dir-free-space")
+                .long("alert-data-dir-free-space")
+                .takes_value(true)
+                .value_name("ALERT-DATA-DIR-FREE-SPACE")
+                .help("Thershold for critical alerts - min free space on the data directory filesystem, in bytes or in % of the total space, e.g. 10% (default 10%)")
+                .validator(|v| v.parse::<FreeSpaceThreshold>().map(|_| ())),
+        )
+        .arg(
+            Arg::with_name("self-heal")
+                .long("self-heal")
+                .help("Restart the node container, when it exited or died, instead of just alerting"),
+        )
+        .arg(
+            Arg::with_name("self-heal-max-restarts")
+                .long("self-heal-max-restarts")
+                .takes_value(true)
+                .value_name("SELF-HEAL-MAX-RESTARTS")
+                .help("Max count of the self-heal restarts of a container within an hour (default 3)"),
+        )
+        .arg(
+            Arg::with_name("shutdown-timeout")
+                .long("shutdown-timeout")
+                .takes_value(true)
+                .value_name("SHUTDOWN-TIMEOUT")
+                .help("Timeout in seconds for the cleanup on shutdown, after which the app is forced to exit (default 30)"),
+        )
         .arg(
             Arg::with_name("tezedge-only")
                 .long("tezedge-only")
                 .help("Only launches the tezedge node with debugger and explorer"),
+        )
+        .arg(
+            Arg::with_name("tezedge-container-name")
+                .long("tezedge-container-name")
+                .takes_value(true)
+                .value_name("NAME-OR-REGEX")
+                .help("Name (or regex matching the whole name) of the tezedge node container to monitor (default deploy-monitoring-tezedge-node)")
+                .validator(validate_container_name),
+        )
+        .arg(
+            Arg::with_name("ocaml-container-name")
+                .long("ocaml-container-name")
+                .takes_value(true)
+                .value_name("NAME-OR-REGEX")
+                .help("Name (or regex matching the whole name) of the ocaml node container to monitor (default deploy-monitoring-ocaml-node)")
+                .validator(validate_container_name),
+        )
+        .arg(
+            Arg::with_name("tezedge-node-containers")
+                .long("tezedge-node-containers")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .value_name("NAME:RPC-PORT")
+                .help("Additional tezedge node containers to monitor, e.g. tezedge-node-2:18742"),
+        )
+        .arg(
+            Arg::with_name("pinned-image-digests")
+                .long("pinned-image-digests")
+                .takes_value(true)
+                .multiple(true)
+                .use_delimiter(true)
+                .value_name("CONTAINER-NAME=DIGEST")
+                .help("Expected image digests of the containers, mismatch is reported and pinned images are not updated, e.g. deploy-monitoring-tezedge-node=sha256:<hash>"),
         );
     app
 }
 
+/// Min interval in seconds between two runs of a monitor, lower values would make the monitors busy-loop
+const MIN_MONITOR_INTERVAL: u64 = 1;
+
+fn validate_interval(v: String) -> Result<(), String> {
+    match v.parse::<u64>() {
+        Ok(interval) if interval >= MIN_MONITOR_INTERVAL => Ok(()),
+        Ok(interval) => Err(format!(
+            "Interval {}s is too low, min allowed is {}s",
+            interval, MIN_MONITOR_INTERVAL
+        )),
+        Err(_) => Err(format!("Expected u64 value of seconds, got '{}'", v)),
+    }
+}
+
+/// Container name pattern has to match the whole container name, not just its part
+pub fn container_name_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+fn validate_container_name(v: String) -> Result<(), String> {
+    container_name_regex(&v)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid container name pattern '{}': {}", v, e))
+}
+
 // Validates single required arg. If missing, exit whole process
 pub fn validate_required_arg(args: &clap::ArgMatches, arg_name: &str) {
     if !args.is_present(arg_name) {
@@ -276,6 +605,39 @@ fn check_slack_args(args: &clap::ArgMatches) -> Option<SlackConfiguration> {
     }
 }
 
+fn is_enabled(args: &clap::ArgMatches, arg_name: &str) -> bool {
+    args.value_of(arg_name)
+        .unwrap_or("true")
+        .parse::<bool>()
+        .expect("Was expecting 'true' or 'false'")
+}
+
+fn parse_node_container(container: &str) -> (String, u16) {
+    match container.rsplitn(2, ':').collect::<Vec<_>>().as_slice() {
+        [port, name] if !name.is_empty() => (
+            name.to_string(),
+            port.parse::<u16>()
+                .expect("Expected u16 value of valid port number"),
+        ),
+        _ => panic!(
+            "Invalid node container: {}, expected format NAME:RPC-PORT",
+            container
+        ),
+    }
+}
+
+fn parse_pinned_image_digest(pinned_digest: &str) -> Image {
+    match pinned_digest.splitn(2, '=').collect::<Vec<_>>().as_slice() {
+        [name, digest] if !name.is_empty() && !digest.is_empty() => {
+            Image::pinned(name.to_string(), digest.to_string())
+        }
+        _ => panic!(
+            "Invalid pinned image digest: {}, expected format CONTAINER-NAME=DIGEST",
+            pinned_digest
+        ),
+    }
+}
+
 impl DeployMonitoringEnvironment {
     pub fn from_args() -> Self {
         let app = deploy_monitoring_app();
@@ -344,6 +706,11 @@ impl DeployMonitoringEnvironment {
                 .unwrap_or("info")
                 .parse::<slog::Level>()
                 .expect("Was expecting one value from slog::Level"),
+            log_format: args
+                .value_of("log-format")
+                .unwrap_or("simple")
+                .parse::<LogFormat>()
+                .expect("Was expecting 'simple' or 'json'"),
             compose_file_path: args
                 .value_of("compose-file-path")
                 .unwrap_or("")
@@ -358,7 +725,7 @@ impl DeployMonitoringEnvironment {
                 }),
             resource_monitor_interval: args
                 .value_of("resource-monitor-interval")
-                .unwrap_or("0")
+                .unwrap_or("15")
                 .parse::<u64>()
                 .expect("Expected u64 value of seconds"),
             rpc_port: args
@@ -366,12 +733,95 @@ impl DeployMonitoringEnvironment {
                 .unwrap_or("38732")
                 .parse::<u16>()
                 .expect("Expected u16 value of valid port number"),
+            resource_history_file: args.value_of("resource-history-file").map(|path| {
+                path.parse::<PathBuf>()
+                    .expect("Expected valid path for the resource history file")
+            }),
+            resource_history_max_size: args
+                .value_of("resource-history-max-size")
+                .unwrap_or("100")
+                .parse::<u64>()
+                .expect("Was expecting number of megabytes [u64]")
+                * 1024
+                * 1024,
+            metrics_prefix: args
+                .value_of("metrics-prefix")
+                .unwrap_or("tezedge")
+                .to_string(),
             is_sandbox: args.is_present("sandbox"),
+            enable_deploy_monitor: is_enabled(&args, "enable-deploy-monitor"),
+            enable_resource_monitor: is_enabled(&args, "enable-resource-monitor"),
+            enable_rpc_server: is_enabled(&args, "enable-rpc-server"),
             cleanup_volumes: args.is_present("cleanup-volumes"),
+            dry_run: args.is_present("dry-run"),
+            shutdown_timeout: args
+                .value_of("shutdown-timeout")
+                .unwrap_or("30")
+                .parse::<u64>()
+                .expect("Expected u64 value of seconds"),
             tezedge_only: args.is_present("tezedge-only"),
+            tezedge_container_name: args
+                .value_of("tezedge-container-name")
+                .unwrap_or(TezedgeNode::NAME)
+                .to_string(),
+            ocaml_container_name: args
+                .value_of("ocaml-container-name")
+                .unwrap_or(OcamlNode::NAME)
+                .to_string(),
+            tezedge_node_containers: args
+                .values_of("tezedge-node-containers")
+                .map(|containers| containers.map(parse_node_container).collect())
+                .unwrap_or_default(),
+            pinned_images: args
+                .values_of("pinned-image-digests")
+                .map(|digests| digests.map(parse_pinned_image_digest).collect())
+                .unwrap_or_default(),
             tezedge_alert_thresholds,
             ocaml_alert_thresholds,
+            memory_ceiling: args
+                .value_of("alert-memory-ceiling")
+                .unwrap_or("0")
+                .parse::<u64>()
+                .expect("Was expecting number of bytes [u64]"),
+            combined_cpu_threshold: args.value_of("alert-combined-cpu-threshold").map(
+                |cpu_thresh| {
+                    cpu_thresh
+                        .parse::<u64>()
+                        .expect("Was expecting percentage [u64]")
+                },
+            ),
+            combined_cpu_window: args
+                .value_of("alert-combined-cpu-window")
+                .unwrap_or("10")
+                .parse::<usize>()
+                .expect("Was expecting number of measurements [usize]"),
+            restart_loop_threshold: args
+                .value_of("alert-restart-loop-threshold")
+                .unwrap_or("3")
+                .parse::<u64>()
+                .expect("Was expecting number of restarts [u64]"),
+            data_dir_path: args.value_of("data-dir-path").map(|path| {
+                path.parse::<PathBuf>()
+                    .expect("Expected valid path for the data directory")
+            }),
+            data_dir_free_space_threshold: args
+                .value_of("alert-data-dir-free-space")
+                .unwrap_or("10%")
+                .parse::<FreeSpaceThreshold>()
+                .expect("Free space threshold is validated on start"),
+            self_heal: args.is_present("self-heal"),
+            self_heal_max_restarts: args
+                .value_of("self-heal-max-restarts")
+                .unwrap_or("3")
+                .parse::<usize>()
+                .expect("Was expecting number of restarts [usize]"),
             slack_configuration,
+            slack_signing_secret: args
+                .value_of("slack-signing-secret")
+                .map(|secret| secret.to_string()),
+            pagerduty_routing_key: args
+                .value_of("pagerduty-routing-key")
+                .map(|routing_key| routing_key.to_string()),
         }
     }
 }
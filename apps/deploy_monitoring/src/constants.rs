@@ -19,3 +19,18 @@ pub const DEBUGGER_VOLUME_PATH: &str =
 
 /// The max capacity of the VecDeque holding the measurements
 pub const MEASUREMENTS_MAX_CAPACITY: usize = 40320;
+
+/// Default count of retries of the failed slack message
+pub const SLACK_MAX_RETRIES: u32 = 3;
+
+/// Endpoint of the pagerduty Events API v2
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Default count of retries of the failed pagerduty event
+pub const PAGERDUTY_MAX_RETRIES: u32 = 3;
+
+/// All the containers launched by deploy monitoring are named with this prefix
+pub const CONTAINER_NAME_PREFIX: &str = "deploy-monitoring-";
+
+/// Count of removal retries for the containers surviving the cleanup
+pub const CLEANUP_MAX_RETRIES: u32 = 3;
@@ -1,65 +1,137 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::os::unix::process::ExitStatusExt;
 use std::path::PathBuf;
-use std::process::{Command, Output};
+use std::process::{Command, ExitStatus, Output};
 
+use regex::Regex;
 use slog::{info, Logger};
 use tokio::time::{sleep, Duration};
 
-use crate::constants::{DEBUGGER_PORT, EXPLORER_PORT, OCAML_PORT, TEZEDGE_PORT};
+use crate::constants::{
+    CONTAINER_NAME_PREFIX, DEBUGGER_PORT, EXPLORER_PORT, OCAML_PORT, TEZEDGE_PORT,
+};
 use crate::image::{DeployMonitoringContainer, Explorer, Sandbox, TezedgeDebugger, TezedgeMemprof};
 use crate::node::{OcamlNode, TezedgeNode};
 
-pub async fn launch_stack(compose_file_path: &PathBuf, log: &Logger, tezedge_only: bool) {
+pub async fn launch_stack(
+    compose_file_path: &PathBuf,
+    log: &Logger,
+    tezedge_only: bool,
+    dry_run: bool,
+) {
     info!(log, "Tezedge explorer is starting");
-    start_with_compose(compose_file_path, Explorer::NAME, "explorer");
-    wait_for_start(&format!("http://localhost:{}", EXPLORER_PORT)).await;
+    start_with_compose(compose_file_path, Explorer::NAME, "explorer", log, dry_run).await;
+    wait_for_start(&format!("http://localhost:{}", EXPLORER_PORT), dry_run).await;
     info!(log, "Tezedge explorer is running");
 
     info!(log, "Debugger is starting");
-    start_with_compose(compose_file_path, TezedgeDebugger::NAME, "tezedge-debugger");
-    wait_for_start(&format!("http://localhost:{}/v2/log", DEBUGGER_PORT)).await;
+    start_with_compose(
+        compose_file_path,
+        TezedgeDebugger::NAME,
+        "tezedge-debugger",
+        log,
+        dry_run,
+    )
+    .await;
+    wait_for_start(
+        &format!("http://localhost:{}/v2/log", DEBUGGER_PORT),
+        dry_run,
+    )
+    .await;
     info!(log, "Debugger is running");
 
     info!(log, "Memprof is starting");
-    start_with_compose(compose_file_path, TezedgeMemprof::NAME, "tezedge-memprof");
+    start_with_compose(
+        compose_file_path,
+        TezedgeMemprof::NAME,
+        "tezedge-memprof",
+        log,
+        dry_run,
+    )
+    .await;
     info!(log, "Memprof is running");
 
     info!(log, "Tezedge node is starting");
-    start_with_compose(compose_file_path, TezedgeNode::NAME, "tezedge-node");
-    wait_for_start(&format!(
-        "http://localhost:{}/chains/main/blocks/head/header",
-        TEZEDGE_PORT
-    ))
+    start_with_compose(
+        compose_file_path,
+        TezedgeNode::NAME,
+        "tezedge-node",
+        log,
+        dry_run,
+    )
+    .await;
+    wait_for_start(
+        &format!(
+            "http://localhost:{}/chains/main/blocks/head/header",
+            TEZEDGE_PORT
+        ),
+        dry_run,
+    )
     .await;
     info!(log, "Tezedge node is running");
 
     if !tezedge_only {
         info!(log, "Ocaml node is starting");
-        start_with_compose(compose_file_path, OcamlNode::NAME, "ocaml-node");
-        wait_for_start(&format!(
-            "http://localhost:{}/chains/main/blocks/head/header",
-            OCAML_PORT
-        ))
+        start_with_compose(
+            compose_file_path,
+            OcamlNode::NAME,
+            "ocaml-node",
+            log,
+            dry_run,
+        )
+        .await;
+        wait_for_start(
+            &format!(
+                "http://localhost:{}/chains/main/blocks/head/header",
+                OCAML_PORT
+            ),
+            dry_run,
+        )
         .await;
         info!(log, "Ocaml node is running");
     }
 }
 
-pub async fn launch_sandbox(compose_file_path: &PathBuf, log: &Logger) {
+pub async fn launch_sandbox(compose_file_path: &PathBuf, log: &Logger, dry_run: bool) {
     info!(log, "Debugger is running");
-    start_with_compose(compose_file_path, TezedgeDebugger::NAME, "tezedge-debugger");
-    wait_for_start(&format!("http://localhost:{}/v2/log", DEBUGGER_PORT)).await;
+    start_with_compose(
+        compose_file_path,
+        TezedgeDebugger::NAME,
+        "tezedge-debugger",
+        log,
+        dry_run,
+    )
+    .await;
+    wait_for_start(
+        &format!("http://localhost:{}/v2/log", DEBUGGER_PORT),
+        dry_run,
+    )
+    .await;
     info!(log, "Debugger is running");
 
     info!(log, "Memprof is starting");
-    start_with_compose(compose_file_path, TezedgeMemprof::NAME, "tezedge-memprof");
+    start_with_compose(
+        compose_file_path,
+        TezedgeMemprof::NAME,
+        "tezedge-memprof",
+        log,
+        dry_run,
+    )
+    .await;
     info!(log, "Memprof is running");
 
     info!(log, "Sandbox launcher starting");
-    start_with_compose(compose_file_path, Sandbox::NAME, "tezedge-sandbox");
-    wait_for_start("http://localhost:3030/list_nodes").await;
+    start_with_compose(
+        compose_file_path,
+        Sandbox::NAME,
+        "tezedge-sandbox",
+        log,
+        dry_run,
+    )
+    .await;
+    wait_for_start("http://localhost:3030/list_nodes", dry_run).await;
     info!(log, "Sandbox launcher running");
 }
 
@@ -68,10 +140,11 @@ pub async fn restart_stack(
     log: &Logger,
     cleanup_data: bool,
     tezedge_only: bool,
+    dry_run: bool,
 ) {
-    stop_with_compose(compose_file_path);
-    cleanup_docker(cleanup_data);
-    launch_stack(compose_file_path, log, tezedge_only).await;
+    stop_with_compose(compose_file_path, log, dry_run).await;
+    cleanup_docker(cleanup_data, log, dry_run).await;
+    launch_stack(compose_file_path, log, tezedge_only, dry_run).await;
 }
 
 pub async fn shutdown_and_update(
@@ -79,97 +152,253 @@ pub async fn shutdown_and_update(
     log: &Logger,
     cleanup_data: bool,
     tezedge_only: bool,
+    dry_run: bool,
 ) {
-    stop_with_compose(compose_file_path);
-    cleanup_docker_system();
-    update_with_compose(compose_file_path);
-    restart_stack(compose_file_path, log, cleanup_data, tezedge_only).await;
+    stop_with_compose(compose_file_path, log, dry_run).await;
+    cleanup_docker_system(log, dry_run).await;
+    update_with_compose(compose_file_path, log, dry_run).await;
+    restart_stack(compose_file_path, log, cleanup_data, tezedge_only, dry_run).await;
 }
 
-pub async fn restart_sandbox(compose_file_path: &PathBuf, log: &Logger) {
-    stop_with_compose(compose_file_path);
-    cleanup_volumes();
-    launch_sandbox(compose_file_path, log).await;
+pub async fn restart_sandbox(compose_file_path: &PathBuf, log: &Logger, dry_run: bool) {
+    stop_with_compose(compose_file_path, log, dry_run).await;
+    cleanup_volumes(log, dry_run).await;
+    launch_sandbox(compose_file_path, log, dry_run).await;
 }
 
-pub async fn shutdown_and_update_sandbox(compose_file_path: &PathBuf, log: &Logger, cleanup: bool) {
-    stop_with_compose(compose_file_path);
-    cleanup_docker(cleanup);
-    update_with_compose(compose_file_path);
-    restart_sandbox(compose_file_path, log).await;
+pub async fn shutdown_and_update_sandbox(
+    compose_file_path: &PathBuf,
+    log: &Logger,
+    cleanup: bool,
+    dry_run: bool,
+) {
+    stop_with_compose(compose_file_path, log, dry_run).await;
+    cleanup_docker(cleanup, log, dry_run).await;
+    update_with_compose(compose_file_path, log, dry_run).await;
+    restart_sandbox(compose_file_path, log, dry_run).await;
 }
 
-pub fn cleanup_docker(cleanup_data: bool) {
-    cleanup_docker_system();
+pub async fn cleanup_docker(cleanup_data: bool, log: &Logger, dry_run: bool) {
+    cleanup_docker_system(log, dry_run).await;
     if cleanup_data {
-        cleanup_volumes();
+        cleanup_volumes(log, dry_run).await;
     }
 }
 
-pub fn start_with_compose(
+pub async fn start_with_compose(
     compose_file_path: &PathBuf,
     container_name: &str,
     service_ports_name: &str,
+    log: &Logger,
+    dry_run: bool,
 ) -> Output {
-    Command::new("docker-compose")
-        .args(&[
-            "-f",
-            compose_file_path
-                .to_str()
-                .unwrap_or("apps/deploy_monitoring/docker-compose.deploy.latest.yml"),
-            "run",
-            "-d",
-            "--name",
-            container_name,
-            "--service-ports",
-            service_ports_name,
-        ])
-        .output()
-        .expect("failed to execute docker-compose command")
-}
-
-pub fn stop_with_compose(compose_file_path: &PathBuf) -> Output {
-    Command::new("docker-compose")
-        .args(&[
-            "-f",
-            compose_file_path
-                .to_str()
-                .unwrap_or("apps/deploy_monitoring/docker-compose.deploy.latest.yml"),
-            "down",
-        ])
-        .output()
-        .expect("failed to execute docker-compose command")
-}
-
-pub fn update_with_compose(compose_file_path: &PathBuf) -> Output {
-    Command::new("docker-compose")
-        .args(&[
-            "-f",
-            compose_file_path
-                .to_str()
-                .unwrap_or("apps/deploy_monitoring/docker-compose.deploy.latest.yml"),
-            "pull",
-        ])
-        .output()
-        .expect("failed to execute docker-compose command")
+    let mut command = Command::new("docker-compose");
+    command.args(&[
+        "-f",
+        compose_file_path
+            .to_str()
+            .unwrap_or("apps/deploy_monitoring/docker-compose.deploy.latest.yml"),
+        "run",
+        "-d",
+        "--name",
+        container_name,
+        "--service-ports",
+        service_ports_name,
+    ]);
+    execute(command, log, dry_run).await
 }
 
-pub fn cleanup_volumes() -> Output {
-    Command::new("docker")
-        .args(&["volume", "prune", "-f"])
-        .output()
-        .expect("failed to execute docker command")
+pub async fn stop_with_compose(compose_file_path: &PathBuf, log: &Logger, dry_run: bool) -> Output {
+    let mut command = Command::new("docker-compose");
+    command.args(&[
+        "-f",
+        compose_file_path
+            .to_str()
+            .unwrap_or("apps/deploy_monitoring/docker-compose.deploy.latest.yml"),
+        "down",
+    ]);
+    execute(command, log, dry_run).await
+}
+
+pub async fn update_with_compose(
+    compose_file_path: &PathBuf,
+    log: &Logger,
+    dry_run: bool,
+) -> Output {
+    let mut command = Command::new("docker-compose");
+    command.args(&[
+        "-f",
+        compose_file_path
+            .to_str()
+            .unwrap_or("apps/deploy_monitoring/docker-compose.deploy.latest.yml"),
+        "pull",
+    ]);
+    execute(command, log, dry_run).await
+}
+
+pub async fn cleanup_volumes(log: &Logger, dry_run: bool) -> Output {
+    let mut command = Command::new("docker");
+    command.args(&["volume", "prune", "-f"]);
+    execute(command, log, dry_run).await
 }
 
-pub fn cleanup_docker_system() -> Output {
-    Command::new("docker")
-        .args(&["system", "prune", "-a", "-f"])
+pub async fn cleanup_docker_system(log: &Logger, dry_run: bool) -> Output {
+    let mut command = Command::new("docker");
+    command.args(&["system", "prune", "-a", "-f"]);
+    execute(command, log, dry_run).await
+}
+
+/// Lists all (also stopped) containers launched by deploy monitoring
+pub async fn list_residual_containers(
+    log: &Logger,
+    dry_run: bool,
+) -> Result<Vec<String>, failure::Error> {
+    let mut command = Command::new("docker");
+    command.args(&["ps", "-a", "--format", "{{.Names}}"]);
+    let output = execute(command, log, dry_run).await;
+    if !output.status.success() {
+        failure::bail!(
+            "Failed to list containers: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(filter_own_containers(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Lists names of the running containers, matching the pattern
+pub async fn list_running_containers_matching(
+    pattern: &Regex,
+    log: &Logger,
+    dry_run: bool,
+) -> Result<Vec<String>, failure::Error> {
+    let mut command = Command::new("docker");
+    command.args(&["ps", "--format", "{{.Names}}"]);
+    let output = execute(command, log, dry_run).await;
+    if !output.status.success() {
+        failure::bail!(
+            "Failed to list containers: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(filter_containers_matching(
+        &String::from_utf8_lossy(&output.stdout),
+        pattern,
+    ))
+}
+
+/// Starts again the stopped container (with its original configuration)
+pub async fn restart_container(container_name: &str, log: &Logger, dry_run: bool) -> Output {
+    let mut command = Command::new("docker");
+    command.args(&["start", container_name]);
+    execute(command, log, dry_run).await
+}
+
+pub async fn remove_containers(container_names: &[String], log: &Logger, dry_run: bool) -> Output {
+    let mut command = Command::new("docker");
+    command.args(&["rm", "-f", "-v"]).args(container_names);
+    execute(command, log, dry_run).await
+}
+
+/// Executes the command or just logs it in dry-run mode (with successful empty output),
+/// the command is awaited without blocking the runtime, so the caller can time out
+async fn execute(command: Command, log: &Logger, dry_run: bool) -> Output {
+    if dry_run {
+        info!(log, "[DRY-RUN] Would execute: {:?}", command);
+        return Output {
+            status: ExitStatus::from_raw(0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+    }
+    let description = format!("{:?}", command);
+    tokio::process::Command::from(command)
         .output()
-        .expect("failed to execute docker command")
+        .await
+        .unwrap_or_else(|e| panic!("failed to execute command {}, reason: {}", description, e))
 }
 
-async fn wait_for_start(url: &str) {
+/// Picks our containers from the `docker ps --format {{.Names}}` output
+fn filter_own_containers(names: &str) -> Vec<String> {
+    names
+        .lines()
+        .map(str::trim)
+        .filter(|name| name.starts_with(CONTAINER_NAME_PREFIX))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Picks containers matching the pattern from the `docker ps --format {{.Names}}` output, sorted by name
+fn filter_containers_matching(names: &str, pattern: &Regex) -> Vec<String> {
+    let mut matching: Vec<String> = names
+        .lines()
+        .map(str::trim)
+        .filter(|name| pattern.is_match(name))
+        .map(str::to_string)
+        .collect();
+    matching.sort();
+    matching
+}
+
+async fn wait_for_start(url: &str, dry_run: bool) {
+    // nothing was started
+    if dry_run {
+        return;
+    }
     while reqwest::get(url).await.is_err() {
         sleep(Duration::from_millis(1000)).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration::container_name_regex;
+
+    use super::*;
+
+    #[test]
+    fn test_filter_own_containers() {
+        let names = "deploy-monitoring-tezedge-debugger\n\
+                     some-other-container\n\
+                     tezedge-deploy-monitoring-node\n\
+                     deploy-monitoring-tezedge-sandbox-launcher\n\
+                     \n";
+
+        assert_eq!(
+            vec![
+                "deploy-monitoring-tezedge-debugger".to_string(),
+                "deploy-monitoring-tezedge-sandbox-launcher".to_string(),
+            ],
+            filter_own_containers(names)
+        );
+        assert!(filter_own_containers("").is_empty());
+    }
+
+    #[test]
+    fn test_filter_containers_matching() {
+        let names = "custom-tezedge-node-2\n\
+                     custom-tezedge-node-1\n\
+                     custom-tezedge-node-1-debugger\n\
+                     custom-ocaml-node\n\
+                     \n";
+
+        let pattern = container_name_regex("custom-tezedge-node-[0-9]+").unwrap();
+        assert_eq!(
+            vec![
+                "custom-tezedge-node-1".to_string(),
+                "custom-tezedge-node-2".to_string(),
+            ],
+            filter_containers_matching(names, &pattern)
+        );
+
+        // plain name has to match the whole container name
+        let pattern = container_name_regex("custom-ocaml-node").unwrap();
+        assert_eq!(
+            vec!["custom-ocaml-node".to_string()],
+            filter_containers_matching(names, &pattern)
+        );
+        let pattern = container_name_regex("ocaml").unwrap();
+        assert!(filter_containers_matching(names, &pattern).is_empty());
+    }
+}
@@ -2,10 +2,12 @@
 // SPDX-License-Identifier: MIT
 
 use std::fmt;
+use std::io;
+use std::path::Path;
 
 use getset::{CopyGetters, Getters};
 // use merge::Merge;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Debug)]
 pub struct DiskSpaceData {
@@ -80,7 +82,36 @@ impl fmt::Display for NodeInfo {
     }
 }
 
-#[derive(Serialize, PartialEq, Clone, Debug, Default, CopyGetters)]
+/// Space on the filesystem holding the node data directory (in bytes)
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default, CopyGetters)]
+pub struct DataDirUsage {
+    #[get_copy = "pub(crate)"]
+    total: u64,
+
+    #[get_copy = "pub(crate)"]
+    used: u64,
+
+    /// Space available to the node (without the space reserved for root)
+    #[get_copy = "pub(crate)"]
+    free: u64,
+}
+
+impl DataDirUsage {
+    pub fn new(total: u64, used: u64, free: u64) -> Self {
+        Self { total, used, free }
+    }
+
+    pub fn measure<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let total = fs2::total_space(&path)?;
+        Ok(Self {
+            total,
+            used: total.saturating_sub(fs2::free_space(&path)?),
+            free: fs2::available_space(&path)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default, CopyGetters)]
 pub struct OcamlDiskData {
     #[get_copy = "pub(crate)"]
     debugger: u64,
@@ -153,7 +184,7 @@ impl fmt::Display for DiskData {
     }
 }
 
-#[derive(Serialize, PartialEq, Clone, Debug, Default, CopyGetters)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default, CopyGetters)]
 pub struct TezedgeDiskData {
     #[get_copy = "pub(crate)"]
     context_irmin: u64,
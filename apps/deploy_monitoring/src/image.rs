@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT
 
 use async_trait::async_trait;
+use getset::Getters;
 use shiplift::{
     rep::{ContainerDetails, ImageDetails},
     Docker,
@@ -19,6 +20,41 @@ pub trait DeployMonitoringContainer {
     }
 }
 
+/// Image pinned by the digest, the container is expected to run from,
+/// pinned image is not updated by the deploy monitor
+#[derive(Clone, Debug, Getters, PartialEq)]
+pub struct Image {
+    #[get = "pub(crate)"]
+    container_name: String,
+
+    /// `sha256:<hash>` or `<repository>@sha256:<hash>`
+    #[get = "pub(crate)"]
+    digest: String,
+}
+
+impl Image {
+    pub fn pinned(container_name: String, digest: String) -> Self {
+        Self {
+            container_name,
+            digest,
+        }
+    }
+
+    /// Checks if the image, the container `T` is running from, has the pinned digest
+    pub async fn matches_running<T: DeployMonitoringContainer + Sync + Send>(
+        &self,
+        docker: &Docker,
+    ) -> Result<bool, failure::Error> {
+        // image id of the running container, not the one the tag is pointing to now
+        let ContainerDetails { image, .. } = docker.containers().get(T::NAME).inspect().await?;
+        let ImageDetails { repo_digests, .. } = docker.images().get(&image).inspect().await?;
+
+        Ok(repo_digests.unwrap_or_default().iter().any(|repo_digest| {
+            repo_digest == &self.digest || repo_digest.rsplit('@').next() == Some(&self.digest)
+        }))
+    }
+}
+
 pub async fn remote_hash<T: DeployMonitoringContainer + Sync + Send>(
 ) -> Result<String, failure::Error> {
     let image = T::image().await?;
@@ -5,8 +5,10 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use slog::{info, Drain, Level, Logger};
+use slog::{info, warn, Drain, Level, Logger};
+use slog_async::{Async, AsyncGuard, OverflowStrategy};
 use tokio::signal;
+use tokio::time::{timeout, Duration};
 
 mod configuration;
 mod constants;
@@ -15,10 +17,11 @@ mod display_info;
 mod image;
 mod monitors;
 mod node;
+mod pagerduty;
 mod rpc;
 mod slack;
 
-use crate::configuration::DeployMonitoringEnvironment;
+use crate::configuration::{DeployMonitoringEnvironment, LogFormat};
 use crate::monitors::resource::ResourceUtilization;
 use crate::monitors::{shutdown_and_cleanup, spawn_node_stack, spawn_sandbox};
 
@@ -27,20 +30,30 @@ async fn main() {
     // parse and validate program arguments
     let env = configuration::DeployMonitoringEnvironment::from_args();
 
-    // create an slog logger
-    let log = create_logger(env.log_level);
+    // create an slog logger, the guard flushes the async logger when dropped
+    let (log, log_guard) = create_logger(env.log_level, env.log_format);
 
     let DeployMonitoringEnvironment {
         slack_configuration,
         is_sandbox,
+        dry_run,
         ..
     } = env.clone();
 
+    if dry_run {
+        warn!(
+            log,
+            "Running in dry-run mode, docker commands are only logged, not executed"
+        );
+    }
+
     let slack_server = slack_configuration.map(|cfg| {
         slack::SlackServer::new(
             cfg.slack_url,
             cfg.slack_token,
             cfg.slack_channel_name,
+            constants::SLACK_MAX_RETRIES,
+            dry_run,
             log.clone(),
         )
     });
@@ -68,30 +81,63 @@ async fn main() {
         drop(handle);
     }
 
-    // cleanup
+    // cleanup, do not wait forever, when slack or docker daemon does not respond
     info!(log, "Cleaning up containers");
-    shutdown_and_cleanup(
-        &env.compose_file_path,
-        slack_server,
-        &log,
-        env.cleanup_volumes,
+    let residual_containers = match timeout(
+        Duration::from_secs(env.shutdown_timeout),
+        shutdown_and_cleanup(
+            &env.compose_file_path,
+            slack_server,
+            &log,
+            env.cleanup_volumes,
+            env.dry_run,
+        ),
     )
     .await
-    .expect("Cleanup failed");
-    info!(log, "Shutdown complete");
+    {
+        Ok(result) => result.expect("Cleanup failed"),
+        Err(_) => {
+            warn!(
+                log,
+                "Cleanup did not finish in {}s, forcing exit", env.shutdown_timeout
+            );
+            // exit does not run the destructors, so flush the pending log records first
+            drop(log_guard);
+            std::process::exit(1);
+        }
+    };
+    info!(
+        log,
+        "Shutdown complete, {} residual containers", residual_containers
+    );
 }
 
-/// Creates a slog Logger
-fn create_logger(level: Level) -> Logger {
-    let drain = slog_async::Async::new(
-        slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
-            .build()
-            .fuse(),
-    )
-    .chan_size(32768)
-    .overflow_strategy(slog_async::OverflowStrategy::Block)
-    .build()
-    .filter_level(level)
-    .fuse();
-    Logger::root(drain, slog::o!())
+/// Creates a slog Logger with the guard of its async drain
+fn create_logger(level: Level, format: LogFormat) -> (Logger, AsyncGuard) {
+    let (drain, guard) = match format {
+        LogFormat::Simple => async_drain(
+            slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
+                .build()
+                .fuse(),
+        ),
+        LogFormat::Json => async_drain(
+            slog_json::Json::new(std::io::stdout())
+                .add_default_keys()
+                .build()
+                .fuse(),
+        ),
+    };
+    let drain = drain.filter_level(level).fuse();
+    (Logger::root(drain, slog::o!()), guard)
+}
+
+/// Moves the logging of the `drain` to a separate thread
+fn async_drain<D>(drain: D) -> (Async, AsyncGuard)
+where
+    D: Drain<Ok = (), Err = slog::Never> + Send + 'static,
+{
+    Async::new(drain)
+        .chan_size(32768)
+        .overflow_strategy(OverflowStrategy::Block)
+        .build_with_guard()
 }
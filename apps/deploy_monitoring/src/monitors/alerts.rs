@@ -1,7 +1,8 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashSet, VecDeque};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
@@ -11,9 +12,11 @@ use slog::{crit, Logger};
 
 use shell::stats::memory::ProcessMemoryStats;
 
-use crate::configuration::AlertThresholds;
+use crate::configuration::{AlertThresholds, FreeSpaceThreshold};
 use crate::constants::TEZEDGE_VOLUME_PATH;
-use crate::display_info::NodeInfo;
+use crate::display_info::{DataDirUsage, NodeInfo};
+use crate::monitors::resource::{MonitoredNode, ResourceUtilizationStorage};
+use crate::pagerduty::{PagerDutyNotifier, Severity};
 use crate::slack::SlackServer;
 use crate::ResourceUtilization;
 
@@ -33,6 +36,21 @@ pub struct Alerts {
 
     #[get = "pub(crate)"]
     ocaml_thresholds: AlertThresholds,
+
+    /// Memory ceiling in bytes common for all nodes, 0 means disabled
+    #[get = "pub(crate)"]
+    memory_ceiling: u64,
+
+    /// Threshold in % for the average cpu usage of all nodes together, None means disabled
+    #[get = "pub(crate)"]
+    combined_cpu_threshold: Option<u64>,
+
+    /// Count of the last measurements, the combined cpu usage is averaged over
+    #[get = "pub(crate)"]
+    combined_cpu_window: usize,
+
+    /// Critical alerts are sent to pagerduty as well, when configured
+    pagerduty: Option<PagerDutyNotifier>,
 }
 
 #[derive(Clone, Debug, Eq)]
@@ -48,7 +66,7 @@ pub struct MonitorAlert {
 
 impl PartialEq for MonitorAlert {
     fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind
+        self.kind == other.kind && self.node_tag == other.node_tag
     }
 }
 
@@ -81,14 +99,29 @@ impl MonitorAlert {
 }
 
 impl Alerts {
-    pub fn new(tezedge_thresholds: AlertThresholds, ocaml_thresholds: AlertThresholds) -> Self {
+    pub fn new(
+        tezedge_thresholds: AlertThresholds,
+        ocaml_thresholds: AlertThresholds,
+        memory_ceiling: u64,
+        combined_cpu_threshold: Option<u64>,
+        combined_cpu_window: usize,
+    ) -> Self {
         Self {
             inner: HashSet::default(),
             tezedge_thresholds,
             ocaml_thresholds,
+            memory_ceiling,
+            combined_cpu_threshold,
+            combined_cpu_window,
+            pagerduty: None,
         }
     }
 
+    /// Sends the critical alerts to pagerduty besides the slack
+    pub fn with_pagerduty(self, pagerduty: Option<PagerDutyNotifier>) -> Self {
+        Self { pagerduty, ..self }
+    }
+
     pub fn assign_resource_alert(
         &mut self,
         node_tag: &str,
@@ -258,7 +291,7 @@ impl Alerts {
             Some(time),
             head_info,
         );
-        send_resource_alert(node_tag, slack, res).await?;
+        send_resource_alert(node_tag, slack, self.pagerduty.as_ref(), res).await?;
         Ok(())
     }
 
@@ -270,33 +303,45 @@ impl Alerts {
         time: i64,
         last_measurement: ResourceUtilization,
     ) -> Result<(), failure::Error> {
-        let ram_total = if node_tag == "tezedge" {
-            last_measurement.memory().node().resident_mem()
-                + last_measurement
-                    .memory()
-                    .protocol_runners()
-                    .as_ref()
-                    .unwrap_or(&ProcessMemoryStats::default())
-                    .resident_mem()
-        } else {
-            last_measurement.memory().node().resident_mem()
-                + last_measurement
-                    .memory()
-                    .validators()
-                    .as_ref()
-                    .unwrap_or(&ProcessMemoryStats::default())
-                    .resident_mem()
-        };
+        let ram_total = total_resident_memory(&last_measurement);
         let res = self.assign_resource_alert(
             node_tag,
             AlertKind::Memory,
             thresholds.memory,
-            ram_total as u64,
+            ram_total,
+            Some(time),
+            last_measurement.head_info().clone(),
+        );
+
+        send_resource_alert(node_tag, slack, self.pagerduty.as_ref(), res).await?;
+
+        Ok(())
+    }
+
+    /// Checks the memory ceiling common for all nodes, alert is sent only once, until the memory drops bellow the ceiling
+    pub async fn check_memory_ceiling_alert(
+        &mut self,
+        node_tag: &str,
+        slack: Option<&SlackServer>,
+        time: i64,
+        last_measurement: ResourceUtilization,
+    ) -> Result<(), failure::Error> {
+        // 0 means, that the check is disabled
+        if self.memory_ceiling == 0 {
+            return Ok(());
+        }
+
+        let ram_total = total_resident_memory(&last_measurement);
+        let res = self.assign_resource_alert(
+            node_tag,
+            AlertKind::MemoryCeiling,
+            self.memory_ceiling,
+            ram_total,
             Some(time),
             last_measurement.head_info().clone(),
         );
 
-        send_resource_alert(node_tag, slack, res).await?;
+        send_resource_alert(node_tag, slack, self.pagerduty.as_ref(), res).await?;
 
         Ok(())
     }
@@ -322,7 +367,77 @@ impl Alerts {
             head_info,
         );
 
-        send_resource_alert(node_tag, slack, res).await?;
+        send_resource_alert(node_tag, slack, self.pagerduty.as_ref(), res).await?;
+
+        Ok(())
+    }
+
+    /// Alerts, when the cpu usage of all the nodes together stays above the threshold for the whole window
+    pub async fn check_combined_cpu_alert(
+        &mut self,
+        slack: Option<&SlackServer>,
+        time: i64,
+        nodes: &[(MonitoredNode, ResourceUtilizationStorage)],
+    ) -> Result<(), failure::Error> {
+        let threshold = match self.combined_cpu_threshold {
+            Some(threshold) => threshold,
+            None => return Ok(()),
+        };
+
+        let average = {
+            let storages = nodes
+                .iter()
+                .map(|(_, storage)| storage.read())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| failure::format_err!("Resource lock poisoned, reason => {}", e))?;
+            combined_cpu_average(
+                &storages
+                    .iter()
+                    .map(|storage| &**storage)
+                    .collect::<Vec<_>>(),
+                self.combined_cpu_window,
+            )
+        };
+
+        // not enough measurements yet
+        let average = match average {
+            Some(average) => average,
+            None => return Ok(()),
+        };
+
+        let res = self.assign_resource_alert(
+            COMBINED_NODE_TAG,
+            AlertKind::CombinedCpu,
+            threshold,
+            average,
+            Some(time),
+            NodeInfo::default(),
+        );
+
+        send_resource_alert(COMBINED_NODE_TAG, slack, self.pagerduty.as_ref(), res).await?;
+
+        Ok(())
+    }
+
+    /// Alerts, when the free space on the filesystem of the node data directory drops bellow the threshold
+    pub async fn check_data_dir_alert(
+        &mut self,
+        threshold: &FreeSpaceThreshold,
+        slack: Option<&SlackServer>,
+        time: i64,
+        usage: DataDirUsage,
+    ) -> Result<(), failure::Error> {
+        let (used_threshold, used) = data_dir_alert_values(threshold, &usage);
+        let res = self.assign_resource_alert(
+            DATA_DIR_NODE_TAG,
+            AlertKind::DataDir,
+            used_threshold,
+            used,
+            Some(time),
+            NodeInfo::default(),
+        );
+
+        send_resource_alert(DATA_DIR_NODE_TAG, slack, self.pagerduty.as_ref(), res).await?;
 
         Ok(())
     }
@@ -346,6 +461,23 @@ impl Alerts {
             log,
             head_info.clone(),
         );
+        if let Some(pagerduty) = self.pagerduty.as_ref() {
+            match &alert_result {
+                AlertResult::Incresed(alert) => {
+                    pagerduty
+                        .trigger(
+                            &dedup_key(node_tag, &alert.kind),
+                            &format!("Node [{}] is stuck on level: {}", node_tag, alert.value),
+                            alert.kind.severity(),
+                        )
+                        .await?;
+                }
+                AlertResult::Decreased(_, alert) if alert.reported => {
+                    pagerduty.resolve(&dedup_key(node_tag, &alert.kind)).await?;
+                }
+                _ => (/* Do not alert on unchanged */),
+            }
+        }
         if let Some(slack_server) = slack {
             match alert_result {
                 AlertResult::Incresed(alert) => {
@@ -422,36 +554,139 @@ impl AlertLevel {
 pub enum AlertKind {
     Disk,
     Memory,
+    MemoryCeiling,
     Cpu,
+    CombinedCpu,
+    DataDir,
     NodeStucked,
 }
 
+impl AlertKind {
+    /// Severity of the pagerduty incident triggered by the critical alert
+    pub fn severity(&self) -> Severity {
+        match *self {
+            AlertKind::Disk | AlertKind::DataDir | AlertKind::NodeStucked => Severity::Critical,
+            AlertKind::Memory | AlertKind::MemoryCeiling => Severity::Error,
+            AlertKind::Cpu | AlertKind::CombinedCpu => Severity::Warning,
+        }
+    }
+}
+
 impl fmt::Display for AlertKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             AlertKind::Disk => write!(f, "TOTAL Disk space"),
             AlertKind::Memory => write!(f, "Memory"),
+            AlertKind::MemoryCeiling => write!(f, "Memory ceiling"),
             AlertKind::Cpu => write!(f, "CPU"),
+            AlertKind::CombinedCpu => write!(f, "Combined CPU"),
+            AlertKind::DataDir => write!(f, "Data directory disk usage"),
             AlertKind::NodeStucked => write!(f, "Synchronization"),
         }
     }
 }
 
+/// Node tag used for the alerts computed from all the monitored nodes
+const COMBINED_NODE_TAG: &str = "all nodes";
+
+/// Node tag used for the alerts on the node data directory
+const DATA_DIR_NODE_TAG: &str = "data dir";
+
+/// Free space drops bellow the threshold, when the space not available to the node
+/// (total - free) reaches the total space reduced by the min free space
+///
+/// Returns (threshold, value) of the not available space, as alert is raised on the value reaching the threshold
+fn data_dir_alert_values(threshold: &FreeSpaceThreshold, usage: &DataDirUsage) -> (u64, u64) {
+    let total = usage.total();
+    (
+        total.saturating_sub(threshold.min_free_bytes(total)),
+        total.saturating_sub(usage.free()),
+    )
+}
+
+/// Average of the cpu usage (node and its protocol runners) summed for all the nodes over the last `window` measurements
+///
+/// Returns None, if any of the nodes does not have enough measurements
+fn combined_cpu_average(storages: &[&VecDeque<ResourceUtilization>], window: usize) -> Option<u64> {
+    if window == 0 || storages.iter().any(|storage| storage.len() < window) {
+        return None;
+    }
+
+    // the latest measurements are in the front
+    let total: i64 = storages
+        .iter()
+        .flat_map(|storage| storage.iter().take(window))
+        .map(|measurement| {
+            (measurement.cpu().node() + measurement.cpu().protocol_runners().unwrap_or(0)) as i64
+        })
+        .sum();
+
+    Some(cmp::max(total, 0) as u64 / window as u64)
+}
+
+/// Resident memory of the node together with its child processes (protocol runners/validators)
+fn total_resident_memory(last_measurement: &ResourceUtilization) -> u64 {
+    // tezedge nodes have protocol_runners, ocaml nodes have validators
+    let children = last_measurement
+        .memory()
+        .protocol_runners()
+        .as_ref()
+        .or_else(|| last_measurement.memory().validators().as_ref());
+
+    (last_measurement.memory().node().resident_mem()
+        + children
+            .unwrap_or(&ProcessMemoryStats::default())
+            .resident_mem()) as u64
+}
+
+/// Key of the pagerduty incident, so the alert of the same kind on the same node resolves the incident it triggered
+fn dedup_key(node_tag: &str, kind: &AlertKind) -> String {
+    format!("{}-{:?}", node_tag, kind)
+}
+
+fn format_alert_value(alert: &MonitorAlert) -> String {
+    match alert.kind {
+        AlertKind::Cpu | AlertKind::CombinedCpu => format!("{}%", alert.value),
+        AlertKind::Disk | AlertKind::Memory | AlertKind::MemoryCeiling | AlertKind::DataDir => {
+            format!("{}MB", alert.value / 1024 / 1024)
+        }
+        AlertKind::NodeStucked => format!("{} level", alert.value),
+    }
+}
+
 async fn send_resource_alert(
     node_tag: &str,
     slack: Option<&SlackServer>,
+    pagerduty: Option<&PagerDutyNotifier>,
     alert_result: AlertResult,
 ) -> Result<(), failure::Error> {
+    if let Some(pagerduty) = pagerduty {
+        match &alert_result {
+            AlertResult::Incresed(alert) if alert.level == AlertLevel::Critical => {
+                pagerduty
+                    .trigger(
+                        &dedup_key(node_tag, &alert.kind),
+                        &format!(
+                            "[{}] - {} surpassed the defined threshold! Current value: {}",
+                            node_tag,
+                            alert.kind,
+                            format_alert_value(alert)
+                        ),
+                        alert.kind.severity(),
+                    )
+                    .await?;
+            }
+            AlertResult::Decreased(AlertLevel::Critical, alert) => {
+                pagerduty.resolve(&dedup_key(node_tag, &alert.kind)).await?;
+            }
+            _ => (/* Do nothing */),
+        }
+    }
+
     if let Some(slack_server) = slack {
         match alert_result {
             AlertResult::Incresed(alert) => {
-                let current_value = match alert.kind {
-                    AlertKind::Cpu => format!("{}%", alert.value),
-                    AlertKind::Disk | AlertKind::Memory => {
-                        format!("{}MB", alert.value / 1024 / 1024)
-                    }
-                    AlertKind::NodeStucked => format!("{} level", alert.value),
-                };
+                let current_value = format_alert_value(&alert);
                 if alert.level == AlertLevel::Critical {
                     slack_server
                         .send_message(&format!(
@@ -465,13 +700,7 @@ async fn send_resource_alert(
                 }
             }
             AlertResult::Decreased(previous_alert, alert) => {
-                let current_value = match alert.kind {
-                    AlertKind::Cpu => format!("{}%", alert.value),
-                    AlertKind::Disk | AlertKind::Memory => {
-                        format!("{}MB", alert.value / 1024 / 1024)
-                    }
-                    AlertKind::NodeStucked => format!("{} level", alert.value),
-                };
+                let current_value = format_alert_value(&alert);
                 if previous_alert == AlertLevel::Critical {
                     slack_server
                     .send_message(&format!(
@@ -492,6 +721,8 @@ async fn send_resource_alert(
 
 #[cfg(test)]
 mod tests {
+    use crate::monitors::resource::ResourceUtilizationBuilder;
+
     use super::*;
 
     #[test]
@@ -515,6 +746,9 @@ mod tests {
                 synchronization: 0,
                 cpu: Some(0),
             },
+            0,
+            None,
+            0,
         );
 
         alerts.assign_resource_alert(
@@ -584,6 +818,9 @@ mod tests {
                 synchronization: 0,
                 cpu: Some(0),
             },
+            0,
+            None,
+            0,
         );
 
         alerts.assign_resource_alert(
@@ -663,6 +900,153 @@ mod tests {
         assert_eq!(alerts.inner.len(), 0);
     }
 
+    #[test]
+    fn test_memory_ceiling_alert_reported_once() {
+        let node_tag = "ocaml";
+        let memory_ceiling = 10000;
+
+        let thresholds = AlertThresholds {
+            memory: 0,
+            disk: 0,
+            synchronization: 0,
+            cpu: None,
+        };
+        let mut alerts = Alerts::new(thresholds, thresholds, memory_ceiling, None, 0);
+
+        // crossing the ceiling is reported
+        let res = alerts.assign_resource_alert(
+            node_tag,
+            AlertKind::MemoryCeiling,
+            memory_ceiling,
+            10001,
+            None,
+            NodeInfo::default(),
+        );
+        assert!(matches!(res, AlertResult::Incresed(_)));
+
+        // still above the ceiling, should not be reported again
+        let res = alerts.assign_resource_alert(
+            node_tag,
+            AlertKind::MemoryCeiling,
+            memory_ceiling,
+            20000,
+            None,
+            NodeInfo::default(),
+        );
+        assert_eq!(res, AlertResult::Unchanged);
+
+        // back bellow the ceiling
+        let res = alerts.assign_resource_alert(
+            node_tag,
+            AlertKind::MemoryCeiling,
+            memory_ceiling,
+            5000,
+            None,
+            NodeInfo::default(),
+        );
+        assert!(matches!(
+            res,
+            AlertResult::Decreased(AlertLevel::Critical, _)
+        ));
+        assert!(!alerts.contains(AlertKind::MemoryCeiling, node_tag));
+
+        // crossing again is reported again
+        let res = alerts.assign_resource_alert(
+            node_tag,
+            AlertKind::MemoryCeiling,
+            memory_ceiling,
+            10001,
+            None,
+            NodeInfo::default(),
+        );
+        assert!(matches!(res, AlertResult::Incresed(_)));
+    }
+
+    fn cpu_measurement(node: i32, protocol_runners: Option<i32>) -> ResourceUtilization {
+        ResourceUtilizationBuilder::default()
+            .cpu(node, protocol_runners)
+            .build()
+    }
+
+    #[test]
+    fn test_combined_cpu_average() {
+        let window = 3;
+
+        // tezedge node with protocol runners and ocaml node, latest measurements are in the front
+        let tezedge: VecDeque<ResourceUtilization> = vec![
+            cpu_measurement(50, Some(10)),
+            cpu_measurement(40, Some(10)),
+            cpu_measurement(30, Some(10)),
+        ]
+        .into_iter()
+        .collect();
+        let ocaml: VecDeque<ResourceUtilization> = vec![
+            cpu_measurement(40, None),
+            cpu_measurement(50, None),
+            cpu_measurement(60, None),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(combined_cpu_average(&[&tezedge, &ocaml], window), Some(100));
+
+        // not enough measurements for the window
+        assert_eq!(combined_cpu_average(&[&tezedge, &ocaml], 4), None);
+    }
+
+    #[test]
+    fn test_combined_cpu_brief_spike_not_alerted() {
+        let threshold = 100;
+        let window = 4;
+        let thresholds = AlertThresholds {
+            memory: 0,
+            disk: 0,
+            synchronization: 0,
+            cpu: None,
+        };
+        let mut alerts = Alerts::new(thresholds, thresholds, 0, Some(threshold), window);
+
+        // one spike above the threshold, which drops back
+        let tezedge: VecDeque<ResourceUtilization> = vec![
+            cpu_measurement(20, Some(0)),
+            cpu_measurement(250, Some(50)),
+            cpu_measurement(20, Some(0)),
+            cpu_measurement(20, Some(0)),
+        ]
+        .into_iter()
+        .collect();
+        let average = combined_cpu_average(&[&tezedge], window).unwrap();
+        let res = alerts.assign_resource_alert(
+            COMBINED_NODE_TAG,
+            AlertKind::CombinedCpu,
+            threshold,
+            average,
+            None,
+            NodeInfo::default(),
+        );
+        assert_eq!(res, AlertResult::Unchanged);
+
+        // sustained usage above the threshold
+        let tezedge: VecDeque<ResourceUtilization> = vec![
+            cpu_measurement(100, Some(20)),
+            cpu_measurement(110, Some(0)),
+            cpu_measurement(90, Some(30)),
+            cpu_measurement(105, Some(10)),
+        ]
+        .into_iter()
+        .collect();
+        let average = combined_cpu_average(&[&tezedge], window).unwrap();
+        let res = alerts.assign_resource_alert(
+            COMBINED_NODE_TAG,
+            AlertKind::CombinedCpu,
+            threshold,
+            average,
+            None,
+            NodeInfo::default(),
+        );
+        assert!(matches!(res, AlertResult::Incresed(_)));
+    }
+
     #[test]
     fn test_multiple_allerts() {
         let node_tag = "tezedge";
@@ -687,6 +1071,9 @@ mod tests {
                 synchronization: 0,
                 cpu: Some(0),
             },
+            0,
+            None,
+            0,
         );
 
         alerts.assign_resource_alert(
@@ -762,6 +1149,9 @@ mod tests {
                 synchronization: 300,
                 cpu: Some(0),
             },
+            0,
+            None,
+            0,
         );
 
         let thresholds = alerts.tezedge_thresholds().clone();
@@ -839,6 +1229,42 @@ mod tests {
     }
 
     #[ignore]
+    #[test]
+    fn test_resource_alerts_per_container() {
+        let memory_threshold = 10000;
+        let thresholds = AlertThresholds {
+            memory: memory_threshold,
+            disk: 0,
+            synchronization: 0,
+            cpu: Some(0),
+        };
+        let mut alerts = Alerts::new(thresholds, thresholds, 0, None, 0);
+
+        for node_tag in &["tezedge-node", "tezedge-node-2"] {
+            alerts.assign_resource_alert(
+                node_tag,
+                AlertKind::Memory,
+                memory_threshold,
+                20000,
+                None,
+                NodeInfo::default(),
+            );
+        }
+        assert_eq!(alerts.inner.len(), 2);
+
+        // alert of one container does not resolve the alert of the other one
+        alerts.assign_resource_alert(
+            "tezedge-node",
+            AlertKind::Memory,
+            memory_threshold,
+            1000,
+            None,
+            NodeInfo::default(),
+        );
+        assert!(!alerts.contains(AlertKind::Memory, "tezedge-node"));
+        assert!(alerts.contains(AlertKind::Memory, "tezedge-node-2"));
+    }
+
     #[test]
     fn test_multiple_node_stuck_alert() {
         let node1_tag = "tezedge";
@@ -856,6 +1282,9 @@ mod tests {
                 synchronization: 300,
                 cpu: Some(0),
             },
+            0,
+            None,
+            0,
         );
 
         let thresholds = alerts.tezedge_thresholds().clone();
@@ -983,4 +1412,57 @@ mod tests {
         assert_eq!(alerts.inner.len(), 2);
         assert_eq!(res, AlertResult::Incresed(expected2));
     }
+
+    #[test]
+    fn test_data_dir_alert() {
+        let thresholds = AlertThresholds {
+            disk: 0,
+            memory: 0,
+            synchronization: 0,
+            cpu: None,
+        };
+        let mut alerts = Alerts::new(thresholds, thresholds, 0, None, 0);
+
+        assert_eq!(
+            "10%".parse::<FreeSpaceThreshold>(),
+            Ok(FreeSpaceThreshold::Percentage(10))
+        );
+        assert_eq!(
+            "1024".parse::<FreeSpaceThreshold>(),
+            Ok(FreeSpaceThreshold::Bytes(1024))
+        );
+        assert!("101%".parse::<FreeSpaceThreshold>().is_err());
+        assert!("ten".parse::<FreeSpaceThreshold>().is_err());
+
+        let mut assign = |threshold: FreeSpaceThreshold, free: u64| {
+            let (used_threshold, used) =
+                data_dir_alert_values(&threshold, &DataDirUsage::new(1000, 1000 - free, free));
+            alerts.assign_resource_alert(
+                DATA_DIR_NODE_TAG,
+                AlertKind::DataDir,
+                used_threshold,
+                used,
+                None,
+                NodeInfo::default(),
+            )
+        };
+
+        // 10% of 1000 bytes
+        assert_eq!(
+            assign(FreeSpaceThreshold::Percentage(10), 500),
+            AlertResult::Unchanged
+        );
+        assert!(matches!(
+            assign(FreeSpaceThreshold::Percentage(10), 50),
+            AlertResult::Incresed(MonitorAlert {
+                level: AlertLevel::Critical,
+                ..
+            })
+        ));
+        assert!(matches!(
+            assign(FreeSpaceThreshold::Bytes(40), 50),
+            AlertResult::Decreased(AlertLevel::Critical, _)
+        ));
+        assert!(!alerts.contains(AlertKind::DataDir, DATA_DIR_NODE_TAG));
+    }
 }
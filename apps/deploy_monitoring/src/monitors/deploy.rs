@@ -1,6 +1,7 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::Write;
 use std::io::{BufRead, BufReader};
@@ -9,22 +10,27 @@ use std::path::PathBuf;
 
 use chrono::Utc;
 use failure::bail;
-use shiplift::Docker;
+use shiplift::{rep::ContainerDetails, Docker};
 use slog::{info, warn, Logger};
 use zip::write::ZipWriter;
 
+use crate::configuration::DeployMonitoringEnvironment;
 use crate::deploy_with_compose::{
-    restart_sandbox, restart_stack, shutdown_and_update, shutdown_and_update_sandbox,
+    restart_container, restart_sandbox, restart_stack, shutdown_and_update,
+    shutdown_and_update_sandbox,
 };
 use crate::image::{
-    local_hash, remote_hash, DeployMonitoringContainer, Explorer, Sandbox, TezedgeDebugger,
+    local_hash, remote_hash, DeployMonitoringContainer, Explorer, Image, Sandbox, TezedgeDebugger,
     TezedgeMemprof,
 };
 
 use crate::constants::{DEBUGGER_PORT, TEZEDGE_NODE_P2P_PORT, TEZEDGE_VOLUME_PATH};
-use crate::node::TezedgeNode;
+use crate::node::{OcamlNode, TezedgeNode};
 use crate::slack::SlackServer;
 
+/// Window in seconds, the self-heal restarts are capped within
+const SELF_HEAL_WINDOW: i64 = 60 * 60;
+
 pub struct DeployMonitor {
     compose_file_path: PathBuf,
     docker: Docker,
@@ -32,24 +38,96 @@ pub struct DeployMonitor {
     log: Logger,
     cleanup: bool,
     tezedge_only: bool,
+    // only report the redeploys, do not execute them
+    dry_run: bool,
+    // alert, when the container is restarted more than this times between two checks
+    restart_loop_threshold: u64,
+    // last seen docker restart count per container
+    restart_counts: HashMap<&'static str, u64>,
+    // images pinned by digest, the running containers are checked against, pinned images are not updated
+    pinned_images: Vec<Image>,
+    // containers with reported digest mismatch, to report it just once
+    digest_mismatches: HashSet<&'static str>,
+    // restart the exited/dead node containers, not just report them
+    self_heal: bool,
+    // max count of the self-heal restarts of a container within the [SELF_HEAL_WINDOW]
+    self_heal_max_restarts: usize,
+    // self-heal restarts per container
+    self_heal_restarts: HashMap<&'static str, SelfHealRestarts>,
+}
+
+/// Self-heal restarts of a container within the last [SELF_HEAL_WINDOW], to avoid restart thrash loops
+#[derive(Debug, Default)]
+struct SelfHealRestarts {
+    // timestamps of the restarts, the oldest in the front
+    restarts: VecDeque<i64>,
+    // reached cap is reported just once
+    cap_reported: bool,
+}
+
+impl SelfHealRestarts {
+    /// Records the restart, if the cap is not reached within the window
+    fn try_restart(&mut self, max_restarts: usize, now: i64) -> bool {
+        while let Some(oldest) = self.restarts.front() {
+            if now - oldest < SELF_HEAL_WINDOW {
+                break;
+            }
+            self.restarts.pop_front();
+        }
+
+        if self.restarts.len() < max_restarts {
+            self.restarts.push_back(now);
+            self.cap_reported = false;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Returns the count of the restarts since the last check, when it is over the `threshold`,
+/// nothing is detected on the first check (no `last_restart_count`)
+fn detect_restart_loop(
+    last_restart_count: Option<u64>,
+    restart_count: u64,
+    threshold: u64,
+) -> Option<u64> {
+    let last_restart_count = last_restart_count?;
+    // the restart count starts from 0, when the container is recreated
+    let restarts = if restart_count < last_restart_count {
+        restart_count
+    } else {
+        restart_count - last_restart_count
+    };
+    if restarts > threshold {
+        Some(restarts)
+    } else {
+        None
+    }
 }
 
 impl DeployMonitor {
     pub fn new(
-        compose_file_path: PathBuf,
+        env: &DeployMonitoringEnvironment,
         docker: Docker,
         slack: Option<SlackServer>,
         log: Logger,
-        cleanup: bool,
-        tezedge_only: bool,
     ) -> Self {
         Self {
-            compose_file_path,
+            compose_file_path: env.compose_file_path.clone(),
             docker,
             slack,
             log,
-            cleanup,
-            tezedge_only,
+            cleanup: env.cleanup_volumes,
+            tezedge_only: env.tezedge_only,
+            dry_run: env.dry_run,
+            restart_loop_threshold: env.restart_loop_threshold,
+            restart_counts: HashMap::new(),
+            pinned_images: env.pinned_images.clone(),
+            digest_mismatches: HashSet::new(),
+            self_heal: env.self_heal,
+            self_heal_max_restarts: env.self_heal_max_restarts,
+            self_heal_restarts: HashMap::new(),
         }
     }
 
@@ -126,7 +204,11 @@ impl DeployMonitor {
         Ok(file_path)
     }
 
-    pub async fn monitor_stack(&self) -> Result<(), failure::Error> {
+    pub async fn monitor_stack(&mut self) -> Result<(), failure::Error> {
+        self.check_restart_loops().await?;
+        self.check_image_digests().await?;
+        self.self_heal_nodes().await?;
+
         let DeployMonitor {
             slack,
             log,
@@ -143,7 +225,19 @@ impl DeployMonitor {
             // if debugger updated not need to restart explorer
             // if explorer updated, only need to restart explorer and so on...
             if node_updated || debugger_updated || explorer_updated || memprof_updated {
-                shutdown_and_update(&compose_file_path, log, self.cleanup, self.tezedge_only).await;
+                if self.dry_run {
+                    report_dry_run(slack, log, "Would update the images and redeploy the stack")
+                        .await?;
+                } else {
+                    shutdown_and_update(
+                        &compose_file_path,
+                        log,
+                        self.cleanup,
+                        self.tezedge_only,
+                        self.dry_run,
+                    )
+                    .await;
+                }
             } else {
                 // Do nothing, No update occurred
                 info!(self.log, "No image change detected");
@@ -157,7 +251,18 @@ impl DeployMonitor {
             }
 
             self.send_log_dump().await?;
-            restart_stack(&compose_file_path, log, self.cleanup, self.tezedge_only).await;
+            if self.dry_run {
+                report_dry_run(slack, log, "Would restart the stack").await?;
+            } else {
+                restart_stack(
+                    &compose_file_path,
+                    log,
+                    self.cleanup,
+                    self.tezedge_only,
+                    self.dry_run,
+                )
+                .await;
+            }
         };
 
         Ok(())
@@ -173,7 +278,22 @@ impl DeployMonitor {
 
         if self.is_sandbox_container_running().await {
             if self.changed::<Sandbox>().await? {
-                shutdown_and_update_sandbox(&compose_file_path, log, self.cleanup).await;
+                if self.dry_run {
+                    report_dry_run(
+                        slack,
+                        log,
+                        "Would update the image and redeploy the sandbox launcher",
+                    )
+                    .await?;
+                } else {
+                    shutdown_and_update_sandbox(
+                        &compose_file_path,
+                        log,
+                        self.cleanup,
+                        self.dry_run,
+                    )
+                    .await;
+                }
             } else {
                 // Do nothing, No update occurred
                 info!(self.log, "No image change detected");
@@ -185,7 +305,237 @@ impl DeployMonitor {
                     .send_message("Sandbox launcher not running. Restarting")
                     .await?;
             }
-            restart_sandbox(&compose_file_path, log).await;
+            if self.dry_run {
+                report_dry_run(slack, log, "Would restart the sandbox launcher").await?;
+            } else {
+                restart_sandbox(&compose_file_path, log, self.dry_run).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn check_restart_loops(&mut self) -> Result<(), failure::Error> {
+        self.check_restart_loop::<TezedgeNode>().await?;
+        self.check_restart_loop::<TezedgeDebugger>().await?;
+        self.check_restart_loop::<TezedgeMemprof>().await?;
+        self.check_restart_loop::<Explorer>().await?;
+        if !self.tezedge_only {
+            self.check_restart_loop::<OcamlNode>().await?;
+        }
+        Ok(())
+    }
+
+    /// Reports the container, which was restarted by docker more than `restart_loop_threshold` times since the last check
+    async fn check_restart_loop<T: DeployMonitoringContainer + Sync + Send>(
+        &mut self,
+    ) -> Result<(), failure::Error> {
+        let DeployMonitor {
+            docker,
+            slack,
+            log,
+            restart_loop_threshold,
+            restart_counts,
+            ..
+        } = self;
+
+        // container not present, (re)start is handled by the stack monitoring
+        let ContainerDetails {
+            config,
+            restart_count,
+            ..
+        } = match docker.containers().get(T::NAME).inspect().await {
+            Ok(container_data) => container_data,
+            Err(_) => return Ok(()),
+        };
+
+        let last_restart_count = restart_counts.insert(T::NAME, restart_count);
+        if let Some(restarts) =
+            detect_restart_loop(last_restart_count, restart_count, *restart_loop_threshold)
+        {
+            warn!(
+                log,
+                "Container {} restarted {} times since last check, image: {}",
+                T::NAME,
+                restarts,
+                config.image
+            );
+            if let Some(slack_server) = slack {
+                slack_server
+                    .send_message(&format!(
+                        ":warning: Restart loop detected - {} image restarted {} times since last check",
+                        config.image, restarts
+                    ))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn self_heal_nodes(&mut self) -> Result<(), failure::Error> {
+        // without self-heal, the problems are just reported
+        if !self.self_heal {
+            return Ok(());
+        }
+
+        self.self_heal_container::<TezedgeNode>().await?;
+        if !self.tezedge_only {
+            self.self_heal_container::<OcamlNode>().await?;
+        }
+        Ok(())
+    }
+
+    /// Restarts just the container, which exited or died, at most `self_heal_max_restarts` times within an hour
+    async fn self_heal_container<T: DeployMonitoringContainer + Sync + Send>(
+        &mut self,
+    ) -> Result<(), failure::Error> {
+        let DeployMonitor {
+            docker,
+            slack,
+            log,
+            dry_run,
+            self_heal_max_restarts,
+            self_heal_restarts,
+            ..
+        } = self;
+
+        // container not present, (re)start is handled by the stack monitoring
+        let status = match docker.containers().get(T::NAME).inspect().await {
+            Ok(container_data) => container_data.state.status,
+            Err(_) => return Ok(()),
+        };
+        if status != "exited" && status != "dead" {
+            return Ok(());
+        }
+
+        let restarts = self_heal_restarts.entry(T::NAME).or_default();
+        if !restarts.try_restart(*self_heal_max_restarts, Utc::now().timestamp()) {
+            warn!(
+                log,
+                "Container {} is {}, self-heal restarts cap ({} per hour) reached",
+                T::NAME,
+                status,
+                self_heal_max_restarts
+            );
+            if !restarts.cap_reported {
+                restarts.cap_reported = true;
+                if let Some(slack_server) = slack {
+                    slack_server
+                        .send_message(&format!(
+                            ":warning: Self-heal - {} container is {}, but it was restarted {} times within the last hour already, not restarting it again",
+                            T::NAME, status, self_heal_max_restarts
+                        ))
+                        .await?;
+                }
+            }
+            return Ok(());
+        }
+
+        if *dry_run {
+            return report_dry_run(
+                slack,
+                log,
+                &format!("Would restart the {} container ({})", T::NAME, status),
+            )
+            .await;
+        }
+
+        warn!(log, "Container {} is {}, restarting it", T::NAME, status);
+        let output = restart_container(T::NAME, log, *dry_run).await;
+        let message = if output.status.success() {
+            format!(
+                ":hammer_and_wrench: Self-heal - {} container was {}, restarted it ({}/{} restarts within the last hour)",
+                T::NAME,
+                status,
+                restarts.restarts.len(),
+                self_heal_max_restarts
+            )
+        } else {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!(log, "Failed to restart container {}: {}", T::NAME, stderr);
+            format!(
+                ":warning: Self-heal - {} container is {}, failed to restart it: {}",
+                T::NAME,
+                status,
+                stderr.trim()
+            )
+        };
+        if let Some(slack_server) = slack {
+            slack_server.send_message(&message).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Image, the container `T` is pinned to
+    fn pinned_image<T: DeployMonitoringContainer>(&self) -> Option<&Image> {
+        self.pinned_images
+            .iter()
+            .find(|image| image.container_name() == T::NAME)
+    }
+
+    async fn check_image_digests(&mut self) -> Result<(), failure::Error> {
+        // nothing pinned, nothing to check
+        if self.pinned_images.is_empty() {
+            return Ok(());
+        }
+
+        self.check_image_digest::<TezedgeNode>().await?;
+        self.check_image_digest::<TezedgeDebugger>().await?;
+        self.check_image_digest::<TezedgeMemprof>().await?;
+        self.check_image_digest::<Explorer>().await?;
+        if !self.tezedge_only {
+            self.check_image_digest::<OcamlNode>().await?;
+        }
+        Ok(())
+    }
+
+    /// Reports the container, which is running from an image with different digest, than the pinned one
+    async fn check_image_digest<T: DeployMonitoringContainer + Sync + Send>(
+        &mut self,
+    ) -> Result<(), failure::Error> {
+        let pinned_image = match self.pinned_image::<T>() {
+            Some(pinned_image) => pinned_image.clone(),
+            None => return Ok(()),
+        };
+        let DeployMonitor {
+            docker,
+            slack,
+            log,
+            digest_mismatches,
+            ..
+        } = self;
+        let expected_digest = pinned_image.digest();
+
+        // container not present, (re)start is handled by the stack monitoring
+        let matches = match pinned_image.matches_running::<T>(docker).await {
+            Ok(matches) => matches,
+            Err(e) => {
+                warn!(log, "Failed to check image digest of {}: {}", T::NAME, e);
+                return Ok(());
+            }
+        };
+
+        if matches {
+            digest_mismatches.remove(T::NAME);
+        } else if digest_mismatches.insert(T::NAME) {
+            let image = T::image().await?;
+            warn!(
+                log,
+                "Container {} is running image {}, which does not match the pinned digest {}",
+                T::NAME,
+                image,
+                expected_digest
+            );
+            if let Some(slack_server) = slack {
+                slack_server
+                    .send_message(&format!(
+                        ":warning: {} image does not match the pinned digest {}",
+                        image, expected_digest
+                    ))
+                    .await?;
+            }
         }
 
         Ok(())
@@ -220,6 +570,7 @@ impl DeployMonitor {
         Ok(())
     }
 
+    /// Checks if the image of the container `T` was updated on docker hub, pinned images are never updated
     async fn changed<T: DeployMonitoringContainer + Sync + Send>(
         &self,
     ) -> Result<bool, failure::Error> {
@@ -227,6 +578,15 @@ impl DeployMonitor {
             docker, slack, log, ..
         } = self;
 
+        if self.pinned_image::<T>().is_some() {
+            info!(
+                log,
+                "Image of {} is pinned, skipping the update check",
+                T::NAME
+            );
+            return Ok(false);
+        }
+
         let image = T::image().await?;
         let local_image_hash = local_hash::<T>(&docker).await?;
         let remote_image_hash = remote_hash::<T>().await?;
@@ -252,3 +612,57 @@ impl DeployMonitor {
         }
     }
 }
+
+/// Reports the redeploy action, which would be executed, when not in dry-run mode
+async fn report_dry_run(
+    slack: &Option<SlackServer>,
+    log: &Logger,
+    action: &str,
+) -> Result<(), failure::Error> {
+    info!(log, "[DRY-RUN] {}", action);
+    if let Some(slack_server) = slack {
+        slack_server.send_message(action).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_self_heal_restarts_cap() {
+        let mut restarts = SelfHealRestarts::default();
+
+        assert!(restarts.try_restart(2, 0));
+        assert!(restarts.try_restart(2, 100));
+        assert!(!restarts.try_restart(2, 200));
+        assert!(!restarts.try_restart(2, SELF_HEAL_WINDOW - 1));
+
+        // the first restart left the window
+        assert!(restarts.try_restart(2, SELF_HEAL_WINDOW));
+        assert!(!restarts.try_restart(2, SELF_HEAL_WINDOW + 1));
+
+        // both left the window
+        assert!(restarts.try_restart(2, 2 * SELF_HEAL_WINDOW + 100));
+        assert_eq!(restarts.restarts.len(), 1);
+
+        // disabled by zero cap
+        assert!(!SelfHealRestarts::default().try_restart(0, 0));
+    }
+
+    #[test]
+    fn test_detect_restart_loop() {
+        // nothing to compare with on the first check
+        assert_eq!(None, detect_restart_loop(None, 10, 3));
+
+        assert_eq!(None, detect_restart_loop(Some(10), 10, 3));
+        assert_eq!(None, detect_restart_loop(Some(10), 13, 3));
+        assert_eq!(Some(4), detect_restart_loop(Some(10), 14, 3));
+
+        // container was recreated, the counter started from 0 again
+        assert_eq!(Some(5), detect_restart_loop(Some(10), 5, 3));
+        assert_eq!(None, detect_restart_loop(Some(10), 2, 3));
+        assert_eq!(None, detect_restart_loop(Some(10), 0, 3));
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use slog::{warn, Logger};
+
+use crate::constants::MEASUREMENTS_MAX_CAPACITY;
+use crate::monitors::resource::{
+    MonitoredNode, PersistedResourceUtilization, ResourceUtilization, ResourceUtilizationStorage,
+};
+
+/// Appends measurements as json lines to the file, so they survive the restart of the app
+///
+/// When the file exceeds `max_size` (bytes), it is rotated to `<path>.1` (previous rotated file is replaced)
+#[derive(Clone, Debug)]
+pub struct ResourceHistory {
+    path: PathBuf,
+    max_size: u64,
+}
+
+#[derive(Serialize)]
+struct HistoryRecord<'a> {
+    node: &'a str,
+    measurement: &'a ResourceUtilization,
+}
+
+#[derive(Deserialize)]
+struct PersistedHistoryRecord {
+    node: String,
+    measurement: PersistedResourceUtilization,
+}
+
+impl ResourceHistory {
+    pub fn new(path: PathBuf, max_size: u64) -> Self {
+        Self { path, max_size }
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+        PathBuf::from(rotated)
+    }
+
+    pub fn append(
+        &self,
+        container_name: &str,
+        measurement: &ResourceUtilization,
+    ) -> Result<(), failure::Error> {
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            if metadata.len() >= self.max_size {
+                fs::rename(&self.path, self.rotated_path())?;
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut line = serde_json::to_vec(&HistoryRecord {
+            node: container_name,
+            measurement,
+        })?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+
+        Ok(())
+    }
+
+    /// Loads the last [MEASUREMENTS_MAX_CAPACITY] measurements for every monitored node (matched by container name)
+    ///
+    /// Note: invalid lines (e.g. last line not fully written before crash, or not valid UTF-8) are skipped
+    pub fn load(
+        &self,
+        nodes: &[(MonitoredNode, ResourceUtilizationStorage)],
+        log: &Logger,
+    ) -> Result<(), failure::Error> {
+        // oldest measurements are in the rotated file
+        for path in &[self.rotated_path(), self.path.clone()] {
+            let file = match File::open(path) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            for line in BufReader::new(file).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    // not valid UTF-8, the line is already consumed, so we can continue with the next one
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        warn!(
+                            log,
+                            "Skipping invalid history record in {:?}, reason: {}", path, e
+                        );
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let record: PersistedHistoryRecord = match serde_json::from_str(&line) {
+                    Ok(record) => record,
+                    Err(e) => {
+                        warn!(
+                            log,
+                            "Skipping invalid history record in {:?}, reason: {}", path, e
+                        );
+                        continue;
+                    }
+                };
+
+                if let Some((node, storage)) = nodes
+                    .iter()
+                    .find(|(node, _)| node.container_name() == &record.node)
+                {
+                    let measurement = match ResourceUtilization::from_persisted(
+                        node.node_tag(),
+                        record.measurement,
+                    ) {
+                        Ok(measurement) => measurement,
+                        Err(e) => {
+                            warn!(
+                                log,
+                                "Skipping invalid history record in {:?}, reason: {}", path, e
+                            );
+                            continue;
+                        }
+                    };
+                    match &mut storage.write() {
+                        Ok(storage) => {
+                            if storage.len() == MEASUREMENTS_MAX_CAPACITY {
+                                storage.pop_back();
+                            }
+                            storage.push_front(measurement);
+                        }
+                        Err(e) => {
+                            return Err(failure::format_err!(
+                                "Resource lock poisoned, reason => {}",
+                                e
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+
+    use slog::{o, Discard};
+
+    use crate::monitors::resource::ResourceUtilizationBuilder;
+
+    use super::*;
+
+    fn measurement(timestamp: i64) -> ResourceUtilization {
+        ResourceUtilizationBuilder::default()
+            .timestamp(timestamp)
+            .memory(1000, 100)
+            .cpu(10, None)
+            .build()
+    }
+
+    #[test]
+    fn test_history_rotation_and_load() -> Result<(), failure::Error> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("resources.jsonl");
+        let log = Logger::root(Discard, o!());
+
+        // fill the file with full capacity, no rotation yet
+        let history = ResourceHistory::new(path.clone(), u64::MAX);
+        for timestamp in 0..MEASUREMENTS_MAX_CAPACITY as i64 {
+            history.append("tezedge-node", &measurement(timestamp))?;
+        }
+        assert!(!history.rotated_path().exists());
+
+        // file exceeds the max size, so it is rotated before the append
+        ResourceHistory::new(path.clone(), 1).append(
+            "tezedge-node",
+            &measurement(MEASUREMENTS_MAX_CAPACITY as i64),
+        )?;
+        assert!(history.rotated_path().exists());
+
+        // invalid records are skipped
+        let mut file = OpenOptions::new().append(true).open(&path)?;
+        file.write_all(b"{\"node\":\"tezedge-node\",\"measurement\":\n")?;
+        file.write_all(b"\xff\xfe\n")?;
+        file.write_all(b"{\"node\":\"tezedge-node\",\"measurement\":{\"timestamp\":1,\"memory\":{\"node\":{\"virtual_mem\":1,\"resident_mem\":1}},\"cpu\":{\"node\":1},\"disk\":\"invalid\"}}\n")?;
+        drop(file);
+
+        let last_timestamp = MEASUREMENTS_MAX_CAPACITY as i64 + 10;
+        for timestamp in MEASUREMENTS_MAX_CAPACITY as i64 + 1..=last_timestamp {
+            history.append("tezedge-node", &measurement(timestamp))?;
+            history.append("unknown-node", &measurement(timestamp))?;
+        }
+
+        let storage: ResourceUtilizationStorage = Arc::new(RwLock::new(Default::default()));
+        let nodes = vec![(
+            MonitoredNode::new("tezedge-node".to_string(), "tezedge", 18732),
+            storage.clone(),
+        )];
+        history.load(&nodes, &log)?;
+
+        // just the last measurements (from both files) are loaded, the newest at the front
+        let storage = storage.read().unwrap();
+        assert_eq!(MEASUREMENTS_MAX_CAPACITY, storage.len());
+        assert_eq!(last_timestamp, *storage.front().unwrap().timestamp());
+        assert_eq!(
+            last_timestamp - MEASUREMENTS_MAX_CAPACITY as i64 + 1,
+            *storage.back().unwrap().timestamp()
+        );
+
+        Ok(())
+    }
+}
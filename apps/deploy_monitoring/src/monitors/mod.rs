@@ -8,45 +8,43 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 use shiplift::Docker;
-use slog::{error, info, Logger};
+use slog::{error, info, warn, Logger};
 use tokio::task::JoinHandle;
 use tokio::time::{sleep, Duration};
 
-use crate::configuration::DeployMonitoringEnvironment;
-use crate::constants::MEASUREMENTS_MAX_CAPACITY;
+use crate::configuration::{container_name_regex, DeployMonitoringEnvironment};
+use crate::constants::{
+    CLEANUP_MAX_RETRIES, MEASUREMENTS_MAX_CAPACITY, OCAML_PORT, PAGERDUTY_MAX_RETRIES, TEZEDGE_PORT,
+};
 use crate::deploy_with_compose::{
-    cleanup_docker, restart_sandbox, restart_stack, stop_with_compose,
+    cleanup_docker, list_residual_containers, list_running_containers_matching, remove_containers,
+    restart_sandbox, restart_stack, stop_with_compose,
 };
 use crate::monitors::alerts::Alerts;
 use crate::monitors::deploy::DeployMonitor;
+use crate::monitors::history::ResourceHistory;
 use crate::monitors::resource::{
-    ResourceMonitor, ResourceUtilization, ResourceUtilizationStorageMap,
+    MonitoredNode, ResourceMonitor, ResourceUtilization, ResourceUtilizationStorage,
+    ResourceUtilizationStorageMap,
 };
+use crate::pagerduty::PagerDutyNotifier;
 use crate::rpc;
 use crate::slack::SlackServer;
 
 pub mod alerts;
 pub mod deploy;
+pub mod history;
 pub mod resource;
 
 pub fn start_deploy_monitoring(
-    compose_file_path: PathBuf,
+    env: &DeployMonitoringEnvironment,
     slack: Option<SlackServer>,
     interval: u64,
     log: Logger,
     running: Arc<AtomicBool>,
-    cleanup_data: bool,
-    tezedge_only: bool,
 ) -> JoinHandle<()> {
     let docker = Docker::new();
-    let deploy_monitor = DeployMonitor::new(
-        compose_file_path,
-        docker,
-        slack,
-        log.clone(),
-        cleanup_data,
-        tezedge_only,
-    );
+    let mut deploy_monitor = DeployMonitor::new(env, docker, slack, log.clone());
     tokio::spawn(async move {
         while running.load(Ordering::Acquire) {
             if let Err(e) = deploy_monitor.monitor_stack().await {
@@ -58,23 +56,14 @@ pub fn start_deploy_monitoring(
 }
 
 pub fn start_sandbox_monitoring(
-    compose_file_path: PathBuf,
+    env: &DeployMonitoringEnvironment,
     slack: Option<SlackServer>,
     interval: u64,
     log: Logger,
     running: Arc<AtomicBool>,
-    cleanup_data: bool,
-    tezedge_only: bool,
 ) -> JoinHandle<()> {
     let docker = Docker::new();
-    let deploy_monitor = DeployMonitor::new(
-        compose_file_path,
-        docker,
-        slack,
-        log.clone(),
-        cleanup_data,
-        tezedge_only,
-    );
+    let deploy_monitor = DeployMonitor::new(env, docker, slack, log.clone());
     tokio::spawn(async move {
         while running.load(Ordering::Acquire) {
             if let Err(e) = deploy_monitor.monitor_sandbox_launcher().await {
@@ -89,23 +78,62 @@ pub fn start_resource_monitoring(
     env: &DeployMonitoringEnvironment,
     log: Logger,
     running: Arc<AtomicBool>,
-    resource_utilization: ResourceUtilizationStorageMap,
+    nodes: Vec<(MonitoredNode, ResourceUtilizationStorage)>,
     slack: Option<SlackServer>,
 ) -> JoinHandle<()> {
     let DeployMonitoringEnvironment {
         tezedge_alert_thresholds,
         ocaml_alert_thresholds,
+        memory_ceiling,
+        combined_cpu_threshold,
+        combined_cpu_window,
         resource_monitor_interval,
+        resource_history_file,
+        resource_history_max_size,
+        data_dir_path,
+        data_dir_free_space_threshold,
+        pagerduty_routing_key,
+        dry_run,
         ..
     } = env;
 
-    let alerts = Alerts::new(*tezedge_alert_thresholds, *ocaml_alert_thresholds);
+    let history = resource_history_file
+        .as_ref()
+        .map(|path| ResourceHistory::new(path.clone(), *resource_history_max_size));
+
+    // reload the measurements from the previous run
+    if let Some(history) = history.as_ref() {
+        if let Err(e) = history.load(&nodes, &log) {
+            error!(log, "Failed to load resource history, reason: {}", e);
+        }
+    }
+
+    let alerts = Alerts::new(
+        *tezedge_alert_thresholds,
+        *ocaml_alert_thresholds,
+        *memory_ceiling,
+        *combined_cpu_threshold,
+        *combined_cpu_window,
+    )
+    .with_pagerduty(pagerduty_routing_key.as_ref().map(|routing_key| {
+        PagerDutyNotifier::new(
+            routing_key.clone(),
+            PAGERDUTY_MAX_RETRIES,
+            *dry_run,
+            log.clone(),
+        )
+    }));
+    let data_dir = data_dir_path
+        .as_ref()
+        .map(|path| (path.clone(), *data_dir_free_space_threshold));
     let mut resource_monitor = ResourceMonitor::new(
-        resource_utilization,
+        nodes,
         HashMap::new(),
         alerts,
         log.clone(),
         slack,
+        history,
+        data_dir,
     );
 
     let resource_monitor_interval = *resource_monitor_interval;
@@ -119,21 +147,53 @@ pub fn start_resource_monitoring(
     })
 }
 
+/// Stops the stack and cleans up, returns the count of containers, which could not be removed
 pub async fn shutdown_and_cleanup(
     compose_file_path: &PathBuf,
     slack: Option<SlackServer>,
     log: &Logger,
     cleanup_data: bool,
-) -> Result<(), failure::Error> {
+    dry_run: bool,
+) -> Result<usize, failure::Error> {
     if let Some(slack_server) = slack {
         slack_server.send_message("Manual shuttdown ").await?;
     }
     info!(log, "Manual shutdown");
 
-    stop_with_compose(compose_file_path);
-    cleanup_docker(cleanup_data);
+    stop_with_compose(compose_file_path, log, dry_run).await;
+    cleanup_docker(cleanup_data, log, dry_run).await;
 
-    Ok(())
+    verify_cleanup(log, dry_run).await
+}
+
+/// Checks that no containers survived the cleanup and retries to remove the remaining ones
+async fn verify_cleanup(log: &Logger, dry_run: bool) -> Result<usize, failure::Error> {
+    let mut residual = list_residual_containers(log, dry_run).await?;
+    let mut retries = 0;
+
+    while !residual.is_empty() && retries < CLEANUP_MAX_RETRIES {
+        retries += 1;
+        warn!(
+            log,
+            "Residual containers found after cleanup, removing (attempt {}/{})",
+            retries,
+            CLEANUP_MAX_RETRIES;
+            "containers" => residual.join(", ")
+        );
+        remove_containers(&residual, log, dry_run).await;
+        sleep(Duration::from_secs(1)).await;
+        residual = list_residual_containers(log, dry_run).await?;
+    }
+
+    if !residual.is_empty() {
+        error!(
+            log,
+            "Failed to remove containers after {} attempts", CLEANUP_MAX_RETRIES;
+            "containers" => residual.join(", ")
+        );
+    }
+
+    Ok(residual.len())
 }
 
 pub async fn start_stack(
@@ -149,11 +209,19 @@ pub async fn start_stack(
         tezedge_alert_thresholds,
         ocaml_alert_thresholds,
         tezedge_only,
+        dry_run,
         ..
     } = env;
 
     // cleanup possible dangling containers/volumes and start the stack
-    restart_stack(&compose_file_path, log, *cleanup_volumes, *tezedge_only).await;
+    restart_stack(
+        &compose_file_path,
+        log,
+        *cleanup_volumes,
+        *tezedge_only,
+        *dry_run,
+    )
+    .await;
     if let Some(slack_server) = slack {
         slack_server.send_message("Tezedge stack started").await?;
         slack_server
@@ -176,11 +244,12 @@ pub async fn start_sandbox(
     compose_file_path: &PathBuf,
     slack: Option<SlackServer>,
     log: &Logger,
+    dry_run: bool,
 ) -> Result<(), failure::Error> {
     info!(log, "Starting tezedge stack");
 
     // cleanup possible dangling containers/volumes and start the stack
-    restart_sandbox(compose_file_path, log).await;
+    restart_sandbox(compose_file_path, log, dry_run).await;
     if let Some(slack_server) = slack {
         slack_server
             .send_message("Tezedge sandbox launcher started")
@@ -195,20 +264,28 @@ pub async fn spawn_sandbox(
     log: &Logger,
     running: Arc<AtomicBool>,
 ) -> Vec<JoinHandle<()>> {
-    start_sandbox(&env.compose_file_path, slack_server.clone(), &log)
-        .await
-        .expect("Sandbox failed to start");
+    start_sandbox(
+        &env.compose_file_path,
+        slack_server.clone(),
+        &log,
+        env.dry_run,
+    )
+    .await
+    .expect("Sandbox failed to start");
+
+    if !env.enable_deploy_monitor {
+        info!(log, "Docker image monitor is disabled");
+        return vec![];
+    }
 
     info!(log, "Creating docker image monitor");
     if let Some(image_monitor_interval) = env.image_monitor_interval {
         let deploy_handle = start_sandbox_monitoring(
-            env.compose_file_path.clone(),
+            &env,
             slack_server.clone(),
             image_monitor_interval,
             log.clone(),
             running.clone(),
-            env.cleanup_volumes,
-            env.tezedge_only,
         );
 
         vec![deploy_handle]
@@ -229,51 +306,121 @@ pub async fn spawn_node_stack(
 
     let mut handles = Vec::new();
 
-    if let Some(image_monitor_interval) = env.image_monitor_interval {
-        let deploy_handle = start_deploy_monitoring(
-            env.compose_file_path.clone(),
-            slack_server.clone(),
-            image_monitor_interval,
-            log.clone(),
-            running.clone(),
-            env.cleanup_volumes,
-            env.tezedge_only,
-        );
-        handles.push(deploy_handle);
+    match env.image_monitor_interval {
+        Some(image_monitor_interval) if env.enable_deploy_monitor => {
+            let deploy_handle = start_deploy_monitoring(
+                &env,
+                slack_server.clone(),
+                image_monitor_interval,
+                log.clone(),
+                running.clone(),
+            );
+            handles.push(deploy_handle);
+        }
+        Some(_) => info!(log, "Deploy monitor is disabled"),
+        None => (),
     }
 
     // TODO: TE-499 - (multiple nodes) rework this to load from a config, where all the nodes all defined
-    // create a thread safe VecDeque for each node's resource utilization data
-    let mut storage_map = HashMap::new();
-    storage_map.insert(
+    let mut monitored_nodes = vec![MonitoredNode::new(
+        resolve_container_name(&env.tezedge_container_name, log, env.dry_run).await,
         "tezedge",
-        Arc::new(RwLock::new(VecDeque::<ResourceUtilization>::with_capacity(
-            MEASUREMENTS_MAX_CAPACITY,
-        ))),
+        TEZEDGE_PORT,
+    )];
+    monitored_nodes.extend(
+        env.tezedge_node_containers
+            .iter()
+            .map(|(name, port)| MonitoredNode::additional(name.clone(), "tezedge", *port)),
     );
     if !env.tezedge_only {
-        storage_map.insert(
+        monitored_nodes.push(MonitoredNode::new(
+            resolve_container_name(&env.ocaml_container_name, log, env.dry_run).await,
             "ocaml",
-            Arc::new(RwLock::new(VecDeque::<ResourceUtilization>::with_capacity(
-                MEASUREMENTS_MAX_CAPACITY,
-            ))),
-        );
+            OCAML_PORT,
+        ));
     }
 
-    info!(log, "Creating reosurces monitor");
-    let resources_handle = start_resource_monitoring(
-        &env,
-        log.clone(),
-        running.clone(),
-        storage_map.clone(),
-        slack_server.clone(),
-    );
+    // create a thread safe VecDeque for each node's resource utilization data
+    let nodes: Vec<(MonitoredNode, ResourceUtilizationStorage)> = monitored_nodes
+        .into_iter()
+        .map(|node| {
+            (
+                node,
+                Arc::new(RwLock::new(VecDeque::<ResourceUtilization>::with_capacity(
+                    MEASUREMENTS_MAX_CAPACITY,
+                ))),
+            )
+        })
+        .collect();
+    let storage_map: ResourceUtilizationStorageMap = nodes
+        .iter()
+        .map(|(node, storage)| (node.container_name().clone(), storage.clone()))
+        .collect();
 
-    handles.push(resources_handle);
+    if env.enable_resource_monitor {
+        info!(log, "Creating reosurces monitor");
+        let resources_handle = start_resource_monitoring(
+            &env,
+            log.clone(),
+            running.clone(),
+            nodes,
+            slack_server.clone(),
+        );
+        handles.push(resources_handle);
+    } else {
+        info!(log, "Resource monitor is disabled");
+    }
 
-    info!(log, "Starting rpc server on port {}", &env.rpc_port);
-    let rpc_server_handle = rpc::spawn_rpc_server(env.rpc_port, log.clone(), storage_map.clone());
-    handles.push(rpc_server_handle);
+    if env.enable_rpc_server {
+        info!(log, "Starting rpc server on port {}", &env.rpc_port);
+        let rpc_server_handle = rpc::spawn_rpc_server(
+            env.rpc_port,
+            log.clone(),
+            storage_map.clone(),
+            env.metrics_prefix.clone(),
+            env.slack_signing_secret.clone(),
+        );
+        handles.push(rpc_server_handle);
+    } else {
+        info!(log, "Rpc server is disabled");
+    }
 
     handles
 }
+
+/// Resolves the configured container name pattern to the name of the running container,
+/// when no running container matches, the pattern is used as the container name as it is
+async fn resolve_container_name(pattern: &str, log: &Logger, dry_run: bool) -> String {
+    let regex =
+        container_name_regex(pattern).expect("Container name pattern is validated on start");
+    match list_running_containers_matching(&regex, log, dry_run).await {
+        Ok(names) => match names.as_slice() {
+            [] => {
+                warn!(
+                    log,
+                    "Container name pattern '{}' matches no running container, the node will not be monitored",
+                    pattern
+                );
+                pattern.to_string()
+            }
+            [name] => name.clone(),
+            [name, ..] => {
+                warn!(
+                    log,
+                    "Container name pattern '{}' matches multiple running containers {:?}, monitoring just '{}'",
+                    pattern,
+                    names,
+                    name
+                );
+                name.clone()
+            }
+        },
+        Err(e) => {
+            warn!(
+                log,
+                "Failed to resolve container name pattern '{}', reason: {}", pattern, e
+            );
+            pattern.to_string()
+        }
+    }
+}
@@ -2,39 +2,87 @@
 // SPDX-License-Identifier: MIT
 
 use std::cmp;
-use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 
 use chrono::Utc;
 use failure::format_err;
 use getset::Getters;
-use serde::Serialize;
-use slog::{error, Logger};
+use serde::{Deserialize, Serialize};
+use shiplift::Docker;
+use slog::{error, info, warn, Logger};
 use sysinfo::{System, SystemExt};
 
 use shell::stats::memory::ProcessMemoryStats;
 
-use crate::constants::{MEASUREMENTS_MAX_CAPACITY, OCAML_PORT, TEZEDGE_PORT};
-use crate::display_info::{NodeInfo, OcamlDiskData, TezedgeDiskData};
+use crate::configuration::FreeSpaceThreshold;
+use crate::constants::MEASUREMENTS_MAX_CAPACITY;
+use crate::display_info::{DataDirUsage, NodeInfo, OcamlDiskData, TezedgeDiskData};
+use crate::monitors::history::ResourceHistory;
 use crate::monitors::Alerts;
 use crate::node::OcamlNode;
-use crate::node::{Node, TezedgeNode};
+use crate::node::{ContainerIoStats, Node, TezedgeNode};
 use crate::slack::SlackServer;
 
 pub type ResourceUtilizationStorage = Arc<RwLock<VecDeque<ResourceUtilization>>>;
-pub type ResourceUtilizationStorageMap = HashMap<&'static str, ResourceUtilizationStorage>;
+/// Resource utilization storages keyed by the container name of the node
+pub type ResourceUtilizationStorageMap = HashMap<String, ResourceUtilizationStorage>;
+
+/// Node container, which resources are monitored
+#[derive(Clone, Debug, Getters)]
+pub struct MonitoredNode {
+    #[get = "pub(crate)"]
+    container_name: String,
+
+    /// Type of the node - "tezedge" or "ocaml"
+    #[get = "pub(crate)"]
+    node_tag: &'static str,
+
+    #[get = "pub(crate)"]
+    rpc_port: u16,
+
+    /// Processes and data of the additional containers cannot be told apart on the host
+    /// from the ones of the main container, see [MonitoredNode::additional]
+    #[get = "pub(crate)"]
+    additional: bool,
+}
+
+impl MonitoredNode {
+    pub fn new(container_name: String, node_tag: &'static str, rpc_port: u16) -> Self {
+        Self {
+            container_name,
+            node_tag,
+            rpc_port,
+            additional: false,
+        }
+    }
+
+    /// Additional node container, its cpu is measured for the whole container by docker
+    /// and its disk is not measured, as it does not use the monitored volume
+    pub fn additional(container_name: String, node_tag: &'static str, rpc_port: u16) -> Self {
+        Self {
+            additional: true,
+            ..Self::new(container_name, node_tag, rpc_port)
+        }
+    }
+}
 
 pub struct ResourceMonitor {
-    resource_utilization: ResourceUtilizationStorageMap,
+    nodes: Vec<(MonitoredNode, ResourceUtilizationStorage)>,
     last_checked_head_level: HashMap<String, u64>,
     alerts: Alerts,
     log: Logger,
     slack: Option<SlackServer>,
     system: System,
+    history: Option<ResourceHistory>,
+    data_dir: Option<(PathBuf, FreeSpaceThreshold)>,
+    /// Containers, which disappeared (e.g. stack is redeployed), are skipped until they reappear
+    disappeared_containers: HashSet<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Getters, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, Getters, Default)]
 pub struct MemoryStats {
     #[get = "pub(crate)"]
     node: ProcessMemoryStats,
@@ -70,12 +118,134 @@ pub struct ResourceUtilization {
     #[get = "pub(crate)"]
     cpu: CpuStats,
 
+    #[get = "pub(crate)"]
+    disk_read_bytes: u64,
+
+    #[get = "pub(crate)"]
+    disk_write_bytes: u64,
+
+    #[get = "pub(crate)"]
+    net_rx_bytes: u64,
+
+    #[get = "pub(crate)"]
+    net_tx_bytes: u64,
+
+    /// Space on the filesystem of the node data directory, measured just when the data directory is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_dir: Option<DataDirUsage>,
+
+    /// Size of the context kv-store on disk as reported by the tezedge node (may be approximate),
+    /// not present for the ocaml node or for the in-memory context kv-store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_disk_size: Option<u64>,
+
     #[get = "pub(crate)"]
     #[serde(skip)]
     head_info: NodeInfo,
 }
 
+/// Measurement in the same (json) format as it is serialized in [ResourceUtilization],
+/// used to load the measurements persisted in the history file
+#[derive(Deserialize)]
+pub struct PersistedResourceUtilization {
+    timestamp: i64,
+    memory: MemoryStats,
+    #[serde(default)]
+    disk: Option<serde_json::Value>,
+    cpu: CpuStats,
+    #[serde(default)]
+    disk_read_bytes: u64,
+    #[serde(default)]
+    disk_write_bytes: u64,
+    #[serde(default)]
+    net_rx_bytes: u64,
+    #[serde(default)]
+    net_tx_bytes: u64,
+    #[serde(default)]
+    data_dir: Option<DataDirUsage>,
+    #[serde(default)]
+    context_disk_size: Option<u64>,
+}
+
+/// Builds measurements for tests in the persisted (json) format, so they are loaded the same way as from the history file
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct ResourceUtilizationBuilder {
+    timestamp: i64,
+    virtual_mem: usize,
+    resident_mem: usize,
+    cpu: i32,
+    protocol_runners_cpu: Option<i32>,
+}
+
+#[cfg(test)]
+impl ResourceUtilizationBuilder {
+    pub(crate) fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub(crate) fn memory(mut self, virtual_mem: usize, resident_mem: usize) -> Self {
+        self.virtual_mem = virtual_mem;
+        self.resident_mem = resident_mem;
+        self
+    }
+
+    pub(crate) fn cpu(mut self, node: i32, protocol_runners: Option<i32>) -> Self {
+        self.cpu = node;
+        self.protocol_runners_cpu = protocol_runners;
+        self
+    }
+
+    /// Builds measurement of the tezedge node
+    pub(crate) fn build(self) -> ResourceUtilization {
+        let persisted: PersistedResourceUtilization = serde_json::from_value(serde_json::json!({
+            "timestamp": self.timestamp,
+            "memory": {
+                "node": {
+                    "virtual_mem": self.virtual_mem,
+                    "resident_mem": self.resident_mem,
+                },
+            },
+            "cpu": {
+                "node": self.cpu,
+                "protocol_runners": self.protocol_runners_cpu,
+            },
+        }))
+        .expect("Invalid persisted measurement");
+        ResourceUtilization::from_persisted("tezedge", persisted)
+            .expect("Failed to load persisted measurement")
+    }
+}
+
 impl ResourceUtilization {
+    /// Creates measurement from persisted one, disk data are resolved according to the node_tag
+    pub fn from_persisted(
+        node_tag: &str,
+        persisted: PersistedResourceUtilization,
+    ) -> Result<Self, failure::Error> {
+        let (ocaml_disk, tezedge_disk) = match persisted.disk {
+            Some(disk) if node_tag == "tezedge" => (None, Some(serde_json::from_value(disk)?)),
+            Some(disk) => (Some(serde_json::from_value(disk)?), None),
+            None => (None, None),
+        };
+
+        Ok(Self {
+            timestamp: persisted.timestamp,
+            memory: persisted.memory,
+            ocaml_disk,
+            tezedge_disk,
+            cpu: persisted.cpu,
+            disk_read_bytes: persisted.disk_read_bytes,
+            disk_write_bytes: persisted.disk_write_bytes,
+            net_rx_bytes: persisted.net_rx_bytes,
+            net_tx_bytes: persisted.net_tx_bytes,
+            data_dir: persisted.data_dir,
+            context_disk_size: persisted.context_disk_size,
+            head_info: NodeInfo::default(),
+        })
+    }
+
     pub fn merge(&self, other: Self) -> Self {
         let merged_ocaml_disk = if let (Some(ocaml_disk1), Some(ocaml_disk2)) =
             (self.ocaml_disk.as_ref(), other.ocaml_disk)
@@ -110,6 +280,24 @@ impl ResourceUtilization {
             None
         };
 
+        let merged_data_dir =
+            if let (Some(data_dir1), Some(data_dir2)) = (self.data_dir, other.data_dir) {
+                Some(DataDirUsage::new(
+                    cmp::max(data_dir1.total(), data_dir2.total()),
+                    cmp::max(data_dir1.used(), data_dir2.used()),
+                    cmp::min(data_dir1.free(), data_dir2.free()),
+                ))
+            } else {
+                None
+            };
+
+        let merged_context_disk_size =
+            if let (Some(size1), Some(size2)) = (self.context_disk_size, other.context_disk_size) {
+                Some(cmp::max(size1, size2))
+            } else {
+                None
+            };
+
         let merged_protocol_runner_memory =
             if let (Some(protocol_runner_mem1), Some(protocol_runner_mem2)) = (
                 self.memory.protocol_runners.as_ref(),
@@ -165,13 +353,19 @@ impl ResourceUtilization {
             },
             ocaml_disk: merged_ocaml_disk,
             tezedge_disk: merged_tezedge_disk,
+            disk_read_bytes: cmp::max(self.disk_read_bytes, other.disk_read_bytes),
+            disk_write_bytes: cmp::max(self.disk_write_bytes, other.disk_write_bytes),
+            net_rx_bytes: cmp::max(self.net_rx_bytes, other.net_rx_bytes),
+            net_tx_bytes: cmp::max(self.net_tx_bytes, other.net_tx_bytes),
+            data_dir: merged_data_dir,
+            context_disk_size: merged_context_disk_size,
             // this is not present in the FE data, do not need to merge with max strategy
             head_info: other.head_info,
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Getters, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize, Getters, Default)]
 pub struct CpuStats {
     #[get = "pub(crate)"]
     node: i32,
@@ -183,105 +377,111 @@ pub struct CpuStats {
 
 impl ResourceMonitor {
     pub fn new(
-        resource_utilization: ResourceUtilizationStorageMap,
+        nodes: Vec<(MonitoredNode, ResourceUtilizationStorage)>,
         last_checked_head_level: HashMap<String, u64>,
         alerts: Alerts,
         log: Logger,
         slack: Option<SlackServer>,
+        history: Option<ResourceHistory>,
+        data_dir: Option<(PathBuf, FreeSpaceThreshold)>,
     ) -> Self {
         Self {
-            resource_utilization,
+            nodes,
             last_checked_head_level,
             alerts,
             log,
             slack,
             system: System::new_all(),
+            history,
+            data_dir,
+            disappeared_containers: HashSet::new(),
         }
     }
 
     pub async fn take_measurement(&mut self) -> Result<(), failure::Error> {
         let ResourceMonitor {
             system,
-            resource_utilization,
+            nodes,
             log,
             last_checked_head_level,
             alerts,
             slack,
-            ..
+            history,
+            data_dir,
+            disappeared_containers,
         } = self;
 
         system.refresh_all();
 
-        for (node_tag, resource_storage) in resource_utilization {
-            let node_resource_measurement = if node_tag == &"tezedge" {
-                let current_head_info = TezedgeNode::collect_head_data(TEZEDGE_PORT).await?;
-                let tezedge_node = TezedgeNode::collect_memory_data(TEZEDGE_PORT).await?;
-                let protocol_runners =
-                    TezedgeNode::collect_protocol_runners_memory_stats(TEZEDGE_PORT).await?;
-                let tezedge_disk = TezedgeNode::collect_disk_data()?;
-
-                let tezedge_cpu = TezedgeNode::collect_cpu_data(system, "light-node")?;
-                let protocol_runners_cpu =
-                    TezedgeNode::collect_cpu_data(system, "protocol-runner")?;
-                let resources = ResourceUtilization {
-                    timestamp: chrono::Local::now().timestamp(),
-                    memory: MemoryStats {
-                        node: tezedge_node,
-                        protocol_runners: Some(protocol_runners),
-                        validators: None,
-                    },
-                    tezedge_disk: Some(tezedge_disk),
-                    ocaml_disk: None,
-                    cpu: CpuStats {
-                        node: tezedge_cpu,
-                        protocol_runners: Some(protocol_runners_cpu),
-                    },
-                    head_info: current_head_info,
-                };
-                handle_alerts(
-                    node_tag,
-                    resources.clone(),
-                    last_checked_head_level,
-                    slack.clone(),
-                    alerts,
-                    log,
-                )
-                .await?;
-                resources
-            } else {
-                let current_head_info = OcamlNode::collect_head_data(OCAML_PORT).await?;
-                let ocaml_node = OcamlNode::collect_memory_data(OCAML_PORT).await?;
-                let tezos_validators = OcamlNode::collect_validator_memory_stats()?;
-                let ocaml_disk = OcamlNode::collect_disk_data()?;
-                let ocaml_cpu = OcamlNode::collect_cpu_data(system, "tezos-node")?;
-
-                let resources = ResourceUtilization {
-                    timestamp: chrono::Local::now().timestamp(),
-                    memory: MemoryStats {
-                        node: ocaml_node,
-                        protocol_runners: None,
-                        validators: Some(tezos_validators),
-                    },
-                    ocaml_disk: Some(ocaml_disk),
-                    tezedge_disk: None,
-                    cpu: CpuStats {
-                        node: ocaml_cpu,
-                        protocol_runners: None,
-                    },
-                    head_info: current_head_info,
-                };
-                handle_alerts(
-                    node_tag,
-                    resources.clone(),
-                    last_checked_head_level,
-                    slack.clone(),
-                    alerts,
-                    log,
-                )
-                .await?;
-                resources
+        // the data directory is shared by the nodes, so it is measured just once for all of them
+        let data_dir_usage = match data_dir.as_ref() {
+            Some((path, _)) => match DataDirUsage::measure(path) {
+                Ok(usage) => Some(usage),
+                Err(e) => {
+                    warn!(
+                        log,
+                        "Failed to measure data directory {}, reason: {}",
+                        path.display(),
+                        e
+                    );
+                    None
+                }
+            },
+            None => None,
+        };
+
+        for (node, resource_storage) in nodes.iter() {
+            let node_resource_measurement = match measure_node(node, system, log).await {
+                Ok(measurement) => ResourceUtilization {
+                    data_dir: data_dir_usage,
+                    ..measurement
+                },
+                Err(e) => {
+                    if is_container_present(node.container_name()).await {
+                        error!(
+                            log,
+                            "[{}] Resource measurement error: {}",
+                            node.container_name(),
+                            e
+                        );
+                    } else if disappeared_containers.insert(node.container_name().clone()) {
+                        warn!(
+                            log,
+                            "[{}] Container disappeared, skipping it until it reappears",
+                            node.container_name()
+                        );
+                    }
+                    continue;
+                }
             };
 
+            if disappeared_containers.remove(node.container_name()) {
+                info!(
+                    log,
+                    "[{}] Container reappeared, monitoring resumed",
+                    node.container_name()
+                );
+            }
+
+            handle_alerts(
+                node,
+                node_resource_measurement.clone(),
+                last_checked_head_level,
+                slack.clone(),
+                alerts,
+                log,
+            )
+            .await?;
+
+            if let Some(history) = history.as_ref() {
+                if let Err(e) = history.append(node.container_name(), &node_resource_measurement) {
+                    error!(
+                        log,
+                        "Failed to persist measurement to history file, reason => {}", e
+                    );
+                }
+            }
+
             match &mut resource_storage.write() {
                 Ok(resources_locked) => {
                     if resources_locked.len() == MEASUREMENTS_MAX_CAPACITY {
@@ -293,27 +493,158 @@ impl ResourceMonitor {
                 Err(e) => error!(log, "Resource lock poisoned, reason => {}", e),
             }
         }
+
+        // measurements of the disappeared containers are stale, so they are not combined
+        let active_nodes: Vec<_> = nodes
+            .iter()
+            .filter(|(node, _)| !disappeared_containers.contains(node.container_name()))
+            .cloned()
+            .collect();
+        alerts
+            .check_combined_cpu_alert(slack.as_ref(), Utc::now().timestamp(), &active_nodes)
+            .await?;
+
+        if let (Some((_, threshold)), Some(usage)) = (data_dir.as_ref(), data_dir_usage) {
+            alerts
+                .check_data_dir_alert(threshold, slack.as_ref(), Utc::now().timestamp(), usage)
+                .await?;
+        }
+
         Ok(())
     }
 }
 
+async fn measure_node(
+    node: &MonitoredNode,
+    system: &mut System,
+    log: &Logger,
+) -> Result<ResourceUtilization, failure::Error> {
+    let port = *node.rpc_port();
+    if node.node_tag() == &"tezedge" {
+        let current_head_info = TezedgeNode::collect_head_data(port).await?;
+        let tezedge_node = TezedgeNode::collect_memory_data(port).await?;
+        let protocol_runners = TezedgeNode::collect_protocol_runners_memory_stats(port).await?;
+        let (tezedge_disk, tezedge_cpu, protocol_runners_cpu) = if *node.additional() {
+            let container_cpu =
+                TezedgeNode::collect_container_cpu_data(node.container_name()).await?;
+            (None, container_cpu, None)
+        } else {
+            (
+                Some(TezedgeNode::collect_disk_data()?),
+                TezedgeNode::collect_cpu_data(system, "light-node")?,
+                Some(TezedgeNode::collect_cpu_data(system, "protocol-runner")?),
+            )
+        };
+        let io_stats = collect_io_data::<TezedgeNode>(node.container_name(), log).await;
+        let context_disk_size = collect_context_disk_size(port, log).await;
+
+        Ok(ResourceUtilization {
+            timestamp: chrono::Local::now().timestamp(),
+            memory: MemoryStats {
+                node: tezedge_node,
+                protocol_runners: Some(protocol_runners),
+                validators: None,
+            },
+            tezedge_disk,
+            ocaml_disk: None,
+            cpu: CpuStats {
+                node: tezedge_cpu,
+                protocol_runners: protocol_runners_cpu,
+            },
+            disk_read_bytes: io_stats.disk_read_bytes,
+            disk_write_bytes: io_stats.disk_write_bytes,
+            net_rx_bytes: io_stats.net_rx_bytes,
+            net_tx_bytes: io_stats.net_tx_bytes,
+            data_dir: None,
+            context_disk_size,
+            head_info: current_head_info,
+        })
+    } else {
+        let current_head_info = OcamlNode::collect_head_data(port).await?;
+        let ocaml_node = OcamlNode::collect_memory_data(port).await?;
+        let tezos_validators = OcamlNode::collect_validator_memory_stats()?;
+        let ocaml_disk = OcamlNode::collect_disk_data()?;
+        let ocaml_cpu = OcamlNode::collect_cpu_data(system, "tezos-node")?;
+        let io_stats = collect_io_data::<OcamlNode>(node.container_name(), log).await;
+
+        Ok(ResourceUtilization {
+            timestamp: chrono::Local::now().timestamp(),
+            memory: MemoryStats {
+                node: ocaml_node,
+                protocol_runners: None,
+                validators: Some(tezos_validators),
+            },
+            ocaml_disk: Some(ocaml_disk),
+            tezedge_disk: None,
+            cpu: CpuStats {
+                node: ocaml_cpu,
+                protocol_runners: None,
+            },
+            disk_read_bytes: io_stats.disk_read_bytes,
+            disk_write_bytes: io_stats.disk_write_bytes,
+            net_rx_bytes: io_stats.net_rx_bytes,
+            net_tx_bytes: io_stats.net_tx_bytes,
+            data_dir: None,
+            context_disk_size: None,
+            head_info: current_head_info,
+        })
+    }
+}
+
+async fn is_container_present(container_name: &str) -> bool {
+    let docker = Docker::new();
+    docker
+        .containers()
+        .get(container_name)
+        .inspect()
+        .await
+        .is_ok()
+}
+
+/// Disk and network io data are not critical, so we do not want to drop the whole measurement, when they are not available
+async fn collect_io_data<T: Node>(container_name: &str, log: &Logger) -> ContainerIoStats {
+    match T::collect_io_data(container_name).await {
+        Ok(io_stats) => io_stats,
+        Err(e) => {
+            warn!(
+                log,
+                "Failed to collect io data for container {}, reason: {}", container_name, e
+            );
+            ContainerIoStats::default()
+        }
+    }
+}
+
+/// Context disk size is not critical (and older nodes do not report it), so the measurement is not dropped without it
+async fn collect_context_disk_size(port: u16, log: &Logger) -> Option<u64> {
+    match TezedgeNode::collect_context_disk_size(port).await {
+        Ok(size) => size,
+        Err(e) => {
+            warn!(log, "Failed to collect context disk size, reason: {}", e);
+            None
+        }
+    }
+}
+
 async fn handle_alerts(
-    node_tag: &str,
+    node: &MonitoredNode,
     last_measurement: ResourceUtilization,
     last_checked_head_level: &mut HashMap<String, u64>,
     slack: Option<SlackServer>,
     alerts: &mut Alerts,
     log: &Logger,
 ) -> Result<(), failure::Error> {
-    // TODO: TE-499 - (multinode) - fix for multinode support
-    let thresholds = if node_tag == "tezedge" {
+    let thresholds = if node.node_tag() == &"tezedge" {
         *alerts.tezedge_thresholds()
-    } else if node_tag == "ocaml" {
+    } else if node.node_tag() == &"ocaml" {
         *alerts.ocaml_thresholds()
     } else {
-        return Err(format_err!("Node [{}] not defined", node_tag));
+        return Err(format_err!("Node [{}] not defined", node.node_tag()));
     };
 
+    // alerts are reported per container
+    let node_tag = node.container_name().as_str();
+
     // current time timestamp
     let current_time = Utc::now().timestamp();
 
@@ -338,6 +669,14 @@ async fn handle_alerts(
             last_measurement.clone(),
         )
         .await?;
+    alerts
+        .check_memory_ceiling_alert(
+            node_tag,
+            slack.as_ref(),
+            current_time,
+            last_measurement.clone(),
+        )
+        .await?;
     alerts
         .check_node_stuck_alert(
             node_tag,
@@ -388,6 +727,12 @@ mod tests {
                 validators: None,
             },
             timestamp: 1,
+            disk_read_bytes: 100,
+            disk_write_bytes: 10,
+            net_rx_bytes: 1000,
+            net_tx_bytes: 500,
+            data_dir: DataDirUsage::new(1000, 500, 400).into(),
+            context_disk_size: Some(1000),
             head_info: NodeInfo::default(),
         };
 
@@ -404,6 +749,12 @@ mod tests {
                 validators: None,
             },
             timestamp: 2,
+            disk_read_bytes: 200,
+            disk_write_bytes: 20,
+            net_rx_bytes: 3000,
+            net_tx_bytes: 400,
+            data_dir: DataDirUsage::new(1000, 700, 200).into(),
+            context_disk_size: Some(3000),
             head_info: NodeInfo::default(),
         };
 
@@ -421,6 +772,12 @@ mod tests {
                 validators: None,
             },
             timestamp: 3,
+            disk_read_bytes: 150,
+            disk_write_bytes: 30,
+            net_rx_bytes: 2000,
+            net_tx_bytes: 900,
+            data_dir: DataDirUsage::new(1000, 600, 300).into(),
+            context_disk_size: Some(2000),
             head_info: NodeInfo::default(),
         };
 
@@ -438,6 +795,12 @@ mod tests {
                 validators: None,
             },
             timestamp: 3,
+            disk_read_bytes: 200,
+            disk_write_bytes: 30,
+            net_rx_bytes: 3000,
+            net_tx_bytes: 900,
+            data_dir: DataDirUsage::new(1000, 700, 200).into(),
+            context_disk_size: Some(3000),
             head_info: NodeInfo::default(),
         };
 
@@ -456,5 +819,58 @@ mod tests {
             expected.memory.protocol_runners
         );
         assert_eq!(merged_final.timestamp, expected.timestamp);
+        assert_eq!(merged_final.disk_read_bytes, expected.disk_read_bytes);
+        assert_eq!(merged_final.disk_write_bytes, expected.disk_write_bytes);
+        assert_eq!(merged_final.net_rx_bytes, expected.net_rx_bytes);
+        assert_eq!(merged_final.net_tx_bytes, expected.net_tx_bytes);
+        assert_eq!(merged_final.data_dir, expected.data_dir);
+        assert_eq!(merged_final.context_disk_size, expected.context_disk_size);
+    }
+
+    #[test]
+    fn test_persisted_resources_roundtrip() -> Result<(), failure::Error> {
+        let resources = ResourceUtilization {
+            cpu: CpuStats {
+                node: 150,
+                protocol_runners: Some(10),
+            },
+            tezedge_disk: TezedgeDiskData::new(1, 2, 3, 4, 5, 6).into(),
+            ocaml_disk: None,
+            memory: MemoryStats {
+                node: ProcessMemoryStats::new(1000, 100),
+                protocol_runners: Some(ProcessMemoryStats::new(1000, 100)),
+                validators: None,
+            },
+            timestamp: 1,
+            disk_read_bytes: 100,
+            disk_write_bytes: 10,
+            net_rx_bytes: 1000,
+            net_tx_bytes: 500,
+            data_dir: DataDirUsage::new(1000, 500, 400).into(),
+            context_disk_size: Some(12345),
+            head_info: NodeInfo::default(),
+        };
+
+        let persisted: PersistedResourceUtilization =
+            serde_json::from_str(&serde_json::to_string(&resources)?)?;
+        let loaded = ResourceUtilization::from_persisted("tezedge", persisted)?;
+
+        assert_eq!(loaded.timestamp, resources.timestamp);
+        assert_eq!(loaded.tezedge_disk, resources.tezedge_disk);
+        assert_eq!(loaded.ocaml_disk, None);
+        assert_eq!(loaded.memory.node, resources.memory.node);
+        assert_eq!(
+            loaded.memory.protocol_runners,
+            resources.memory.protocol_runners
+        );
+        assert_eq!(loaded.cpu.node, resources.cpu.node);
+        assert_eq!(loaded.disk_read_bytes, resources.disk_read_bytes);
+        assert_eq!(loaded.disk_write_bytes, resources.disk_write_bytes);
+        assert_eq!(loaded.net_rx_bytes, resources.net_rx_bytes);
+        assert_eq!(loaded.net_tx_bytes, resources.net_tx_bytes);
+        assert_eq!(loaded.data_dir, resources.data_dir);
+        assert_eq!(loaded.context_disk_size, resources.context_disk_size);
+
+        Ok(())
     }
 }
@@ -1,14 +1,18 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::collections::HashMap;
 use std::convert::TryInto;
 
 use async_trait::async_trait;
 use failure::{bail, format_err};
 use fs_extra::dir;
+use futures::StreamExt;
 use itertools::Itertools;
 use merge::Merge;
-
+use serde::Deserialize;
+use shiplift::rep::{BlkioStat, NetworkStats};
+use shiplift::Docker;
 use sysinfo::{ProcessExt, System, SystemExt};
 
 use shell::stats::memory::{MemoryData, ProcessMemoryStats};
@@ -78,6 +82,24 @@ impl TezedgeNode {
 
         Ok(memory_stats)
     }
+
+    /// Collects approximate bytes of the context kv-store on disk, as reported by the node itself,
+    /// `None` for in-memory context kv-store
+    pub async fn collect_context_disk_size(port: u16) -> Result<Option<u64>, failure::Error> {
+        let context_stats: ContextStats =
+            match reqwest::get(&format!("http://localhost:{}/stats/context", port)).await {
+                Ok(result) => result.json().await?,
+                Err(e) => bail!("GET context stats error: {}", e),
+            };
+
+        Ok(context_stats.kv_store_disk_size)
+    }
+}
+
+/// Part of the node's context stats (/stats/context) we are interested in
+#[derive(Deserialize)]
+struct ContextStats {
+    kv_store_disk_size: Option<u64>,
 }
 
 pub struct OcamlNode;
@@ -206,6 +228,54 @@ pub trait Node {
         Ok(commit_hash.trim_matches('"').trim_matches('\n').to_string())
     }
 
+    /// Collects total disk read/write bytes (blkio_stats) and network rx/tx bytes (networks)
+    /// for container from single docker stats sample
+    ///
+    /// Note: if container reports no blkio data or has no network, returns zeros for them
+    async fn collect_io_data(container_name: &str) -> Result<ContainerIoStats, failure::Error> {
+        let docker = Docker::new();
+        let stats = match docker.containers().get(container_name).stats().next().await {
+            Some(stats) => stats?,
+            None => return Ok(ContainerIoStats::default()),
+        };
+
+        let (net_rx_bytes, net_tx_bytes) = sum_network_bytes(&stats.networks);
+        Ok(ContainerIoStats {
+            disk_read_bytes: sum_blkio_bytes(&stats.blkio_stats.io_service_bytes_recursive, "read"),
+            disk_write_bytes: sum_blkio_bytes(
+                &stats.blkio_stats.io_service_bytes_recursive,
+                "write",
+            ),
+            net_rx_bytes,
+            net_tx_bytes,
+        })
+    }
+
+    /// Collects cpu usage of the whole container from two consecutive docker stats samples,
+    /// in the same units as [Node::collect_cpu_data] (100 is one fully used cpu)
+    async fn collect_container_cpu_data(container_name: &str) -> Result<i32, failure::Error> {
+        let docker = Docker::new();
+        let container = docker.containers().get(container_name);
+        let mut stats = container.stats();
+        let (previous, current) = match (stats.next().await, stats.next().await) {
+            (Some(previous), Some(current)) => (previous?, current?),
+            _ => bail!("Missing docker stats for container {}", container_name),
+        };
+
+        Ok(cpu_usage_percent(
+            current
+                .cpu_stats
+                .cpu_usage
+                .total_usage
+                .saturating_sub(previous.cpu_stats.cpu_usage.total_usage),
+            current
+                .cpu_stats
+                .system_cpu_usage
+                .saturating_sub(previous.cpu_stats.system_cpu_usage),
+            current.cpu_stats.cpu_usage.percpu_usage.len(),
+        ))
+    }
+
     fn collect_cpu_data(system: &mut System, process_name: &str) -> Result<i32, failure::Error> {
         // get node process
         Ok(system
@@ -216,3 +286,51 @@ pub trait Node {
             .sum::<f32>() as i32)
     }
 }
+
+/// Total io counters of the container, as reported by docker
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ContainerIoStats {
+    pub disk_read_bytes: u64,
+    pub disk_write_bytes: u64,
+    pub net_rx_bytes: u64,
+    pub net_tx_bytes: u64,
+}
+
+/// Cpu usage of the container from the cpu time used by the container and by the whole system (of all `cpus`)
+/// in the same interval, 100 is one fully used cpu
+fn cpu_usage_percent(container_delta: u64, system_delta: u64, cpus: usize) -> i32 {
+    if system_delta == 0 {
+        return 0;
+    }
+    (container_delta as f64 / system_delta as f64 * cpus.max(1) as f64 * 100.0) as i32
+}
+
+fn sum_blkio_bytes(blkio_stats: &[BlkioStat], op: &str) -> u64 {
+    blkio_stats
+        .iter()
+        .filter(|stat| stat.op.eq_ignore_ascii_case(op))
+        .map(|stat| stat.value)
+        .sum()
+}
+
+/// Sums received/transmitted bytes (in this order) over all the network interfaces of the container
+fn sum_network_bytes(networks: &HashMap<String, NetworkStats>) -> (u64, u64) {
+    networks.values().fold((0, 0), |(rx, tx), network| {
+        (rx + network.rx_bytes, tx + network.tx_bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_usage_percent() {
+        // half of the system time on 4 cpus is two fully used cpus
+        assert_eq!(200, cpu_usage_percent(500, 1000, 4));
+        assert_eq!(50, cpu_usage_percent(500, 1000, 1));
+        // cgroup v2 does not report per cpu usage
+        assert_eq!(50, cpu_usage_percent(500, 1000, 0));
+        assert_eq!(0, cpu_usage_percent(500, 0, 4));
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::fmt;
+
+use serde::Serialize;
+use slog::{error, info, warn, Logger};
+use tokio::time::sleep;
+
+use crate::constants::PAGERDUTY_EVENTS_URL;
+use crate::slack::{is_retryable, retry_after, retry_delay};
+
+/// Source of the events, as displayed in pagerduty
+const EVENT_SOURCE: &str = "tezedge-deploy-monitoring";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Critical,
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum EventAction {
+    Trigger,
+    Resolve,
+}
+
+/// Event of the pagerduty Events API v2, events with the same dedup_key belong to the same incident
+#[derive(Debug, Serialize)]
+struct Event<'a> {
+    routing_key: &'a str,
+    event_action: EventAction,
+    dedup_key: &'a str,
+    // required just for the trigger event
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<EventPayload<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct EventPayload<'a> {
+    summary: &'a str,
+    source: &'static str,
+    severity: Severity,
+}
+
+/// Sends the alerts to pagerduty, critical alert triggers an incident, which is resolved, when the alert is gone
+#[derive(Clone)]
+pub struct PagerDutyNotifier {
+    routing_key: String,
+    // how many times is the failed event resent (with exponential backoff 1s, 2s, 4s, ... see [retry_delay])
+    max_retries: u32,
+    // in dry-run mode, the events are only logged, so nobody is paged
+    dry_run: bool,
+    log: Logger,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(routing_key: String, max_retries: u32, dry_run: bool, log: Logger) -> Self {
+        Self {
+            routing_key,
+            max_retries,
+            dry_run,
+            log,
+        }
+    }
+
+    pub async fn trigger(
+        &self,
+        dedup_key: &str,
+        summary: &str,
+        severity: Severity,
+    ) -> Result<(), failure::Error> {
+        self.send_event(&Event {
+            routing_key: &self.routing_key,
+            event_action: EventAction::Trigger,
+            dedup_key,
+            payload: Some(EventPayload {
+                summary,
+                source: EVENT_SOURCE,
+                severity,
+            }),
+        })
+        .await
+    }
+
+    pub async fn resolve(&self, dedup_key: &str) -> Result<(), failure::Error> {
+        self.send_event(&Event {
+            routing_key: &self.routing_key,
+            event_action: EventAction::Resolve,
+            dedup_key,
+            payload: None,
+        })
+        .await
+    }
+
+    async fn send_event(&self, event: &Event<'_>) -> Result<(), failure::Error> {
+        if self.dry_run {
+            info!(
+                self.log,
+                "[DRY-RUN] PagerDuty event not sent: {:?} {}", event.event_action, event.dedup_key
+            );
+            return Ok(());
+        }
+
+        let client = reqwest::Client::new();
+
+        let mut attempt = 0;
+        loop {
+            let res = client.post(PAGERDUTY_EVENTS_URL).json(event).send().await;
+
+            // only rate limiting, server errors and connection errors are worth retrying
+            let retry_after = match res {
+                Ok(response) if is_retryable(response.status()) => {
+                    let retry_after = retry_after(response.headers());
+                    warn!(
+                        self.log,
+                        "PagerDuty event failed with status: {}, attempt: {}",
+                        response.status(),
+                        attempt + 1
+                    );
+                    retry_after
+                }
+                Ok(response) if !response.status().is_success() => {
+                    error!(
+                        self.log,
+                        "PagerDuty event rejected with status: {}, response: {}",
+                        response.status(),
+                        response.text().await.unwrap_or_default()
+                    );
+                    break;
+                }
+                Ok(_) => {
+                    info!(
+                        self.log,
+                        "PagerDuty event sent: {:?} {}", event.event_action, event.dedup_key
+                    );
+                    break;
+                }
+                Err(e) => {
+                    warn!(
+                        self.log,
+                        "PagerDuty event error: {:?}, attempt: {}",
+                        e,
+                        attempt + 1
+                    );
+                    None
+                }
+            };
+
+            if attempt >= self.max_retries {
+                error!(
+                    self.log,
+                    "PagerDuty event was not sent after {} attempts: {:?} {}",
+                    attempt + 1,
+                    event.event_action,
+                    event.dedup_key
+                );
+                break;
+            }
+
+            sleep(retry_delay(attempt, retry_after)).await;
+            attempt += 1;
+        }
+
+        Ok(())
+    }
+}
+
+// the routing key is a secret, so it is not part of the debug output
+impl fmt::Debug for PagerDutyNotifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PagerDutyNotifier")
+            .field("max_retries", &self.max_retries)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization() {
+        let trigger = Event {
+            routing_key: "key",
+            event_action: EventAction::Trigger,
+            dedup_key: "tezedge-Memory",
+            payload: Some(EventPayload {
+                summary: "Memory surpassed the defined threshold",
+                source: EVENT_SOURCE,
+                severity: Severity::Error,
+            }),
+        };
+        assert_eq!(
+            serde_json::json!({
+                "routing_key": "key",
+                "event_action": "trigger",
+                "dedup_key": "tezedge-Memory",
+                "payload": {
+                    "summary": "Memory surpassed the defined threshold",
+                    "source": EVENT_SOURCE,
+                    "severity": "error",
+                }
+            }),
+            serde_json::to_value(&trigger).unwrap()
+        );
+
+        let resolve = Event {
+            routing_key: "key",
+            event_action: EventAction::Resolve,
+            dedup_key: "tezedge-Memory",
+            payload: None,
+        };
+        assert_eq!(
+            serde_json::json!({
+                "routing_key": "key",
+                "event_action": "resolve",
+                "dedup_key": "tezedge-Memory",
+            }),
+            serde_json::to_value(&resolve).unwrap()
+        );
+    }
+}
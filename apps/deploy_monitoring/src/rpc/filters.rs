@@ -4,12 +4,19 @@
 use slog::Logger;
 use warp::Filter;
 
+use crate::image::DeployMonitoringContainer;
 use crate::monitors::resource::{ResourceUtilizationStorage, ResourceUtilizationStorageMap};
-use crate::rpc::handlers::{get_measurements, MeasurementOptions};
+use crate::node::{OcamlNode, TezedgeNode};
+use crate::rpc::handlers::{
+    get_all_measurements, get_measurements, get_metrics, get_node_measurements,
+    handle_slack_command, MeasurementOptions, SLACK_COMMAND_MAX_BODY_SIZE,
+};
 
 pub fn filters(
     log: Logger,
     resource_utilization_storage: ResourceUtilizationStorageMap,
+    metrics_prefix: String,
+    slack_signing_secret: Option<String>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     // Allow cors from any origin
     let cors = warp::cors()
@@ -17,24 +24,39 @@ pub fn filters(
         .allow_headers(vec!["content-type"])
         .allow_methods(vec!["GET"]);
 
-    // TODO: TE-499 - (multiple nodes) rework this to load from a config, where all the nodes all defined
-    let tezedge_resource_utilization_storage = resource_utilization_storage.get("tezedge").unwrap();
-    if let Some(ocaml_resource_utilization_storage) = resource_utilization_storage.get("ocaml") {
-        get_ocaml_measurements_filter(log.clone(), ocaml_resource_utilization_storage.clone())
-            .or(get_tezedge_measurements_filter(
-                log,
-                tezedge_resource_utilization_storage.clone(),
-            ))
-            .with(cors)
-    } else {
-        // This is just a hack to enable only tezedge node
-        get_ocaml_measurements_filter(log.clone(), ResourceUtilizationStorage::default())
-            .or(get_tezedge_measurements_filter(
-                log,
-                tezedge_resource_utilization_storage.clone(),
-            ))
-            .with(cors)
-    }
+    let metrics_filter = get_metrics_filter(metrics_prefix, resource_utilization_storage.clone());
+    let slack_command_filter = slack_command_filter(
+        log.clone(),
+        slack_signing_secret,
+        resource_utilization_storage.clone(),
+    );
+
+    // "tezedge" and "ocaml" are kept as aliases for the default node containers
+    let tezedge_resource_utilization_storage = resource_utilization_storage
+        .get(TezedgeNode::NAME)
+        .cloned()
+        .unwrap_or_default();
+    let ocaml_resource_utilization_storage = resource_utilization_storage
+        .get(OcamlNode::NAME)
+        .cloned()
+        .unwrap_or_default();
+
+    get_ocaml_measurements_filter(log.clone(), ocaml_resource_utilization_storage)
+        .or(get_tezedge_measurements_filter(
+            log.clone(),
+            tezedge_resource_utilization_storage,
+        ))
+        .or(get_node_measurements_filter(
+            log.clone(),
+            resource_utilization_storage.clone(),
+        ))
+        .or(get_all_measurements_filter(
+            log,
+            resource_utilization_storage,
+        ))
+        .or(metrics_filter)
+        .or(slack_command_filter)
+        .with(cors)
 }
 
 pub fn get_tezedge_measurements_filter(
@@ -65,6 +87,63 @@ pub fn get_ocaml_measurements_filter(
         .and_then(get_measurements)
 }
 
+pub fn get_node_measurements_filter(
+    log: Logger,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("resources" / String)
+        .and(warp::get())
+        .and(warp::query::<MeasurementOptions>())
+        .and(with_log(log))
+        .and(warp::any().map(move || resource_utilization_storage.clone()))
+        .and_then(get_node_measurements)
+}
+
+pub fn get_all_measurements_filter(
+    log: Logger,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("resources")
+        .and(warp::get())
+        .and(warp::query::<MeasurementOptions>())
+        .and(with_log(log))
+        .and(warp::any().map(move || resource_utilization_storage.clone()))
+        .and_then(get_all_measurements)
+}
+
+pub fn get_metrics_filter(
+    metrics_prefix: String,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("metrics")
+        .and(warp::get())
+        .and(warp::any().map(move || metrics_prefix.clone()))
+        .and(warp::any().map(move || resource_utilization_storage.clone()))
+        .and_then(get_metrics)
+}
+
+/// Slack slash-command callback, the raw body is needed to verify the request signature
+pub fn slack_command_filter(
+    log: Logger,
+    slack_signing_secret: Option<String>,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("slack" / "command")
+        .and(warp::post())
+        .and(warp::header::optional::<String>(
+            "x-slack-request-timestamp",
+        ))
+        .and(warp::header::optional::<String>("x-slack-signature"))
+        .and(warp::body::content_length_limit(
+            SLACK_COMMAND_MAX_BODY_SIZE,
+        ))
+        .and(warp::body::bytes())
+        .and(with_log(log))
+        .and(warp::any().map(move || slack_signing_secret.clone()))
+        .and(warp::any().map(move || resource_utilization_storage.clone()))
+        .and_then(handle_slack_command)
+}
+
 fn with_log(
     log: Logger,
 ) -> impl Filter<Extract = (Logger,), Error = std::convert::Infallible> + Clone {
@@ -1,25 +1,187 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
 
-use serde::Deserialize;
-use slog::{info, Logger};
+use serde::{Deserialize, Serialize};
+use slog::{info, warn, Logger};
+use sodiumoxide::crypto::auth::hmacsha256;
 use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
 use warp::reject;
 
 use itertools::Itertools;
 
 use crate::constants::MEASUREMENTS_MAX_CAPACITY;
-use crate::monitors::resource::{ResourceUtilization, ResourceUtilizationStorage};
+use crate::monitors::resource::{
+    ResourceUtilization, ResourceUtilizationStorage, ResourceUtilizationStorageMap,
+};
 
 const FE_CAPACITY: usize = 1000;
 
+/// Version of the shape of the json rpc responses, has to be bumped on every incompatible change
+/// (e.g. renamed/removed field), so the clients can detect it instead of mis-parsing the data
+pub const RPC_SCHEMA_VERSION: u32 = 1;
+
+/// Max size in bytes of the slack slash-command request body
+pub const SLACK_COMMAND_MAX_BODY_SIZE: u64 = 16 * 1024;
+
+/// Max age in seconds of the slack request, older requests are rejected to prevent replay attacks
+const SLACK_REQUEST_MAX_AGE: i64 = 5 * 60;
+
+const SLACK_COMMAND_HELP: &str =
+    "Usage: `status` - current resources and head level of the monitored nodes";
+
 #[derive(Debug, Deserialize)]
 pub struct MeasurementOptions {
     limit: Option<usize>,
     offset: Option<usize>,
     every_nth: Option<usize>,
+    // unix timestamps (seconds), parsed in the handler to be able to report invalid values
+    from: Option<String>,
+    to: Option<String>,
+    // server side aggregation of the measurements instead of the raw history, e.g. "minmax"
+    agg: Option<String>,
+}
+
+/// Envelope of the json rpc responses - `{ "schema_version": 1, "data": ... }`
+#[derive(Debug, Serialize)]
+struct Versioned<T> {
+    schema_version: u32,
+    data: T,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorMessage {
+    code: u16,
+    message: String,
+}
+
+/// Inclusive range of timestamps, the measurements are filtered with (no bound means unlimited)
+#[derive(Debug, Default, PartialEq)]
+struct TimestampRange {
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+/// Server side aggregation of the measurements
+#[derive(Debug, PartialEq)]
+enum Aggregation {
+    /// min, max and latest value of the node memory and cpu
+    MinMax,
+}
+
+impl Aggregation {
+    fn parse(options: &MeasurementOptions) -> Result<Option<Self>, String> {
+        match options.agg.as_deref() {
+            None => Ok(None),
+            Some("minmax") => Ok(Some(Aggregation::MinMax)),
+            Some(agg) => Err(format!(
+                "Invalid aggregation: {}, supported aggregations: minmax",
+                agg
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct MinMaxLatest<T> {
+    min: T,
+    max: T,
+    latest: T,
+}
+
+impl<T: Copy + Ord> MinMaxLatest<T> {
+    /// Expects the values ordered from the latest one, as they are stored
+    fn from_values(mut values: impl Iterator<Item = T>) -> Option<Self> {
+        let latest = values.next()?;
+        Some(values.fold(
+            Self {
+                min: latest,
+                max: latest,
+                latest,
+            },
+            |acc, value| Self {
+                min: acc.min.min(value),
+                max: acc.max.max(value),
+                latest: acc.latest,
+            },
+        ))
+    }
+}
+
+/// Memory (resident, in bytes) and cpu (in %) of the node over the retained measurements
+#[derive(Debug, PartialEq, Serialize)]
+struct MinMaxAggregation {
+    measurements: usize,
+    memory: MinMaxLatest<usize>,
+    cpu: MinMaxLatest<i32>,
+}
+
+impl MinMaxAggregation {
+    fn from_measurements(measurements: &VecDeque<ResourceUtilization>) -> Option<Self> {
+        Some(Self {
+            measurements: measurements.len(),
+            memory: MinMaxLatest::from_values(
+                measurements
+                    .iter()
+                    .map(|measurement| measurement.memory().node().resident_mem()),
+            )?,
+            cpu: MinMaxLatest::from_values(
+                measurements
+                    .iter()
+                    .map(|measurement| *measurement.cpu().node()),
+            )?,
+        })
+    }
+}
+
+/// Reply for the measurements of single node, raw history is the default
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum MeasurementsReply {
+    History(VecDeque<ResourceUtilization>),
+    MinMax(Option<MinMaxAggregation>),
+}
+
+impl TimestampRange {
+    fn parse(options: &MeasurementOptions) -> Result<Self, String> {
+        let parse_timestamp = |name: &str, value: &Option<String>| {
+            value
+                .as_ref()
+                .map(|value| {
+                    value.parse::<i64>().map_err(|_| {
+                        format!(
+                            "Invalid '{}' timestamp: {}, expected unix timestamp in seconds",
+                            name, value
+                        )
+                    })
+                })
+                .transpose()
+        };
+
+        let range = Self {
+            from: parse_timestamp("from", &options.from)?,
+            to: parse_timestamp("to", &options.to)?,
+        };
+
+        if let (Some(from), Some(to)) = (range.from, range.to) {
+            if from > to {
+                return Err(format!(
+                    "Invalid timestamp range: 'from' ({}) is greater than 'to' ({})",
+                    from, to
+                ));
+            }
+        }
+
+        Ok(range)
+    }
+
+    fn contains(&self, timestamp: i64) -> bool {
+        self.from.map_or(true, |from| timestamp >= from)
+            && self.to.map_or(true, |to| timestamp <= to)
+    }
 }
 
 pub async fn get_measurements(
@@ -27,11 +189,166 @@ pub async fn get_measurements(
     log: Logger,
     measurements_storage: ResourceUtilizationStorage,
 ) -> Result<impl warp::Reply, reject::Rejection> {
-    let storage = measurements_storage.read().unwrap();
+    let range = match TimestampRange::parse(&options) {
+        Ok(range) => range,
+        Err(message) => return Ok(bad_request(message)),
+    };
+    let aggregation = match Aggregation::parse(&options) {
+        Ok(aggregation) => aggregation,
+        Err(message) => return Ok(bad_request(message)),
+    };
+
+    Ok(versioned_reply(
+        &reply_measurements(
+            &options,
+            &range,
+            aggregation.as_ref(),
+            &log,
+            &measurements_storage,
+        ),
+        StatusCode::OK,
+    ))
+}
+
+/// Measurements of the node identified by its container name
+pub async fn get_node_measurements(
+    container_name: String,
+    options: MeasurementOptions,
+    log: Logger,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    let range = match TimestampRange::parse(&options) {
+        Ok(range) => range,
+        Err(message) => return Ok(bad_request(message)),
+    };
+    let aggregation = match Aggregation::parse(&options) {
+        Ok(aggregation) => aggregation,
+        Err(message) => return Ok(bad_request(message)),
+    };
+
+    match resource_utilization_storage.get(&container_name) {
+        Some(measurements_storage) => Ok(versioned_reply(
+            &reply_measurements(
+                &options,
+                &range,
+                aggregation.as_ref(),
+                &log,
+                measurements_storage,
+            ),
+            StatusCode::OK,
+        )),
+        None => Err(reject::not_found()),
+    }
+}
+
+/// Measurements of all the nodes keyed by the container name
+pub async fn get_all_measurements(
+    options: MeasurementOptions,
+    log: Logger,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    let range = match TimestampRange::parse(&options) {
+        Ok(range) => range,
+        Err(message) => return Ok(bad_request(message)),
+    };
+    let aggregation = match Aggregation::parse(&options) {
+        Ok(aggregation) => aggregation,
+        Err(message) => return Ok(bad_request(message)),
+    };
+
+    let measurements: BTreeMap<&str, MeasurementsReply> = resource_utilization_storage
+        .iter()
+        .map(|(container_name, measurements_storage)| {
+            (
+                container_name.as_str(),
+                reply_measurements(
+                    &options,
+                    &range,
+                    aggregation.as_ref(),
+                    &log,
+                    measurements_storage,
+                ),
+            )
+        })
+        .collect();
+
+    Ok(versioned_reply(&measurements, StatusCode::OK))
+}
+
+fn bad_request(message: String) -> warp::reply::WithStatus<warp::reply::Json> {
+    error_reply(StatusCode::BAD_REQUEST, message)
+}
+
+fn error_reply(status: StatusCode, message: String) -> warp::reply::WithStatus<warp::reply::Json> {
+    versioned_reply(
+        &ErrorMessage {
+            code: status.as_u16(),
+            message,
+        },
+        status,
+    )
+}
+
+/// Json reply wrapped in the [Versioned] envelope
+fn versioned_reply<T: Serialize>(
+    data: &T,
+    status: StatusCode,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&Versioned {
+            schema_version: RPC_SCHEMA_VERSION,
+            data,
+        }),
+        status,
+    )
+}
+
+fn reply_measurements(
+    options: &MeasurementOptions,
+    range: &TimestampRange,
+    aggregation: Option<&Aggregation>,
+    log: &Logger,
+    measurements_storage: &ResourceUtilizationStorage,
+) -> MeasurementsReply {
+    match aggregation {
+        // aggregated over the whole retained window (in range), not just the returned chunk
+        Some(Aggregation::MinMax) => {
+            MeasurementsReply::MinMax(MinMaxAggregation::from_measurements(
+                &measurements_in_range(range, measurements_storage),
+            ))
+        }
+        None => MeasurementsReply::History(process_measurements(
+            options,
+            range,
+            log,
+            measurements_storage,
+        )),
+    }
+}
+
+fn measurements_in_range(
+    range: &TimestampRange,
+    measurements_storage: &ResourceUtilizationStorage,
+) -> VecDeque<ResourceUtilization> {
+    measurements_storage
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|measurement| range.contains(*measurement.timestamp()))
+        .cloned()
+        .collect()
+}
+
+fn process_measurements(
+    options: &MeasurementOptions,
+    range: &TimestampRange,
+    log: &Logger,
+    measurements_storage: &ResourceUtilizationStorage,
+) -> VecDeque<ResourceUtilization> {
+    let storage = measurements_in_range(range, measurements_storage);
 
-    let ret: VecDeque<ResourceUtilization> = if let Some(every_nth) = options.every_nth {
+    if let Some(every_nth) = options.every_nth {
         storage
-            .clone()
             .into_iter()
             .chunks(every_nth)
             .into_iter()
@@ -42,7 +359,6 @@ pub async fn get_measurements(
         let chunk_by = storage.len() / FE_CAPACITY + 1;
         info!(log, "LEN: {}, CHUNKING_BY: {}", storage.len(), chunk_by);
         storage
-            .clone()
             .into_iter()
             .chunks(chunk_by)
             .into_iter()
@@ -51,14 +367,421 @@ pub async fn get_measurements(
             .collect()
     } else {
         storage
-            .clone()
             .into_iter()
             .take(options.limit.unwrap_or(MEASUREMENTS_MAX_CAPACITY))
             .collect()
+    }
+}
+
+/// Renders the latest measurement of every node in prometheus text exposition format
+pub async fn get_metrics(
+    metrics_prefix: String,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    let latest_measurements = latest_measurements(&resource_utilization_storage);
+
+    Ok(warp::reply::with_status(
+        warp::reply::with_header(
+            render_metrics(&metrics_prefix, &latest_measurements),
+            "content-type",
+            "text/plain; version=0.0.4",
+        ),
+        StatusCode::OK,
+    ))
+}
+
+/// Latest measurement for every node, sorted by container name to have stable output
+fn latest_measurements(
+    resource_utilization_storage: &ResourceUtilizationStorageMap,
+) -> Vec<(&str, ResourceUtilization)> {
+    let mut latest_measurements: Vec<(&str, ResourceUtilization)> = resource_utilization_storage
+        .iter()
+        .filter_map(|(node_tag, storage)| {
+            storage
+                .read()
+                .ok()
+                .and_then(|storage| storage.front().cloned())
+                .map(|measurement| (node_tag.as_str(), measurement))
+        })
+        .collect();
+    latest_measurements.sort_by_key(|(node_tag, _)| *node_tag);
+    latest_measurements
+}
+
+fn render_metrics(metrics_prefix: &str, measurements: &[(&str, ResourceUtilization)]) -> String {
+    let gauges: [(&str, &str, fn(&ResourceUtilization) -> u64); 8] = [
+        ("node_memory_bytes", "Resident memory of the node", |m| {
+            m.memory().node().resident_mem() as u64
+        }),
+        (
+            "node_virtual_memory_bytes",
+            "Virtual memory of the node",
+            |m| m.memory().node().virtual_mem() as u64,
+        ),
+        ("node_cpu_percent", "Cpu usage of the node", |m| {
+            *m.cpu().node() as u64
+        }),
+        (
+            "disk_read_bytes",
+            "Total bytes read from disk by the container",
+            |m| *m.disk_read_bytes(),
+        ),
+        (
+            "disk_write_bytes",
+            "Total bytes written to disk by the container",
+            |m| *m.disk_write_bytes(),
+        ),
+        (
+            "network_rx_bytes",
+            "Total bytes received by the container over all its networks",
+            |m| *m.net_rx_bytes(),
+        ),
+        (
+            "network_tx_bytes",
+            "Total bytes transmitted by the container over all its networks",
+            |m| *m.net_tx_bytes(),
+        ),
+        ("head_level", "Level of the current head of the node", |m| {
+            *m.head_info().level()
+        }),
+    ];
+
+    let mut metrics = String::new();
+    for (name, help, value) in gauges.iter() {
+        let _ = writeln!(metrics, "# HELP {}_{} {}", metrics_prefix, name, help);
+        let _ = writeln!(metrics, "# TYPE {}_{} gauge", metrics_prefix, name);
+        for (node_tag, measurement) in measurements {
+            let _ = writeln!(
+                metrics,
+                "{}_{}{{node=\"{}\"}} {}",
+                metrics_prefix,
+                name,
+                node_tag,
+                value(measurement)
+            );
+        }
+    }
+    metrics
+}
+
+#[derive(Debug, Deserialize)]
+struct SlackCommand {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SlackCommandResponse {
+    response_type: &'static str,
+    text: String,
+}
+
+/// Handles slack slash-command callback, e.g. `/tezedge status`, the reply is visible just to the caller
+pub async fn handle_slack_command(
+    timestamp: Option<String>,
+    signature: Option<String>,
+    body: Bytes,
+    log: Logger,
+    slack_signing_secret: Option<String>,
+    resource_utilization_storage: ResourceUtilizationStorageMap,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    // endpoint is enabled just with configured signing secret
+    let slack_signing_secret = match slack_signing_secret {
+        Some(slack_signing_secret) => slack_signing_secret,
+        None => return Err(reject::not_found()),
+    };
+
+    if let Err(message) = verify_slack_signature(
+        &slack_signing_secret,
+        timestamp.as_deref(),
+        signature.as_deref(),
+        &body,
+        chrono::Utc::now().timestamp(),
+    ) {
+        warn!(log, "Rejected slack command: {}", message);
+        return Ok(error_reply(StatusCode::UNAUTHORIZED, message));
+    }
+
+    let command: SlackCommand = match serde_urlencoded::from_bytes(&body) {
+        Ok(command) => command,
+        Err(e) => return Ok(bad_request(format!("Invalid slack command: {}", e))),
     };
 
+    let text = match command.text.trim() {
+        "" | "status" => render_status(&latest_measurements(&resource_utilization_storage)),
+        _ => SLACK_COMMAND_HELP.to_string(),
+    };
+
+    // slack expects its own format of the response, so it is not wrapped in the versioned envelope
     Ok(warp::reply::with_status(
-        warp::reply::json(&ret),
+        warp::reply::json(&SlackCommandResponse {
+            response_type: "ephemeral",
+            text,
+        }),
         StatusCode::OK,
     ))
 }
+
+/// Verifies the request signature according to https://api.slack.com/authentication/verifying-requests-from-slack
+fn verify_slack_signature(
+    slack_signing_secret: &str,
+    timestamp: Option<&str>,
+    signature: Option<&str>,
+    body: &[u8],
+    now: i64,
+) -> Result<(), String> {
+    let (timestamp, signature) = match (timestamp, signature) {
+        (Some(timestamp), Some(signature)) => (timestamp, signature),
+        _ => return Err("Missing slack signature headers".to_string()),
+    };
+
+    let request_time = timestamp
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid slack request timestamp: {}", timestamp))?;
+    if (now - request_time).abs() > SLACK_REQUEST_MAX_AGE {
+        return Err(format!(
+            "Slack request timestamp {} is out of the allowed window",
+            timestamp
+        ));
+    }
+
+    let mut state = hmacsha256::State::init(slack_signing_secret.as_bytes());
+    state.update(b"v0:");
+    state.update(timestamp.as_bytes());
+    state.update(b":");
+    state.update(body);
+    let expected = format!("v0={}", hex::encode(state.finalize()));
+
+    // constant time comparison, not to leak the expected signature
+    if sodiumoxide::utils::memcmp(expected.as_bytes(), signature.as_bytes()) {
+        Ok(())
+    } else {
+        Err("Invalid slack signature".to_string())
+    }
+}
+
+fn render_status(measurements: &[(&str, ResourceUtilization)]) -> String {
+    if measurements.is_empty() {
+        return "No measurements available yet".to_string();
+    }
+
+    let mut status = String::new();
+    for (node_tag, measurement) in measurements {
+        let _ = writeln!(
+            status,
+            "*{}* - head level: {}, memory: {}MB, cpu: {}%",
+            node_tag,
+            measurement.head_info().level(),
+            measurement.memory().node().resident_mem() / 1024 / 1024,
+            measurement.cpu().node(),
+        );
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::monitors::resource::ResourceUtilizationBuilder;
+
+    use super::*;
+
+    fn options(from: Option<&str>, to: Option<&str>) -> MeasurementOptions {
+        MeasurementOptions {
+            limit: None,
+            offset: None,
+            every_nth: None,
+            from: from.map(|from| from.to_string()),
+            to: to.map(|to| to.to_string()),
+            agg: None,
+        }
+    }
+
+    fn measurement(timestamp: i64, resident_mem: usize, cpu: i32) -> ResourceUtilization {
+        ResourceUtilizationBuilder::default()
+            .timestamp(timestamp)
+            .memory(0, resident_mem)
+            .cpu(cpu, None)
+            .build()
+    }
+
+    #[test]
+    fn test_timestamp_range() {
+        // no range means full history
+        let range = TimestampRange::parse(&options(None, None)).unwrap();
+        assert_eq!(range, TimestampRange::default());
+        assert!(range.contains(0));
+        assert!(range.contains(i64::MAX));
+
+        let range = TimestampRange::parse(&options(Some("100"), Some("200"))).unwrap();
+        assert!(!range.contains(99));
+        assert!(range.contains(100));
+        assert!(range.contains(200));
+        assert!(!range.contains(201));
+
+        let range = TimestampRange::parse(&options(Some("100"), None)).unwrap();
+        assert!(!range.contains(99));
+        assert!(range.contains(i64::MAX));
+
+        assert!(TimestampRange::parse(&options(Some("yesterday"), None)).is_err());
+        assert!(TimestampRange::parse(&options(None, Some("1.5"))).is_err());
+        assert!(TimestampRange::parse(&options(Some("200"), Some("100"))).is_err());
+    }
+
+    const SLACK_SIGNING_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const SLACK_TIMESTAMP: &str = "1531420618";
+    const SLACK_SIGNATURE: &str =
+        "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+    const SLACK_BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+
+    #[test]
+    fn test_aggregation_parse() {
+        let mut options = options(None, None);
+        assert_eq!(Aggregation::parse(&options), Ok(None));
+
+        options.agg = Some("minmax".to_string());
+        assert_eq!(Aggregation::parse(&options), Ok(Some(Aggregation::MinMax)));
+
+        options.agg = Some("avg".to_string());
+        assert!(Aggregation::parse(&options).is_err());
+    }
+
+    #[test]
+    fn test_min_max_aggregation() {
+        assert_eq!(MinMaxAggregation::from_measurements(&VecDeque::new()), None);
+
+        // latest measurements are in the front
+        let measurements: VecDeque<ResourceUtilization> = vec![
+            measurement(3, 2000, 40),
+            measurement(2, 3000, 10),
+            measurement(1, 1000, 90),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            MinMaxAggregation::from_measurements(&measurements),
+            Some(MinMaxAggregation {
+                measurements: 3,
+                memory: MinMaxLatest {
+                    min: 1000,
+                    max: 3000,
+                    latest: 2000,
+                },
+                cpu: MinMaxLatest {
+                    min: 10,
+                    max: 90,
+                    latest: 40,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_versioned_envelope() {
+        let reply = Versioned {
+            schema_version: RPC_SCHEMA_VERSION,
+            data: &ErrorMessage {
+                code: 400,
+                message: "Invalid request".to_string(),
+            },
+        };
+        assert_eq!(
+            serde_json::json!({
+                "schema_version": 1,
+                "data": {
+                    "code": 400,
+                    "message": "Invalid request",
+                }
+            }),
+            serde_json::to_value(&reply).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_slack_signature() {
+        // example request from the slack documentation
+        let now = SLACK_TIMESTAMP.parse::<i64>().unwrap() + 10;
+        assert!(verify_slack_signature(
+            SLACK_SIGNING_SECRET,
+            Some(SLACK_TIMESTAMP),
+            Some(SLACK_SIGNATURE),
+            SLACK_BODY.as_bytes(),
+            now
+        )
+        .is_ok());
+
+        // tampered body
+        assert!(verify_slack_signature(
+            SLACK_SIGNING_SECRET,
+            Some(SLACK_TIMESTAMP),
+            Some(SLACK_SIGNATURE),
+            SLACK_BODY.replace("text=", "text=status").as_bytes(),
+            now
+        )
+        .is_err());
+
+        // different secret
+        assert!(verify_slack_signature(
+            "another-secret",
+            Some(SLACK_TIMESTAMP),
+            Some(SLACK_SIGNATURE),
+            SLACK_BODY.as_bytes(),
+            now
+        )
+        .is_err());
+
+        // replayed request
+        assert!(verify_slack_signature(
+            SLACK_SIGNING_SECRET,
+            Some(SLACK_TIMESTAMP),
+            Some(SLACK_SIGNATURE),
+            SLACK_BODY.as_bytes(),
+            now + SLACK_REQUEST_MAX_AGE
+        )
+        .is_err());
+
+        // missing headers
+        assert!(verify_slack_signature(
+            SLACK_SIGNING_SECRET,
+            None,
+            Some(SLACK_SIGNATURE),
+            SLACK_BODY.as_bytes(),
+            now
+        )
+        .is_err());
+        assert!(verify_slack_signature(
+            SLACK_SIGNING_SECRET,
+            Some(SLACK_TIMESTAMP),
+            None,
+            SLACK_BODY.as_bytes(),
+            now
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_slack_command_text() {
+        let command: SlackCommand = serde_urlencoded::from_str(SLACK_BODY).unwrap();
+        assert_eq!(command.text, "");
+
+        let command: SlackCommand =
+            serde_urlencoded::from_str("command=%2Ftezedge&text=status").unwrap();
+        assert_eq!(command.text, "status");
+    }
+
+    #[test]
+    fn test_render_status() {
+        assert_eq!(render_status(&[]), "No measurements available yet");
+
+        let measurement = ResourceUtilizationBuilder::default()
+            .timestamp(1)
+            .memory(0, 536870912)
+            .cpu(35, None)
+            .build();
+
+        assert_eq!(
+            render_status(&[("tezedge-node", measurement)]),
+            "*tezedge-node* - head level: 0, memory: 512MB, cpu: 35%\n"
+        );
+    }
+}
@@ -13,9 +13,16 @@ pub fn spawn_rpc_server(
     rpc_port: u16,
     log: Logger,
     resource_utilization: ResourceUtilizationStorageMap,
+    metrics_prefix: String,
+    slack_signing_secret: Option<String>,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
-        let api = filters::filters(log.clone(), resource_utilization.clone());
+        let api = filters::filters(
+            log.clone(),
+            resource_utilization.clone(),
+            metrics_prefix,
+            slack_signing_secret,
+        );
 
         warp::serve(api).run(([0, 0, 0, 0], rpc_port)).await;
     })
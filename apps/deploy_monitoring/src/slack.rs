@@ -3,14 +3,23 @@
 
 use std::collections::HashMap;
 
-use reqwest::header::AUTHORIZATION;
-use slog::{error, info, Logger};
+use reqwest::header::{HeaderMap, AUTHORIZATION, RETRY_AFTER};
+use reqwest::StatusCode;
+use slog::{error, info, warn, Logger};
+use tokio::time::{sleep, Duration};
+
+/// Upper bound of the delay before the failed message is resent, applies also to the Retry-After sent by slack
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct SlackServer {
     monitor_channel_url: String,
     auth_token: String,
     channel: String,
+    // how many times is the failed message resent (with exponential backoff 1s, 2s, 4s, ... up to [MAX_RETRY_DELAY])
+    max_retries: u32,
+    // in dry-run mode, all the messages are prefixed with [DRY-RUN]
+    dry_run: bool,
     log: Logger,
 }
 
@@ -19,12 +28,16 @@ impl SlackServer {
         monitor_channel_url: String,
         auth_token: String,
         channel: String,
+        max_retries: u32,
+        dry_run: bool,
         log: Logger,
     ) -> Self {
         Self {
             monitor_channel_url,
             auth_token,
             channel,
+            max_retries,
+            dry_run,
             log,
         }
     }
@@ -32,26 +45,66 @@ impl SlackServer {
     pub async fn send_message(&self, text: &str) -> Result<(), failure::Error> {
         let client = reqwest::Client::new();
 
+        let text = &self.with_dry_run_prefix(text);
         let mut map = HashMap::new();
-        map.insert("text", text);
+        map.insert("text", text.as_str());
 
-        let res = client
-            .post(&self.monitor_channel_url)
-            .json(&map)
-            .send()
-            .await;
+        let mut attempt = 0;
+        loop {
+            let res = client
+                .post(&self.monitor_channel_url)
+                .json(&map)
+                .send()
+                .await;
+
+            // only rate limiting, server errors and connection errors are worth retrying
+            let retry_after = match res {
+                Ok(response) if is_retryable(response.status()) => {
+                    let retry_after = retry_after(response.headers());
+                    warn!(
+                        self.log,
+                        "Slack message failed with status: {}, attempt: {}",
+                        response.status(),
+                        attempt + 1
+                    );
+                    retry_after
+                }
+                Ok(_) => {
+                    info!(self.log, "Slack message sent: {}", text);
+                    break;
+                }
+                Err(e) => {
+                    warn!(
+                        self.log,
+                        "Slack message error: {:?}, attempt: {}",
+                        e,
+                        attempt + 1
+                    );
+                    None
+                }
+            };
 
-        match res {
-            Ok(_) => info!(self.log, "Slack message sent: {}", text),
-            Err(e) => error!(self.log, "Slack message error: {:?}", e),
+            if attempt >= self.max_retries {
+                error!(
+                    self.log,
+                    "Slack message was not sent after {} attempts: {}",
+                    attempt + 1,
+                    text
+                );
+                break;
+            }
+
+            sleep(retry_delay(attempt, retry_after)).await;
+            attempt += 1;
         }
 
         Ok(())
     }
 
     pub async fn upload_file(&self, text: &str, file_content: &str) -> Result<(), failure::Error> {
+        let text = self.with_dry_run_prefix(text);
         let params = [
-            ("initial_comment", text),
+            ("initial_comment", text.as_str()),
             ("content", file_content),
             ("channels", &self.channel),
         ];
@@ -72,4 +125,88 @@ impl SlackServer {
         );
         Ok(())
     }
+
+    fn with_dry_run_prefix(&self, text: &str) -> String {
+        if self.dry_run {
+            format!("[DRY-RUN] {}", text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+pub(crate) fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parses the Retry-After header, only the delay in seconds is supported
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Delay before the next attempt, Retry-After (if any) or exponential backoff, capped by [MAX_RETRY_DELAY]
+pub(crate) fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    let delay = retry_after
+        .unwrap_or_else(|| Duration::from_secs(1_u64.checked_shl(attempt).unwrap_or(u64::MAX)));
+    delay.min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+
+        assert!(!is_retryable(StatusCode::OK));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(None, retry_after(&headers));
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(" 7 "));
+        assert_eq!(Some(Duration::from_secs(7)), retry_after(&headers));
+
+        // http date is not supported
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(None, retry_after(&headers));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        // exponential backoff
+        assert_eq!(Duration::from_secs(1), retry_delay(0, None));
+        assert_eq!(Duration::from_secs(2), retry_delay(1, None));
+        assert_eq!(Duration::from_secs(8), retry_delay(3, None));
+
+        // capped, even for the shift overflow
+        assert_eq!(MAX_RETRY_DELAY, retry_delay(10, None));
+        assert_eq!(MAX_RETRY_DELAY, retry_delay(100, None));
+
+        // Retry-After takes precedence, but is capped too
+        assert_eq!(
+            Duration::from_secs(5),
+            retry_delay(3, Some(Duration::from_secs(5)))
+        );
+        assert_eq!(
+            MAX_RETRY_DELAY,
+            retry_delay(0, Some(Duration::from_secs(3600)))
+        );
+    }
 }
@@ -26,6 +26,7 @@ use shell::PeerConnectionThreshold;
 use storage::context::actions::action_file_storage::ActionFileStorage;
 use storage::context::actions::context_action_storage::ContextActionStorage;
 use storage::context::actions::ContextActionStoreBackend;
+use storage::context::gc::GcStrategy;
 use storage::context::kv_store::SupportedContextKeyValueStore;
 use storage::context::ActionRecorder;
 use storage::initializer::{
@@ -158,6 +159,7 @@ pub struct Storage {
 
     // merkle cfg
     pub context_kv_store: ContextKvStoreConfiguration,
+    pub context_kv_store_gc: GcStrategy,
     // context actions cfg
     pub merkle_context_actions_store: Option<RocksDbConfig<ContextActionsRocksDbTableInitializer>>,
 
@@ -337,6 +339,12 @@ pub fn tezos_app() -> App<'static, 'static> {
             .value_name("NUM")
             .help("Max number of threads used by database configuration. If not specified, then number of threads equal to CPU cores.")
             .validator(parse_validator_fn!(usize, "Value must be a valid number")))
+        .arg(Arg::with_name("db-cfg-create-missing-column-families")
+            .long("db-cfg-create-missing-column-families")
+            .takes_value(true)
+            .value_name("BOOL")
+            .help("Create column families missing in the existing databases (e.g. added by newer version) on start, otherwise start fails. Default: true")
+            .validator(parse_validator_fn!(bool, "Value must be a valid bool")))
         .arg(Arg::with_name("bootstrap-lookup-address")
             .long("bootstrap-lookup-address")
             .takes_value(true)
@@ -573,6 +581,12 @@ pub fn tezos_app() -> App<'static, 'static> {
             .value_name("STRING")
             .possible_values(&SupportedContextKeyValueStore::possible_values())
             .help("Choose the merkle storege backend - supported backends: 'rocksdb', 'sled', 'inmem', 'btree'"))
+        .arg(Arg::with_name("context-kv-store-gc")
+            .long("context-kv-store-gc")
+            .takes_value(true)
+            .value_name("STRING")
+            .possible_values(&GcStrategy::possible_values())
+            .help("Choose the garbage collection for merkle storage backend - supported strategies: 'none', 'mark_sweep', 'mark_move' (only for 'inmem' and 'btree')"))
         .arg(Arg::with_name("compute-context-action-tree-hashes")
             .long("compute-context-action-tree-hashes")
             .takes_value(true)
@@ -957,12 +971,19 @@ impl Environment {
                             .expect("Provided value cannot be converted to number")
                     });
 
+                let create_missing_column_families = args
+                    .value_of("db-cfg-create-missing-column-families")
+                    .unwrap_or("true")
+                    .parse::<bool>()
+                    .expect("Provided value cannot be converted to bool");
+
                 let db = RocksDbConfig {
                     cache_size: Storage::LRU_CACHE_SIZE_96MB,
                     expected_db_version: Storage::DB_STORAGE_VERSION,
                     db_path: db_path.join("db"),
                     columns: DbsRocksDbTableInitializer,
                     threads: db_threads_count,
+                    create_missing_column_families,
                 };
 
                 let backends: HashSet<String> = match args.values_of("actions-store-backend") {
@@ -982,6 +1003,7 @@ impl Environment {
                                 db_path: db_path.join("context_actions"),
                                 columns: ContextActionsRocksDbTableInitializer,
                                 threads: db_context_actions_threads_count,
+                                create_missing_column_families,
                             });
                             ContextActionStoreBackend::RocksDB
                         }
@@ -1014,6 +1036,7 @@ impl Environment {
                                 db_path: db_path.join("context"),
                                 columns: ContextRocksDbTableInitializer,
                                 threads: db_context_threads_count,
+                                create_missing_column_families,
                             })
                         }
                         SupportedContextKeyValueStore::Sled { .. } => {
@@ -1034,6 +1057,19 @@ impl Environment {
                         )
                     });
 
+                let context_kv_store_gc = args
+                    .value_of("context-kv-store-gc")
+                    .map(|v| {
+                        v.parse::<GcStrategy>().unwrap_or_else(|e| {
+                            panic!(
+                                "Expecting one value from {:?}, error: {:?}",
+                                GcStrategy::possible_values(),
+                                e
+                            )
+                        })
+                    })
+                    .unwrap_or_default();
+
                 let compute_context_action_tree_hashes = args
                     .value_of("compute-context-action-tree-hashes")
                     .unwrap_or("false")
@@ -1047,6 +1083,7 @@ impl Environment {
                     compute_context_action_tree_hashes,
                     context_action_recorders,
                     context_kv_store,
+                    context_kv_store_gc,
                     merkle_context_actions_store,
                     patch_context: {
                         match args.value_of("sandbox-patch-context-json-file") {
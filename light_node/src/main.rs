@@ -517,6 +517,7 @@ fn main() {
     let merkle = Arc::new(Mutex::new(
         initialize_merkle(
             &env.storage.context_kv_store,
+            &env.storage.context_kv_store_gc,
             &main_chain,
             &log,
             &mut caches,
@@ -94,6 +94,8 @@ impl Monitor {
             if msg.current_branch().current_head().level() > 0 {
                 self.bootstrap_monitor
                     .set_level(msg.current_branch().current_head().level() as usize);
+                self.block_application_monitor
+                    .remote_head_level_received(msg.current_branch().current_head().level());
             }
         }
 
@@ -302,12 +304,17 @@ impl Receive<ShellChannelMsg> for Monitor {
                 // update stats for block header
                 self.chain_monitor.process_block_header(msg.level);
             }
-            ShellChannelMsg::NewCurrentHead(head, ..) => {
+            ShellChannelMsg::NewCurrentHead(head, _, application_time) => {
                 // update stats for block applications
                 self.chain_monitor.process_block_application(*head.level());
 
                 self.blocks_monitor.block_was_applied_by_protocol();
-                self.block_application_monitor.block_was_applied(head);
+                // shell does not announce start of the sync, so much lower head means, that we are syncing again
+                if self.block_application_monitor.is_fresh_sync(&head) {
+                    self.block_application_monitor.reset();
+                }
+                self.block_application_monitor
+                    .block_was_applied(head, application_time);
             }
             ShellChannelMsg::AllBlockOperationsReceived(msg) => {
                 self.bootstrap_monitor.increase_block_count();
@@ -1,36 +1,133 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 use tezos_messages::Head;
 
-use crate::websocket::handler_messages::{BlockApplicationMessage, BlockInfo};
+use crate::websocket::handler_messages::{
+    ApplicationTimePercentiles, BlockApplicationMessage, BlockInfo,
+};
+
+/// Default weight of the latest speed in the smoothed (EMA) speed
+pub const DEFAULT_SMOOTHING_FACTOR: f32 = 0.2;
+
+/// Max count of the latest block application times used for percentiles
+pub const APPLICATION_TIMES_MAX_CAPACITY: usize = 1024;
+
+/// Min count of levels, which the new head has to be below the last applied block to be handled as a fresh sync,
+/// reorgs are just a few levels deep
+pub const FRESH_SYNC_MIN_LEVELS_BELOW: i32 = 100;
 
 pub struct ApplicationMonitor {
     total_applied: usize,
+    /// Level, from which are applied blocks counted (None means, that it is resolved by the next applied block)
+    base_level: Option<usize>,
     current_applied: usize,
     last_applied_block: Option<Head>,
     first_update: Instant,
     last_update: Instant,
+    /// Weight (0.0 - 1.0) of the latest current speed in the smoothed speed
+    smoothing_factor: f32,
+    smoothed_speed: Option<f32>,
+    /// Latest block application times, the oldest ones are dropped on overflow
+    application_times: VecDeque<Duration>,
+    /// The highest head level announced by peers
+    remote_head_level: Option<i32>,
 }
 
 impl ApplicationMonitor {
     pub fn new() -> Self {
+        Self::with_smoothing_factor(DEFAULT_SMOOTHING_FACTOR)
+    }
+
+    pub fn with_smoothing_factor(smoothing_factor: f32) -> Self {
         let now = Instant::now();
         Self {
             total_applied: 0,
+            base_level: Some(0),
             current_applied: 0,
             last_applied_block: None,
             first_update: now,
             last_update: now,
+            smoothing_factor: smoothing_factor.max(0f32).min(1f32),
+            smoothed_speed: None,
+            application_times: VecDeque::with_capacity(APPLICATION_TIMES_MAX_CAPACITY),
+            remote_head_level: None,
         }
     }
 
-    pub fn block_was_applied(&mut self, block_info: Head) {
-        self.total_applied = *block_info.level() as usize;
+    pub fn block_was_applied(&mut self, block_info: Head, application_time: Option<Duration>) {
+        let level = *block_info.level() as usize;
+        let base_level = *self.base_level.get_or_insert(level.saturating_sub(1));
+        self.total_applied = level.saturating_sub(base_level);
         self.current_applied += 1;
         self.last_applied_block = Some(block_info);
+
+        if let Some(application_time) = application_time {
+            if self.application_times.len() >= APPLICATION_TIMES_MAX_CAPACITY {
+                self.application_times.pop_front();
+            }
+            self.application_times.push_back(application_time);
+        }
+    }
+
+    /// Remembers the remote (peer's) head level, if it is the highest one seen so far
+    pub fn remote_head_level_received(&mut self, level: i32) {
+        self.remote_head_level = Some(match self.remote_head_level {
+            Some(remote_head_level) => remote_head_level.max(level),
+            None => level,
+        });
+    }
+
+    /// How many levels is the last applied block behind the remote head, None if remote head is not known
+    pub fn levels_behind(&self) -> Option<i32> {
+        let applied_level = match &self.last_applied_block {
+            Some(block) => *block.level(),
+            None => 0,
+        };
+        self.remote_head_level
+            .map(|remote_head_level| (remote_head_level - applied_level).max(0))
+    }
+
+    /// Starts counting from scratch, so the speeds (and application times) reflect just blocks applied after the reset
+    pub fn reset(&mut self) {
+        let now = Instant::now();
+        self.total_applied = 0;
+        self.base_level = None;
+        self.current_applied = 0;
+        self.last_applied_block = None;
+        self.application_times.clear();
+        self.first_update = now;
+        self.last_update = now;
+        self.smoothed_speed = None;
+    }
+
+    /// Returns true, if the new head is more than [FRESH_SYNC_MIN_LEVELS_BELOW] levels lower than the last applied one,
+    /// which means, that sync started again (e.g. chain was switched), not just a reorg
+    pub fn is_fresh_sync(&self, new_head: &Head) -> bool {
+        match &self.last_applied_block {
+            Some(last_applied_block) => {
+                last_applied_block.level() - new_head.level() > FRESH_SYNC_MIN_LEVELS_BELOW
+            }
+            None => false,
+        }
+    }
+
+    pub fn application_time_percentiles(&self) -> Option<ApplicationTimePercentiles> {
+        if self.application_times.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = self.application_times.iter().copied().collect();
+        sorted.sort_unstable();
+
+        Some(ApplicationTimePercentiles {
+            p50: percentile_ms(&sorted, 50),
+            p95: percentile_ms(&sorted, 95),
+            p99: percentile_ms(&sorted, 99),
+        })
     }
 
     pub fn avg_speed(&self) -> f32 {
@@ -51,10 +148,19 @@ impl ApplicationMonitor {
             None
         };
 
+        let current_speed = self.current_speed();
+        let smoothed_speed =
+            exponential_moving_average(self.smoothed_speed, current_speed, self.smoothing_factor);
+        self.smoothed_speed = Some(smoothed_speed);
+
         let ret = BlockApplicationMessage {
-            current_application_speed: self.current_speed(),
+            current_application_speed: current_speed,
             average_application_speed: self.avg_speed(),
+            smoothed_application_speed: smoothed_speed,
             last_applied_block: last_block,
+            application_time_percentiles: self.application_time_percentiles(),
+            remote_head_level: self.remote_head_level,
+            levels_behind: self.levels_behind(),
         };
 
         self.current_applied = 0;
@@ -62,3 +168,140 @@ impl ApplicationMonitor {
         ret
     }
 }
+
+/// Smooths `current` value with the `previous` average, the first value is taken as it is
+fn exponential_moving_average(previous: Option<f32>, current: f32, smoothing_factor: f32) -> f32 {
+    match previous {
+        Some(previous) if previous.is_finite() => {
+            smoothing_factor * current + (1f32 - smoothing_factor) * previous
+        }
+        _ => current,
+    }
+}
+
+/// Nearest-rank percentile of already sorted (non-empty) durations, in milliseconds
+fn percentile_ms(sorted: &[Duration], percentile: usize) -> f32 {
+    let rank = (percentile * sorted.len() + 99) / 100;
+    let index = rank.max(1).min(sorted.len()) - 1;
+    sorted[index].as_secs_f32() * 1000f32
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto::hash::BlockHash;
+
+    use super::*;
+
+    #[test]
+    fn test_exponential_moving_average() {
+        assert!((exponential_moving_average(None, 10f32, 0.2) - 10f32).abs() < 0.001);
+        assert!((exponential_moving_average(Some(10f32), 20f32, 0.2) - 12f32).abs() < 0.001);
+
+        // a brief spike moves the average just partially
+        let mut smoothed = None;
+        for speed in &[100f32, 100f32, 500f32, 100f32] {
+            smoothed = Some(exponential_moving_average(smoothed, *speed, 0.2));
+        }
+        assert!(smoothed.unwrap() < 200f32);
+    }
+
+    #[test]
+    fn test_application_time_percentiles() {
+        let mut monitor = ApplicationMonitor::new();
+        assert!(monitor.application_time_percentiles().is_none());
+
+        // 1..=100 ms, in reverse order
+        for ms in (1..=100).rev() {
+            monitor.block_was_applied(head(ms), Some(Duration::from_millis(ms as u64)));
+        }
+        // not measured block is not part of percentiles
+        monitor.block_was_applied(head(101), None);
+
+        let percentiles = monitor.application_time_percentiles().unwrap();
+        assert!((percentiles.p50 - 50f32).abs() < 0.001);
+        assert!((percentiles.p95 - 95f32).abs() < 0.001);
+        assert!((percentiles.p99 - 99f32).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_application_times_are_bounded() {
+        let mut monitor = ApplicationMonitor::new();
+        for level in 0..(APPLICATION_TIMES_MAX_CAPACITY as i32 * 2) {
+            monitor.block_was_applied(head(level), Some(Duration::from_secs(1)));
+        }
+        assert_eq!(
+            APPLICATION_TIMES_MAX_CAPACITY,
+            monitor.application_times.len()
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut monitor = ApplicationMonitor::new();
+        for level in 1..=100 {
+            monitor.block_was_applied(head(level), Some(Duration::from_millis(10)));
+        }
+        monitor.remote_head_level_received(200);
+        assert_eq!(100, monitor.total_applied);
+
+        monitor.reset();
+        assert_eq!(0, monitor.total_applied);
+        assert!(monitor.last_applied_block.is_none());
+        assert!(monitor.application_time_percentiles().is_none());
+        // remote head is kept, it is still the head of the network
+        assert_eq!(Some(200), monitor.remote_head_level);
+        std::thread::sleep(Duration::from_millis(10));
+        for level in 101..=105 {
+            monitor.block_was_applied(head(level), None);
+        }
+        assert_eq!(5, monitor.total_applied);
+
+        // average speed is counted just from the 5 blocks applied after the reset
+        let elapsed_before = monitor.first_update.elapsed().as_secs_f32() / 60f32;
+        let avg_speed = monitor.avg_speed();
+        let elapsed_after = monitor.first_update.elapsed().as_secs_f32() / 60f32;
+        assert!(avg_speed <= 5f32 / elapsed_before);
+        assert!(avg_speed >= 5f32 / elapsed_after);
+    }
+
+    #[test]
+    fn test_is_fresh_sync() {
+        let mut monitor = ApplicationMonitor::new();
+        assert!(!monitor.is_fresh_sync(&head(1)));
+
+        monitor.block_was_applied(head(1000), None);
+        assert!(!monitor.is_fresh_sync(&head(1001)));
+        // reorg is not a fresh sync
+        assert!(!monitor.is_fresh_sync(&head(999)));
+        assert!(!monitor.is_fresh_sync(&head(1000 - FRESH_SYNC_MIN_LEVELS_BELOW)));
+
+        assert!(monitor.is_fresh_sync(&head(1000 - FRESH_SYNC_MIN_LEVELS_BELOW - 1)));
+        assert!(monitor.is_fresh_sync(&head(1)));
+    }
+
+    #[test]
+    fn test_levels_behind() {
+        let mut monitor = ApplicationMonitor::new();
+        assert!(monitor.levels_behind().is_none());
+        monitor.block_was_applied(head(1_000_000), None);
+        assert!(monitor.levels_behind().is_none());
+
+        monitor.remote_head_level_received(1_000_350);
+        // lower remote head does not override the highest known one
+        monitor.remote_head_level_received(999_000);
+        assert_eq!(Some(350), monitor.levels_behind());
+        assert_eq!(Some(350), monitor.snapshot().levels_behind);
+
+        monitor.block_was_applied(head(1_000_400), None);
+        assert_eq!(Some(0), monitor.levels_behind());
+    }
+
+    fn head(level: i32) -> Head {
+        Head::new(
+            BlockHash::from_base58_check("BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe")
+                .expect("Failed to decode block hash"),
+            level,
+            vec![],
+        )
+    }
+}
@@ -166,7 +166,24 @@ impl From<IncomingTransferMetrics> for HandlerMessage {
 pub struct BlockApplicationMessage {
     pub(crate) current_application_speed: f32,
     pub(crate) average_application_speed: f32,
+    /// Exponential moving average of the current speed across snapshots
+    pub(crate) smoothed_application_speed: f32,
     pub(crate) last_applied_block: Option<BlockInfo>,
+    /// Percentiles of the recent block application times, None if no block was measured yet
+    pub(crate) application_time_percentiles: Option<ApplicationTimePercentiles>,
+    /// The highest head level announced by peers, None if not known yet
+    pub(crate) remote_head_level: Option<i32>,
+    /// How many levels is the last applied block behind the remote head, None if remote head is not known yet
+    pub(crate) levels_behind: Option<i32>,
+}
+
+/// Block application times in milliseconds
+#[derive(Clone, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplicationTimePercentiles {
+    pub(crate) p50: f32,
+    pub(crate) p95: f32,
+    pub(crate) p99: f32,
 }
 
 #[derive(Clone, Serialize, Debug)]
@@ -132,6 +132,9 @@ fn main() {
         None => None,
     };
 
+    // Start the OCaml runtime in advance, so the first command is not penalized by the runtime startup
+    tezos_client::client::start_runtime();
+
     // Process commands from from the Rust node. Most commands are instructions for the Tezos protocol
     if let Err(err) = tezos_wrapper::service::process_protocol_commands::<
         crate::tezos::NativeTezosLib,
@@ -5,6 +5,8 @@ use failure::format_err;
 use hyper::{Body, Request};
 use slog::warn;
 
+use crypto::hash::BlockHash;
+
 use crate::helpers::{parse_block_hash, parse_chain_id, MAIN_CHAIN_ID};
 use crate::server::{HasSingleValue, Params, Query, RpcServiceEnvironment};
 use crate::services::dev_services;
@@ -42,10 +44,29 @@ pub async fn dev_blocks(
     };
     let limit = query.get_usize("limit").unwrap_or(50);
 
-    result_to_json_response(
-        dev_services::get_blocks(chain_id, from_block_id, every_nth_level, limit, &env),
-        env.log(),
-    )
+    // if since_hash is present, return just blocks which are newer
+    match query.get_str("since_hash") {
+        Some(since_hash_param) => {
+            let since_hash = BlockHash::from_base58_check(since_hash_param).map_err(|e| {
+                format_err!("Invalid since_hash: {}, reason: {}", since_hash_param, e)
+            })?;
+            result_to_json_response(
+                dev_services::get_blocks_since(
+                    chain_id,
+                    from_block_id,
+                    since_hash,
+                    every_nth_level,
+                    limit,
+                    &env,
+                ),
+                env.log(),
+            )
+        }
+        None => result_to_json_response(
+            dev_services::get_blocks(chain_id, from_block_id, every_nth_level, limit, &env),
+            env.log(),
+        ),
+    }
 }
 
 #[allow(dead_code)]
@@ -1,6 +1,8 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::convert::TryFrom;
+
 use failure::bail;
 use serde::Serialize;
 use slog::Logger;
@@ -15,6 +17,7 @@ use storage::context::merkle::merkle_storage_stats::MerkleStoragePerfReport;
 use storage::context::{ContextApi, TezedgeContext};
 use storage::{
     BlockMetaStorage, BlockMetaStorageReader, BlockStorage, BlockStorageReader, PersistentStorage,
+    StorageError,
 };
 use tezos_context::channel::ContextAction;
 use tezos_messages::base::rpc_support::UniversalValue;
@@ -251,6 +254,125 @@ pub(crate) fn get_blocks(
     Ok(blocks)
 }
 
+/// Retrieve only blocks, which are newer than `since_block_hash`.
+///
+/// If `since_block_hash` is not a predecessor of `block_hash` (unknown block or reorg happened),
+/// then `reorg` is signaled and the whole (new) range is returned, like in [get_blocks].
+///
+/// If there are more new blocks than `limit`, then `next` contains the block to continue from.
+pub(crate) fn get_blocks_since(
+    chain_id: ChainId,
+    block_hash: BlockHash,
+    since_block_hash: BlockHash,
+    every_nth_level: Option<i32>,
+    limit: usize,
+    env: &RpcServiceEnvironment,
+) -> Result<SlimBlocksDelta, failure::Error> {
+    let block_storage = BlockStorage::new(env.persistent_storage());
+    let levels_limit = i32::try_from(limit)
+        .unwrap_or(i32::MAX)
+        .saturating_mul(every_nth_level.unwrap_or(1));
+
+    match resolve_blocks_since(&block_storage, &block_hash, &since_block_hash, levels_limit)? {
+        BlocksSince::UnknownFrom => Ok(SlimBlocksDelta {
+            reorg: false,
+            blocks: Vec::new(),
+            next: None,
+        }),
+        BlocksSince::NewerThan { level, count, next } => {
+            let blocks = get_blocks(
+                chain_id,
+                block_hash,
+                every_nth_level,
+                std::cmp::min(count, limit),
+                env,
+            )?
+            .into_iter()
+            .filter(|block| block.level > level)
+            .collect();
+            Ok(SlimBlocksDelta {
+                reorg: false,
+                blocks,
+                next: next.map(|next| next.to_base58_check()),
+            })
+        }
+        BlocksSince::Reorg => Ok(SlimBlocksDelta {
+            reorg: true,
+            blocks: get_blocks(chain_id, block_hash, every_nth_level, limit, env)?,
+            next: None,
+        }),
+    }
+}
+
+/// Blocks requested by [get_blocks_since], resolved just from the block storage
+#[derive(Debug, PartialEq)]
+enum BlocksSince {
+    /// `block_hash` is not stored, so there is nothing to return
+    UnknownFrom,
+    /// `since_block_hash` is the predecessor of `block_hash` on the `level`, just `count` blocks above it are new,
+    /// `next` is the highest new block below the requested range of levels, if any
+    NewerThan {
+        level: i32,
+        count: usize,
+        next: Option<BlockHash>,
+    },
+    /// `since_block_hash` is not the predecessor of `block_hash` (unknown block or reorg happened)
+    Reorg,
+}
+
+fn resolve_blocks_since(
+    block_storage: &BlockStorage,
+    block_hash: &BlockHash,
+    since_block_hash: &BlockHash,
+    levels_limit: i32,
+) -> Result<BlocksSince, StorageError> {
+    let from_block = match block_storage.get(block_hash)? {
+        Some(block) => block,
+        None => return Ok(BlocksSince::UnknownFrom),
+    };
+    let since_level = match block_storage.get(since_block_hash)? {
+        Some(since_block) => since_block.header.level(),
+        None => return Ok(BlocksSince::Reorg),
+    };
+    let from_level = from_block.header.level();
+    let next_level = from_level.saturating_sub(levels_limit);
+
+    // since_block_hash is still valid, only if we reach it by predecessors of block_hash
+    let mut next = None;
+    let mut current_block = from_block;
+    while current_block.header.level() > since_level {
+        if current_block.header.level() == next_level {
+            next = Some(current_block.hash.clone());
+        }
+        current_block = match block_storage.get(current_block.header.predecessor())? {
+            Some(predecessor) => predecessor,
+            // we cannot confirm the chain, so we handle it as reorg
+            None => return Ok(BlocksSince::Reorg),
+        };
+    }
+
+    Ok(if current_block.hash == *since_block_hash {
+        BlocksSince::NewerThan {
+            level: since_level,
+            count: (from_level - since_level) as usize,
+            next,
+        }
+    } else {
+        BlocksSince::Reorg
+    })
+}
+
+/// Incremental response for [get_blocks_since]
+#[derive(Serialize, Debug, Clone)]
+pub struct SlimBlocksDelta {
+    /// true, if previously seen block is not valid anymore and `blocks` contains the whole new range
+    pub reorg: bool,
+    pub blocks: Vec<SlimBlockData>,
+    /// Block hash to request as the next `from_block_id` (with the same `since_hash`), if not all new blocks fit into `limit`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
 /// Struct to show in tezedge explorer to lower data flow
 #[derive(Serialize, Debug, Clone)]
 pub struct SlimBlockData {
@@ -262,3 +384,135 @@ pub struct SlimBlockData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cycle_position: Option<i64>,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use storage::tests_common::TmpStorage;
+    use storage::BlockHeaderWithHash;
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use super::*;
+
+    fn block(level: i32, predecessor: &BlockHash, timestamp: i64) -> BlockHeaderWithHash {
+        BlockHeaderWithHash::new(
+            BlockHeaderBuilder::default()
+                .level(level)
+                .proto(1)
+                .predecessor(predecessor.clone())
+                .timestamp(timestamp)
+                .validation_pass(4)
+                .operations_hash(
+                    "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc"
+                        .try_into()
+                        .unwrap(),
+                )
+                .fitness(vec![vec![0, 0]])
+                .context(
+                    "CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd"
+                        .try_into()
+                        .unwrap(),
+                )
+                .protocol_data(vec![])
+                .build()
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_blocks_since() -> Result<(), failure::Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_resolve_blocks_since")?;
+        let block_storage = BlockStorage::new(tmp_storage.storage());
+
+        // A1 - A2 - A3 - A4 - A5
+        let mut predecessor: BlockHash =
+            "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".try_into()?;
+        let mut chain_a = Vec::new();
+        for level in 1..=5 {
+            let block = block(level, &predecessor, level as i64);
+            block_storage.put_block_header(&block)?;
+            predecessor = block.hash.clone();
+            chain_a.push(block);
+        }
+        let (a1, a2, a3, a5) = (
+            &chain_a[0].hash,
+            &chain_a[1].hash,
+            &chain_a[2].hash,
+            &chain_a[4].hash,
+        );
+
+        // since_hash on the chain, just newer blocks are requested
+        assert_eq!(
+            BlocksSince::NewerThan {
+                level: 2,
+                count: 3,
+                next: None
+            },
+            resolve_blocks_since(&block_storage, a5, a2, 50)?
+        );
+        assert_eq!(
+            BlocksSince::NewerThan {
+                level: 5,
+                count: 0,
+                next: None
+            },
+            resolve_blocks_since(&block_storage, a5, a5, 50)?
+        );
+
+        // more new blocks than limit, continue with A3
+        assert_eq!(
+            BlocksSince::NewerThan {
+                level: 1,
+                count: 4,
+                next: Some(a3.clone())
+            },
+            resolve_blocks_since(&block_storage, a5, a1, 2)?
+        );
+
+        // unknown from block
+        let unknown = block(6, a5, 6).hash;
+        assert_eq!(
+            BlocksSince::UnknownFrom,
+            resolve_blocks_since(&block_storage, &unknown, a2, 50)?
+        );
+
+        // unknown since block is handled as reorg
+        assert_eq!(
+            BlocksSince::Reorg,
+            resolve_blocks_since(&block_storage, a5, &unknown, 50)?
+        );
+
+        // A1 - A2 - B3 - B4 - B5 (A3 is replaced)
+        let mut predecessor = a2.clone();
+        let mut chain_b = Vec::new();
+        for level in 3..=5 {
+            let block = block(level, &predecessor, 100 + level as i64);
+            block_storage.put_block_header(&block)?;
+            predecessor = block.hash.clone();
+            chain_b.push(block);
+        }
+        let b4 = &chain_b[1].hash;
+        assert_eq!(
+            BlocksSince::Reorg,
+            resolve_blocks_since(&block_storage, &predecessor, a3, 50)?
+        );
+        // A2 is still on the chain
+        assert_eq!(
+            BlocksSince::NewerThan {
+                level: 2,
+                count: 3,
+                next: None
+            },
+            resolve_blocks_since(&block_storage, &predecessor, a2, 50)?
+        );
+        // B4 is the last stored block on its level, but it is not the predecessor of A5
+        assert_eq!(
+            BlocksSince::Reorg,
+            resolve_blocks_since(&block_storage, a5, b4, 50)?
+        );
+
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@
 //! -- ...
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use riker::actors::*;
 use slog::{debug, info, warn, Logger};
@@ -34,11 +35,21 @@ use crate::state::StateError;
 pub struct ProcessValidatedBlock {
     pub block: Arc<BlockHeaderWithHash>,
     chain_id: Arc<ChainId>,
+    /// How long did the block validation/application take (if measured)
+    apply_duration: Option<Duration>,
 }
 
 impl ProcessValidatedBlock {
-    pub fn new(block: Arc<BlockHeaderWithHash>, chain_id: Arc<ChainId>) -> Self {
-        Self { block, chain_id }
+    pub fn new(
+        block: Arc<BlockHeaderWithHash>,
+        chain_id: Arc<ChainId>,
+        apply_duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            block,
+            chain_id,
+            apply_duration,
+        }
     }
 }
 
@@ -112,7 +123,11 @@ impl ChainCurrentHeadManager {
         ctx: &Context<ChainCurrentHeadManagerMsg>,
         validated_block: ProcessValidatedBlock,
     ) -> Result<(), StateError> {
-        let ProcessValidatedBlock { block, chain_id } = validated_block;
+        let ProcessValidatedBlock {
+            block,
+            chain_id,
+            apply_duration,
+        } = validated_block;
 
         // we try to set it as "new current head", if some means set, if none means just ignore block
         if let Some((new_head, new_head_result)) =
@@ -127,7 +142,11 @@ impl ChainCurrentHeadManager {
             // notify other actors that new current head was changed
             self.shell_channel.tell(
                 Publish {
-                    msg: ShellChannelMsg::NewCurrentHead(new_head.clone(), block.clone()),
+                    msg: ShellChannelMsg::NewCurrentHead(
+                        new_head.clone(),
+                        block.clone(),
+                        apply_duration,
+                    ),
                     topic: ShellChannelTopic::ShellNewCurrentHead.into(),
                 },
                 None,
@@ -915,12 +915,13 @@ fn _apply_block(
         &mut block_meta,
     )?;
     let store_result_elapsed = store_result_timer.elapsed();
+    let validated_at_elapsed = validated_at_timer.elapsed();
 
     Ok(Some((
-        ProcessValidatedBlock::new(block, chain_id),
+        ProcessValidatedBlock::new(block, chain_id, Some(validated_at_elapsed)),
         block_additional_data,
         BlockValidationTimer::new(
-            validated_at_timer.elapsed(),
+            validated_at_elapsed,
             load_metadata_elapsed,
             protocol_call_elapsed,
             context_wait_elapsed,
@@ -1107,11 +1108,12 @@ pub(crate) fn initialize_protocol_context(
                 commit_data,
             )?;
             let store_result_elapsed = store_result_timer.elapsed();
+            let validated_at_elapsed = validated_at_timer.elapsed();
 
             let mut stats = ApplyBlockStats::default();
             stats.set_applied_block_level(genesis_with_hash.header.level());
             stats.add_block_validation_stats(&BlockValidationTimer::new(
-                validated_at_timer.elapsed(),
+                validated_at_elapsed,
                 load_metadata_elapsed,
                 protocol_call_elapsed,
                 context_wait_elapsed,
@@ -1128,6 +1130,7 @@ pub(crate) fn initialize_protocol_context(
                     ProcessValidatedBlock::new(
                         Arc::new(genesis_with_hash),
                         Arc::new(init_storage_data.chain_id.clone()),
+                        Some(validated_at_elapsed),
                     ),
                     None,
                 );
@@ -49,7 +49,7 @@ use crate::shell_channel::{
     AllBlockOperationsReceived, BlockReceived, InjectBlock, InjectBlockOneshotResultCallback,
     ShellChannelMsg, ShellChannelRef, ShellChannelTopic,
 };
-use crate::state::chain_state::{BlockAcceptanceResult, BlockchainState};
+use crate::state::chain_state::{BlockAcceptanceResult, BlockchainState, ReorgDetected};
 use crate::state::head_state::CurrentHeadRef;
 use crate::state::peer_state::{tell_peer, PeerState};
 use crate::state::synchronization_state::{
@@ -924,7 +924,17 @@ impl ChainManager {
         peer_id: &Arc<PeerId>,
     ) -> Result<(), Error> {
         // store header
-        if chain_state.process_block_header_from_peer(&received_block, log, peer_id)? {
+        let (is_new_block, reorg) =
+            chain_state.process_block_header_from_peer(&received_block, log, peer_id)?;
+
+        if let Some(ReorgDetected { from, to }) = reorg {
+            info!(log, "Reorg detected - other branch outgrew the best known block";
+                       "from" => from.to_base58_check(),
+                       "to" => to.to_base58_check(),
+                       "level" => received_block.header.level());
+        }
+
+        if is_new_block {
             // update stats for new header
             stats.unseen_block_last = Instant::now();
             stats.unseen_block_count += 1;
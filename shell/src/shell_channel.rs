@@ -4,6 +4,7 @@
 //! Shell channel is used to transmit high level shell messages.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use riker::actors::*;
 
@@ -51,8 +52,8 @@ pub type InjectBlockOneshotResultCallback = OneshotResultCallback<Result<(), Sta
 #[derive(Clone, Debug)]
 pub enum ShellChannelMsg {
     /// Events
-    /// If chain_manager resolved new current head for chain
-    NewCurrentHead(Head, Arc<BlockHeaderWithHash>),
+    /// If chain_manager resolved new current head for chain (with duration of block application, if measured)
+    NewCurrentHead(Head, Arc<BlockHeaderWithHash>, Option<Duration>),
     BlockReceived(BlockReceived),
     BlockApplied(Arc<BlockHash>),
     AllBlockOperationsReceived(AllBlockOperationsReceived),
@@ -5,7 +5,7 @@ use std::collections::HashSet;
 use std::sync::Arc;
 
 use riker::actors::*;
-use slog::Logger;
+use slog::{warn, Logger};
 
 use crypto::hash::{BlockHash, ChainId, ProtocolHash};
 use crypto::seeded_step::{Seed, Step};
@@ -15,7 +15,8 @@ use storage::chain_meta_storage::ChainMetaStorageReader;
 use storage::PersistentStorage;
 use storage::{
     BlockHeaderWithHash, BlockMetaStorage, BlockMetaStorageReader, BlockStorage,
-    BlockStorageReader, ChainMetaStorage, OperationsMetaStorage, OperationsStorage, StorageError,
+    BlockStorageReader, ChainMetaStorage, IteratorMode, OperationsMetaStorage, OperationsStorage,
+    StorageError,
 };
 use tezos_messages::p2p::encoding::current_branch::CurrentBranchMessage;
 use tezos_messages::p2p::encoding::prelude::{CurrentHeadMessage, OperationsForBlocksMessage};
@@ -35,6 +36,7 @@ use crate::state::head_state::CurrentHeadRef;
 use crate::state::peer_state::{DataQueuesLimits, PeerState};
 use crate::state::StateError;
 use crate::validation;
+use crate::validation::fitness_comparator::FitnessWrapper;
 
 /// Constants for controlling bootstrap speed
 ///
@@ -72,6 +74,15 @@ pub enum BlockAcceptanceResult {
     MutlipassValidationError(ProtocolServiceError),
 }
 
+/// Newly processed block header made the other branch the best known one
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReorgDetected {
+    /// Previous best known block
+    pub from: BlockHash,
+    /// New best known block, which is not a successor of `from`
+    pub to: BlockHash,
+}
+
 /// Holds and manages state of the chain
 pub struct BlockchainState {
     /// persistent block storage
@@ -96,6 +107,15 @@ pub struct BlockchainState {
 
     chain_id: Arc<ChainId>,
     chain_genesis_block_hash: Arc<BlockHash>,
+
+    /// Finalized block, we dont accept blocks, which are not its successors (cached from chain_meta_storage)
+    checkpoint: Option<Head>,
+
+    /// The highest block verified to be a successor of the checkpoint, shortens the walk to the checkpoint
+    checkpoint_branch_tip: Option<Head>,
+
+    /// The highest stored block header (cached from chain_meta_storage)
+    best_known_head: Option<Head>,
 }
 
 impl BlockchainState {
@@ -106,7 +126,15 @@ impl BlockchainState {
         chain_id: Arc<ChainId>,
         chain_genesis_block_hash: Arc<BlockHash>,
     ) -> Self {
-        BlockchainState {
+        let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+        let checkpoint = chain_meta_storage
+            .get_checkpoint(&chain_id)
+            .unwrap_or_default();
+        let best_known_head = chain_meta_storage
+            .get_best_known_head(&chain_id)
+            .unwrap_or_default();
+
+        let mut state = BlockchainState {
             requester: DataRequesterRef::new(DataRequester::new(
                 BlockMetaStorage::new(&persistent_storage),
                 OperationsMetaStorage::new(&persistent_storage),
@@ -115,13 +143,199 @@ impl BlockchainState {
             peer_branch_bootstrapper: None,
             block_storage: BlockStorage::new(persistent_storage),
             block_meta_storage: BlockMetaStorage::new(persistent_storage),
-            chain_meta_storage: ChainMetaStorage::new(persistent_storage),
+            chain_meta_storage,
             operations_storage: OperationsStorage::new(persistent_storage),
             operations_meta_storage: OperationsMetaStorage::new(persistent_storage),
             shell_channel,
             chain_id,
             chain_genesis_block_hash,
+            checkpoint,
+            checkpoint_branch_tip: None,
+            best_known_head,
+        };
+
+        // storage from older version does not have best known head stored yet, so we scan it just once
+        if state.best_known_head.is_none() {
+            state.best_known_head = state.migrate_best_known_head().unwrap_or_default();
         }
+        state
+    }
+
+    /// Returns checkpoint (finalized block) for this chain, if any
+    pub fn checkpoint(&self) -> Option<&Head> {
+        self.checkpoint.as_ref()
+    }
+
+    /// Sets and stores checkpoint (finalized block) for this chain
+    pub fn set_checkpoint(&mut self, level: i32, hash: BlockHash) -> Result<(), StorageError> {
+        let checkpoint = Head::new(hash, level, vec![]);
+        self.chain_meta_storage
+            .set_checkpoint(&self.chain_id, checkpoint.clone())?;
+        self.checkpoint = Some(checkpoint);
+        self.checkpoint_branch_tip = None;
+        Ok(())
+    }
+
+    /// Returns false, if block is above the checkpoint level, but the checkpoint is not its predecessor
+    /// (means on the other branch), or if block is on the checkpoint level, but with different hash.
+    ///
+    /// Blocks below the checkpoint level and blocks not connected to the checkpoint by stored metadata (yet) are accepted.
+    fn is_on_checkpoint_branch(
+        &mut self,
+        block: &BlockHeaderWithHash,
+    ) -> Result<bool, StorageError> {
+        let checkpoint = match self.checkpoint.as_ref() {
+            Some(checkpoint) => checkpoint.clone(),
+            None => return Ok(true),
+        };
+
+        let level = block.header.level();
+        if level < *checkpoint.level() {
+            return Ok(true);
+        }
+        if level == *checkpoint.level() {
+            return Ok(block.hash == *checkpoint.block_hash());
+        }
+
+        // successor of already verified block is on the checkpoint branch too
+        let on_checkpoint_branch = match self.checkpoint_branch_tip.as_ref() {
+            Some(tip)
+                if level > *tip.level() && self.is_predecessor_of(tip, block)? == Some(true) =>
+            {
+                Some(true)
+            }
+            _ => self.is_predecessor_of(&checkpoint, block)?,
+        };
+
+        match on_checkpoint_branch {
+            Some(true) => {
+                if self
+                    .checkpoint_branch_tip
+                    .as_ref()
+                    .map_or(true, |tip| level > *tip.level())
+                {
+                    self.checkpoint_branch_tip = Some(Head::new(block.hash.clone(), level, vec![]));
+                }
+                Ok(true)
+            }
+            Some(false) => Ok(false),
+            // we cannot decide yet (not connected by stored metadata)
+            None => Ok(true),
+        }
+    }
+
+    /// Returns the best (by fitness) block header stored for this chain, `None` for empty storage
+    pub fn best_known_header(&self) -> Result<Option<BlockHeaderWithHash>, StorageError> {
+        match self.best_known_head.as_ref() {
+            Some(best_known_head) => self.block_storage.get(best_known_head.block_hash()),
+            None => Ok(None),
+        }
+    }
+
+    /// Updates (and stores) best known head, if `block` has greater fitness
+    ///
+    /// Returns [ReorgDetected], if `block` is on the other branch than the previous best known head
+    fn update_best_known_head(
+        &mut self,
+        block: &BlockHeaderWithHash,
+    ) -> Result<Option<ReorgDetected>, StorageError> {
+        let reorg = match self.best_known_head.as_ref() {
+            Some(best_known_head) => {
+                if FitnessWrapper::new(block.header.fitness())
+                    <= FitnessWrapper::new(best_known_head.fitness())
+                {
+                    return Ok(None);
+                }
+                if block.header.predecessor() == best_known_head.block_hash() {
+                    // direct successor, no need to walk the predecessors
+                    None
+                } else {
+                    match self.is_predecessor_of(best_known_head, block)? {
+                        Some(false) => Some(ReorgDetected {
+                            from: best_known_head.block_hash().clone(),
+                            to: block.hash.clone(),
+                        }),
+                        // the same branch or we cannot decide yet (not connected by stored metadata)
+                        _ => None,
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let best_known_head = Head::new(
+            block.hash.clone(),
+            block.header.level(),
+            block.header.fitness().clone(),
+        );
+        self.chain_meta_storage
+            .set_best_known_head(&self.chain_id, best_known_head.clone())?;
+        self.best_known_head = Some(best_known_head);
+        Ok(reorg)
+    }
+
+    /// Walks stored block metadata from `block` down to the level of `head`
+    ///
+    /// Returns None, if chain of predecessors is not stored (yet)
+    fn is_predecessor_of(
+        &self,
+        head: &Head,
+        block: &BlockHeaderWithHash,
+    ) -> Result<Option<bool>, StorageError> {
+        let mut current_block_hash = block.header.predecessor().clone();
+        let mut current_level = block.header.level() - 1;
+
+        while current_level > *head.level() {
+            match self.block_meta_storage.get(&current_block_hash)? {
+                Some(meta) => match meta.predecessor() {
+                    Some(predecessor) => {
+                        current_block_hash = predecessor.clone();
+                        current_level = meta.level() - 1;
+                    }
+                    None => return Ok(None),
+                },
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(
+            current_level == *head.level() && current_block_hash == *head.block_hash(),
+        ))
+    }
+
+    /// Scans block metadata for the best (by fitness) block of this chain, which has stored header, and stores it
+    fn migrate_best_known_head(&self) -> Result<Option<Head>, StorageError> {
+        let mut best_known_head: Option<Head> = None;
+        for (block_hash, meta) in self.block_meta_storage.iter(IteratorMode::Start)? {
+            let (block_hash, meta) = match (block_hash, meta) {
+                (Ok(block_hash), Ok(meta)) => (block_hash, meta),
+                _ => continue,
+            };
+            if meta.chain_id() != self.chain_id.as_ref() {
+                continue;
+            }
+            // metadata can be created just for predecessor, without its header
+            if let Some(block) = self.block_storage.get(&block_hash)? {
+                if let Some(best_known_head) = best_known_head.as_ref() {
+                    if FitnessWrapper::new(block.header.fitness())
+                        <= FitnessWrapper::new(best_known_head.fitness())
+                    {
+                        continue;
+                    }
+                }
+                best_known_head = Some(Head::new(
+                    block_hash,
+                    meta.level(),
+                    block.header.fitness().clone(),
+                ));
+            }
+        }
+
+        if let Some(best_known_head) = best_known_head.as_ref() {
+            self.chain_meta_storage
+                .set_best_known_head(&self.chain_id, best_known_head.clone())?;
+        }
+        Ok(best_known_head)
     }
 
     pub(crate) fn requester(&self) -> &DataRequesterRef {
@@ -465,13 +679,34 @@ impl BlockchainState {
     /// Process block_header, stores/updates storages,
     /// schedules missing stuff to peer
     ///
-    /// Returns bool - true, if it is a new block or false for previosly stored
+    /// Returns tuple:
+    ///     (
+    ///         is_new_block - true, if it is a new block or false for previosly stored,
+    ///         reorg - if block made the other branch the best known one
+    ///     )
     pub fn process_block_header_from_peer(
         &mut self,
         received_block: &BlockHeaderWithHash,
         log: &Logger,
         peer_id: &Arc<PeerId>,
-    ) -> Result<bool, StorageError> {
+    ) -> Result<(bool, Option<ReorgDetected>), StorageError> {
+        // refuse block from the other branch than checkpoint
+        if !self.is_on_checkpoint_branch(received_block)? {
+            warn!(log, "Ignoring block header on the other branch than checkpoint";
+                       "block_header_hash" => received_block.hash.to_base58_check(),
+                       "level" => received_block.header.level(),
+                       "peer_id" => peer_id.peer_id_marker.clone());
+            return Ok((false, None));
+        }
+
+        // refuse block, which belongs to the other chain (according to its already stored predecessor)
+        if let Some(predecessor_meta) = self
+            .block_meta_storage
+            .get(received_block.header.predecessor())?
+        {
+            self.check_chain_id(predecessor_meta.chain_id())?;
+        }
+
         // store block
         let is_new_block = self.block_storage.put_block_header(received_block)?;
 
@@ -483,6 +718,8 @@ impl BlockchainState {
         // update operations metadata for block
         let (are_operations_complete, _) = self.process_block_header_operations(received_block)?;
 
+        let reorg = self.update_best_known_head(received_block)?;
+
         // ping branch bootstrapper with received block and actual state
         if let Some(peer_branch_bootstrapper) = self.peer_branch_bootstrapper() {
             peer_branch_bootstrapper.tell(
@@ -498,7 +735,7 @@ impl BlockchainState {
             );
         }
 
-        Ok(is_new_block)
+        Ok((is_new_block, reorg))
     }
 
     /// Process block_header, stores/updates storages, schedules missing stuff
@@ -514,6 +751,9 @@ impl BlockchainState {
         block_header: &BlockHeaderWithHash,
         log: &Logger,
     ) -> Result<(Meta, bool, bool), StorageError> {
+        // refuse block for the other chain
+        self.check_chain_id(chain_id)?;
+
         // store block
         let is_new_block = self.block_storage.put_block_header(block_header)?;
 
@@ -526,9 +766,22 @@ impl BlockchainState {
         let are_operations_complete =
             self.process_injected_block_header_operations(block_header)?;
 
+        // reorg is reported just for blocks received from peers
+        let _ = self.update_best_known_head(block_header)?;
+
         Ok((metadata, is_new_block, are_operations_complete))
     }
 
+    fn check_chain_id(&self, chain_id: &ChainId) -> Result<(), StorageError> {
+        if self.chain_id.as_ref() != chain_id {
+            return Err(StorageError::ChainIdMismatch {
+                expected: self.chain_id.to_base58_check(),
+                found: chain_id.to_base58_check(),
+            });
+        }
+        Ok(())
+    }
+
     /// Process block header. This will create record in meta storage with
     /// unseen operations for the block header.
     ///
@@ -700,15 +953,282 @@ impl BlockchainState {
 
 #[cfg(test)]
 mod tests {
+    use serial_test::serial;
     use slog::Level;
 
     use crypto::hash::chain_id_from_block_hash;
+    use networking::p2p::network_channel::NetworkChannel;
     use storage::tests_common::TmpStorage;
 
-    use crate::state::tests::prerequisites::create_logger;
+    use crate::shell_channel::ShellChannel;
+    use crate::state::tests::prerequisites::{
+        chain_feeder_mock, create_logger, create_test_actor_system, create_test_tokio_runtime,
+        test_peer,
+    };
 
     use super::*;
 
+    /// Actors, storage and peer for the tests processing block headers received from peer
+    struct TestFixture {
+        log: Logger,
+        peer: PeerState,
+        storage: TmpStorage,
+        block_storage: BlockStorage,
+        block_meta_storage: BlockMetaStorage,
+        shell_channel: ShellChannelRef,
+        chain_feeder_mock: ChainFeederRef,
+        blocksdb: data::BlocksDb,
+        _actor_system: ActorSystem,
+        _tokio_runtime: tokio::runtime::Runtime,
+    }
+
+    impl TestFixture {
+        fn new(storage_name: &str, peer_port: u16) -> Result<Self, failure::Error> {
+            let log = create_logger(Level::Debug);
+            let tokio_runtime = create_test_tokio_runtime();
+            let actor_system = create_test_actor_system(log.clone());
+            let network_channel =
+                NetworkChannel::actor(&actor_system).expect("Failed to create network channel");
+            let shell_channel = actor_system
+                .actor_of::<ShellChannel>("test-shell-channel")
+                .expect("Failed to create shell channel");
+            let storage = TmpStorage::create_to_out_dir(storage_name)?;
+            let block_meta_storage = BlockMetaStorage::new(storage.storage());
+            let block_storage = BlockStorage::new(storage.storage());
+            let peer = test_peer(&actor_system, network_channel, &tokio_runtime, peer_port);
+            let (chain_feeder_mock, _) = chain_feeder_mock(&actor_system)?;
+
+            Ok(Self {
+                log,
+                peer,
+                storage,
+                block_storage,
+                block_meta_storage,
+                shell_channel,
+                chain_feeder_mock,
+                blocksdb: data::init_blocks(),
+                _actor_system: actor_system,
+                _tokio_runtime: tokio_runtime,
+            })
+        }
+
+        /// Stores genesis header with metadata for its own chain
+        fn store_genesis(&self) -> Result<(BlockHash, ChainId), failure::Error> {
+            let (genesis_hash, genesis_header) = (
+                self.blocksdb.block_hash("Genesis"),
+                self.blocksdb.header("Genesis"),
+            );
+            let chain_id = chain_id_from_block_hash(&genesis_hash)?;
+            self.block_storage.put_block_header(&genesis_header)?;
+            self.block_meta_storage.put(
+                &genesis_hash,
+                &Meta::genesis_meta(&genesis_hash, &chain_id, true),
+            )?;
+            Ok((genesis_hash, chain_id))
+        }
+
+        fn chain_state(&self, chain_id: ChainId, genesis_hash: BlockHash) -> BlockchainState {
+            BlockchainState::new(
+                self.chain_feeder_mock.clone(),
+                self.storage.storage(),
+                self.shell_channel.clone(),
+                Arc::new(chain_id),
+                Arc::new(genesis_hash),
+            )
+        }
+
+        fn process_block_header(
+            &self,
+            chain_state: &mut BlockchainState,
+            name: &str,
+        ) -> Result<(bool, Option<ReorgDetected>), StorageError> {
+            chain_state.process_block_header_from_peer(
+                &self.blocksdb.header(name),
+                &self.log,
+                &self.peer.peer_id,
+            )
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_process_block_header_from_other_chain_is_rejected() -> Result<(), failure::Error> {
+        let fixture = TestFixture::new("__test_process_block_header_other_chain", 7779)?;
+
+        // genesis is stored for its own chain
+        let (genesis_hash, chain_id) = fixture.store_genesis()?;
+
+        // state for the other chain
+        let other_chain_id = ChainId::from_base58_check("NetXgtSLGNJvNye")?;
+        assert_ne!(chain_id, other_chain_id);
+        let mut chain_state = fixture.chain_state(other_chain_id, genesis_hash);
+
+        // A1 (successor of genesis) is rejected and not stored
+        let block = fixture.blocksdb.header("A1");
+        assert!(matches!(
+            fixture.process_block_header(&mut chain_state, "A1"),
+            Err(StorageError::ChainIdMismatch { .. })
+        ));
+        assert!(fixture.block_storage.get(&block.hash)?.is_none());
+        assert!(fixture.block_meta_storage.get(&block.hash)?.is_none());
+
+        // injected block for the other chain is rejected too
+        assert!(matches!(
+            chain_state.process_injected_block_header(&chain_id, &block, &fixture.log),
+            Err(StorageError::ChainIdMismatch { .. })
+        ));
+        assert!(fixture.block_storage.get(&block.hash)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_best_known_header() -> Result<(), failure::Error> {
+        let fixture = TestFixture::new("__test_best_known_header", 7780)?;
+        let blocksdb = &fixture.blocksdb;
+
+        let genesis_hash = blocksdb.block_hash("Genesis");
+        let chain_id = chain_id_from_block_hash(&genesis_hash)?;
+        let chain_meta_storage = ChainMetaStorage::new(fixture.storage.storage());
+        let chain_state = fixture.chain_state(chain_id.clone(), genesis_hash.clone());
+
+        // empty storage
+        assert!(chain_state.best_known_header()?.is_none());
+
+        // storage without stored best known head (e.g. from older version) is scanned on start
+        fixture.store_genesis()?;
+        let mut chain_state = fixture.chain_state(chain_id.clone(), genesis_hash.clone());
+        assert_eq!(
+            Some(genesis_hash.clone()),
+            chain_meta_storage
+                .get_best_known_head(&chain_id)?
+                .map(|head| head.block_hash().clone())
+        );
+        assert_eq!(
+            Some(genesis_hash.clone()),
+            chain_state.best_known_header()?.map(|b| b.hash)
+        );
+
+        // processed headers move the best known header, lower ones do not
+        for name in &["A1", "A3", "A2"] {
+            fixture.process_block_header(&mut chain_state, name)?;
+        }
+        assert_eq!(
+            Some(blocksdb.block_hash("A3")),
+            chain_state.best_known_header()?.map(|b| b.hash)
+        );
+
+        assert_eq!(
+            Some(blocksdb.block_hash("A3")),
+            chain_meta_storage
+                .get_best_known_head(&chain_id)?
+                .map(|head| head.block_hash().clone())
+        );
+
+        // after restart, best known header is loaded from storage
+        let chain_state = fixture.chain_state(chain_id, genesis_hash);
+        assert_eq!(
+            Some(blocksdb.block_hash("A3")),
+            chain_state.best_known_header()?.map(|b| b.hash)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_process_block_header_detects_reorg() -> Result<(), failure::Error> {
+        let fixture = TestFixture::new("__test_process_block_header_reorg", 7781)?;
+        let blocksdb = &fixture.blocksdb;
+
+        /*
+         * Genesis - A1 - A2 - A3 - A4 - A5 - A6 - A7 - A8
+         *                      \
+         *                       B1 - B2 - B3 - B4 - B5 - B6 - B7 - B8
+         */
+        let (genesis_hash, chain_id) = fixture.store_genesis()?;
+        let mut chain_state = fixture.chain_state(chain_id, genesis_hash);
+
+        let mut process = |name: &str| -> Result<Option<ReorgDetected>, StorageError> {
+            fixture
+                .process_block_header(&mut chain_state, name)
+                .map(|(_, reorg)| reorg)
+        };
+
+        // extending the best branch is not a reorg
+        for name in &["A1", "A2", "A3", "A4", "A5"] {
+            assert_eq!(None, process(name)?);
+        }
+
+        // B branch does not outgrow A5 yet
+        assert_eq!(None, process("B1")?);
+        assert_eq!(None, process("B2")?);
+
+        // B3 has greater fitness than A5
+        assert_eq!(
+            Some(ReorgDetected {
+                from: blocksdb.block_hash("A5"),
+                to: blocksdb.block_hash("B3"),
+            }),
+            process("B3")?
+        );
+        assert_eq!(None, process("B4")?);
+
+        // A branch overtakes B again
+        assert_eq!(None, process("A6")?);
+        assert_eq!(None, process("A7")?);
+        assert_eq!(
+            Some(ReorgDetected {
+                from: blocksdb.block_hash("B4"),
+                to: blocksdb.block_hash("A8"),
+            }),
+            process("A8")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn test_process_block_header_refuses_other_branch_than_checkpoint() -> Result<(), failure::Error>
+    {
+        let fixture = TestFixture::new("__test_process_block_header_checkpoint", 7782)?;
+
+        /*
+         * Genesis - A1 - A2 - A3 - A4 - A5 - A6 - A7 - A8
+         *                      \
+         *                       B1 - B2 - B3 - B4 - B5 - B6 - B7 - B8
+         */
+        let (genesis_hash, chain_id) = fixture.store_genesis()?;
+        let mut chain_state = fixture.chain_state(chain_id, genesis_hash);
+
+        for name in &["A1", "A2", "A3", "A4", "A5", "B1"] {
+            fixture.process_block_header(&mut chain_state, name)?;
+        }
+
+        // A4 is finalized
+        let checkpoint = fixture.blocksdb.header("A4");
+        chain_state.set_checkpoint(checkpoint.header.level(), checkpoint.hash)?;
+
+        let mut process = |name: &str| -> Result<bool, StorageError> {
+            fixture.process_block_header(&mut chain_state, name)?;
+            Ok(fixture
+                .block_storage
+                .get(&fixture.blocksdb.block_hash(name))?
+                .is_some())
+        };
+
+        // successors of the checkpoint are accepted
+        assert!(process("A6")?);
+        assert!(process("A7")?);
+
+        // B2 is above the checkpoint, but B branch forked below it
+        assert!(!process("B2")?);
+
+        Ok(())
+    }
+
     /// This test is rewritten according to [test_state.ml -> test_locator]
     #[test]
     fn test_history_and_compute_locator() -> Result<(), failure::Error> {
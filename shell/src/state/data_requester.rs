@@ -491,10 +491,7 @@ fn tell_peer(msg: Arc<PeerMessageResponse>, peer: &PeerId) {
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
-    use std::sync::atomic::AtomicBool;
-    use std::sync::mpsc::{channel, Receiver};
-    use std::sync::{Arc, Mutex};
-    use std::thread;
+    use std::sync::Arc;
 
     use riker::actors::*;
     use serial_test::serial;
@@ -507,12 +504,10 @@ mod tests {
     };
     use tezos_messages::p2p::encoding::prelude::OperationsForBlock;
 
-    use crate::chain_feeder;
-    use crate::chain_feeder::{ChainFeeder, ChainFeederRef};
-    use crate::shell_channel::ShellChannel;
     use crate::state::data_requester::DataRequester;
     use crate::state::tests::prerequisites::{
-        create_logger, create_test_actor_system, create_test_tokio_runtime, test_peer,
+        chain_feeder_mock, create_logger, create_test_actor_system, create_test_tokio_runtime,
+        test_peer,
     };
     use crate::state::tests::{block, block_ref};
     use crate::state::ApplyBlockBatch;
@@ -859,29 +854,4 @@ mod tests {
 
         Ok(())
     }
-
-    fn chain_feeder_mock(
-        actor_system: &ActorSystem,
-    ) -> Result<(ChainFeederRef, Receiver<chain_feeder::Event>), failure::Error> {
-        // run actor's
-        let shell_channel =
-            ShellChannel::actor(&actor_system).expect("Failed to create shell channel");
-
-        let (block_applier_event_sender, block_applier_event_receiver) = channel();
-        let block_applier_run = Arc::new(AtomicBool::new(true));
-
-        actor_system
-            .actor_of_props::<ChainFeeder>(
-                "mocked_chain_feeder",
-                Props::new_args((
-                    shell_channel,
-                    Arc::new(Mutex::new(block_applier_event_sender)),
-                    block_applier_run,
-                    Arc::new(Mutex::new(Some(thread::spawn(|| Ok(()))))),
-                    2,
-                )),
-            )
-            .map(|feeder| (feeder, block_applier_event_receiver))
-            .map_err(|e| e.into())
-    }
 }
@@ -38,7 +38,7 @@ pub struct DarwinOsData {
     resident: String, // resident set size
 }
 
-#[derive(Serialize, Debug, Default, Merge, Clone, PartialEq, CopyGetters)]
+#[derive(Serialize, Deserialize, Debug, Default, Merge, Clone, PartialEq, CopyGetters)]
 pub struct ProcessMemoryStats {
     #[get_copy = "pub"]
     #[merge(strategy = merge::num::saturating_add)]
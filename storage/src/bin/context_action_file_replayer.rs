@@ -15,6 +15,7 @@ use slog::{debug, info, warn, Drain, Level, Logger};
 use crypto::hash::ChainId;
 use storage::context::actions::action_file::ActionsFileReader;
 use storage::context::actions::get_new_tree_hash;
+use storage::context::gc::GcStrategy;
 use storage::context::kv_store::SupportedContextKeyValueStore;
 use storage::context::merkle::merkle_storage::MerkleStorage;
 use storage::context::merkle::merkle_storage_stats::MerkleStorageAction;
@@ -32,6 +33,7 @@ struct Args {
     input: PathBuf,
     output: PathBuf,
     context_kv_store: ContextKvStoreConfiguration,
+    context_kv_store_gc: GcStrategy,
 }
 
 const LRU_CACHE_SIZE_64MB: usize = 64 * 1024 * 1024;
@@ -67,7 +69,14 @@ impl Args {
                 .required(true)
                 .default_value("rocksdb")
                 .possible_values(&SupportedContextKeyValueStore::possible_values())
-                .help("Choose the merkle storege backend - supported backends: 'rocksdb', 'sled', 'inmem', 'btree'"));
+                .help("Choose the merkle storege backend - supported backends: 'rocksdb', 'sled', 'inmem', 'btree'"))
+            .arg(Arg::with_name("context-kv-store-gc")
+                .long("context-kv-store-gc")
+                .takes_value(true)
+                .value_name("STRING")
+                .default_value("none")
+                .possible_values(&GcStrategy::possible_values())
+                .help("Choose the garbage collection for merkle storage backend - supported strategies: 'none', 'mark_sweep', 'mark_move' (only for 'inmem' and 'btree')"));
 
         let matches = app.get_matches();
 
@@ -94,6 +103,7 @@ impl Args {
                             db_path: out_dir.join("replayed_context_rocksdb"),
                             columns: ContextRocksDbTableInitializer,
                             threads: None,
+                            create_missing_column_families: true,
                         })
                     }
                     SupportedContextKeyValueStore::Sled { .. } => {
@@ -113,6 +123,17 @@ impl Args {
                         e
                     )
                 }),
+            context_kv_store_gc: matches
+                .value_of("context-kv-store-gc")
+                .unwrap()
+                .parse::<GcStrategy>()
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "Expecting one value from {:?}, error: {:?}",
+                        GcStrategy::possible_values(),
+                        e
+                    )
+                }),
             blocks_limit: matches
                 .value_of("blocks_limit")
                 .map(|s| s.parse::<usize>().unwrap()),
@@ -414,6 +435,7 @@ fn main() -> Result<(), Error> {
     // create merkle storage
     let merkle = Arc::new(Mutex::new(initialize_merkle(
         &params.context_kv_store,
+        &params.context_kv_store_gc,
         &mocked_test_main_chain,
         &log,
         &mut global_cache_holder,
@@ -491,7 +513,13 @@ fn main() -> Result<(), Error> {
 
                 context.block_applied().unwrap();
                 if counter > 0 && counter % params.blocks_per_cycle == 0 {
-                    context.cycle_started().unwrap();
+                    let cycle_stats = context.cycle_started().unwrap();
+                    info!(
+                        log,
+                        "GC cycle freed {} MB ({} entries)",
+                        cycle_stats.reclaimed_bytes / 1024 / 1024,
+                        cycle_stats.entries_collected
+                    );
                     cycle_counter += 1;
                 }
             }
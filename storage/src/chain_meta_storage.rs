@@ -32,6 +32,16 @@ pub trait ChainMetaStorageReader: Sync + Send {
 
     /// Load genesis for chain_id from dedicated storage
     fn get_genesis(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError>;
+
+    /// Load checkpoint for chain_id from dedicated storage
+    ///
+    /// checkpoint - is the finalized block, node should not accept other branch on this level
+    fn get_checkpoint(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError>;
+
+    /// Load best known head for chain_id from dedicated storage
+    ///
+    /// best known head - is the highest block, which header was received (not necessary applied)
+    fn get_best_known_head(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError>;
 }
 
 /// Represents storage of the chain metadata (current_head, test_chain, ...).
@@ -93,6 +103,26 @@ impl ChainMetaStorage {
             .map_err(StorageError::from)
     }
 
+    #[inline]
+    pub fn set_checkpoint(&self, chain_id: &ChainId, head: Head) -> Result<(), StorageError> {
+        self.kv
+            .put(
+                &MetaKey::key_checkpoint(chain_id.clone()),
+                &MetadataValue::Head(head),
+            )
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    pub fn set_best_known_head(&self, chain_id: &ChainId, head: Head) -> Result<(), StorageError> {
+        self.kv
+            .put(
+                &MetaKey::key_best_known_head(chain_id.clone()),
+                &MetadataValue::Head(head),
+            )
+            .map_err(StorageError::from)
+    }
+
     #[inline]
     pub fn get_test_chain_id(&self, chain_id: &ChainId) -> Result<Option<ChainId>, StorageError> {
         self.kv
@@ -159,6 +189,28 @@ impl ChainMetaStorageReader for ChainMetaStorage {
             })
             .map_err(StorageError::from)
     }
+
+    #[inline]
+    fn get_checkpoint(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError> {
+        self.kv
+            .get(&MetaKey::key_checkpoint(chain_id.clone()))
+            .map(|result| match result {
+                Some(MetadataValue::Head(value)) => Some(value),
+                _ => None,
+            })
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    fn get_best_known_head(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError> {
+        self.kv
+            .get(&MetaKey::key_best_known_head(chain_id.clone()))
+            .map(|result| match result {
+                Some(MetadataValue::Head(value)) => Some(value),
+                _ => None,
+            })
+            .map_err(StorageError::from)
+    }
 }
 
 impl KeyValueSchema for ChainMetaStorage {
@@ -192,6 +244,8 @@ impl MetaKey {
     const KEY_CURRENT_HEAD: &'static str = "ch";
     const KEY_CABOOSE: &'static str = "cbs";
     const KEY_GENESIS: &'static str = "gns";
+    const KEY_CHECKPOINT: &'static str = "chkp";
+    const KEY_BEST_KNOWN_HEAD: &'static str = "bkh";
     const KEY_TEST_CHAIN_ID: &'static str = "tcid";
 
     fn key_current_head(chain_id: ChainId) -> MetaKey {
@@ -215,6 +269,20 @@ impl MetaKey {
         }
     }
 
+    fn key_checkpoint(chain_id: ChainId) -> MetaKey {
+        MetaKey {
+            chain_id,
+            key: Self::KEY_CHECKPOINT.to_string(),
+        }
+    }
+
+    fn key_best_known_head(chain_id: ChainId) -> MetaKey {
+        MetaKey {
+            chain_id,
+            key: Self::KEY_BEST_KNOWN_HEAD.to_string(),
+        }
+    }
+
     fn key_test_chain_id(chain_id: ChainId) -> MetaKey {
         MetaKey {
             chain_id,
@@ -451,6 +519,81 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_checkpoint_survives_restart() -> Result<(), Error> {
+        let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not defined - check build.rs");
+        let path = std::path::Path::new(out_dir.as_str()).join("__test_checkpoint");
+
+        let chain_id1 = "NetXgtSLGNJvNye".try_into()?;
+        let chain_id2 = "NetXjD3HPJJjmcd".try_into()?;
+        let block_1 = Head::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".try_into()?,
+            1,
+            vec![],
+        );
+
+        {
+            let tmp_storage = TmpStorage::initialize(&path, true, false)?;
+            let index = ChainMetaStorage::new(tmp_storage.storage());
+
+            // no checkpoint
+            assert!(index.get_checkpoint(&chain_id1)?.is_none());
+
+            // set for chain_id1
+            index.set_checkpoint(&chain_id1, block_1.clone())?;
+            assert!(index.get_checkpoint(&chain_id1)?.is_some());
+            assert!(index.get_checkpoint(&chain_id2)?.is_none());
+        }
+
+        // reopen storage
+        let tmp_storage = TmpStorage::initialize(&path, false, true)?;
+        let index = ChainMetaStorage::new(tmp_storage.storage());
+
+        let checkpoint = index.get_checkpoint(&chain_id1)?;
+        assert!(checkpoint.is_some());
+        let checkpoint = checkpoint.unwrap();
+        assert_eq!(checkpoint.block_hash(), block_1.block_hash());
+        assert_eq!(checkpoint.level(), block_1.level());
+        assert!(index.get_checkpoint(&chain_id2)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_known_head() -> Result<(), Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_best_known_head")?;
+        let index = ChainMetaStorage::new(tmp_storage.storage());
+
+        let chain_id1 = "NetXgtSLGNJvNye".try_into()?;
+        let chain_id2 = "NetXjD3HPJJjmcd".try_into()?;
+        let block_1 = Head::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".try_into()?,
+            1,
+            vec![],
+        );
+        let block_2 = Head::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7".try_into()?,
+            2,
+            vec![],
+        );
+
+        // no best known head
+        assert!(index.get_best_known_head(&chain_id1)?.is_none());
+
+        // set and update for chain_id1
+        index.set_best_known_head(&chain_id1, block_1)?;
+        index.set_best_known_head(&chain_id1, block_2.clone())?;
+        let best_known_head = index.get_best_known_head(&chain_id1)?.unwrap();
+        assert_eq!(best_known_head.block_hash(), block_2.block_hash());
+        assert_eq!(best_known_head.level(), block_2.level());
+        assert!(index.get_best_known_head(&chain_id2)?.is_none());
+
+        // does not interfere with current head
+        assert!(index.get_current_head(&chain_id1)?.is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_test_chain_id() -> Result<(), Error> {
         let tmp_storage = TmpStorage::create_to_out_dir("__test_test_chain_id")?;
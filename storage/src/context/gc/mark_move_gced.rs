@@ -14,12 +14,13 @@ use crypto::hash::HashType;
 
 use crate::context::gc::{
     collect_hashes_recursively, fetch_entry_from_store, GarbageCollectionError, GarbageCollector,
+    GcCycleStats,
 };
 use crate::context::merkle::hash::EntryHash;
 use crate::context::merkle::Entry;
 use crate::context::{ContextKeyValueStoreSchema, ContextValue};
 use crate::persistent::database::DBError;
-use crate::persistent::KeyValueStoreBackend;
+use crate::persistent::{Flushable, KeyValueStoreBackend, MultiInstanceable, Persistable};
 
 const COUNT_OF_KEYS_TO_CLEANUP_IN_SINGLE_GC_ITERATION: usize = 2048;
 
@@ -170,14 +171,16 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Send + Sync
     /// Starts a new cycle.
     ///
     /// Garbage collector will start collecting the oldest cycle.
-    pub fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
+    /// Collection runs on the background thread, so nothing is reported as collected yet.
+    pub fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError> {
         self.msg_cnt.fetch_add(1, Ordering::Acquire);
         self.stores.write()?.push(mem::take(&mut self.current));
         self.msg.lock()?.send(CmdMsg::StartNewCycle).map_err(|_| {
             GarbageCollectionError::GarbageCollectorError {
                 error: "cannot send message to GC thread".to_string(),
             }
-        })
+        })?;
+        Ok(GcCycleStats::default())
     }
 
     /// Waits for garbage collector to finish collecting the oldest cycle.
@@ -202,6 +205,12 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Send + Sync
     }
 
     fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        // value from older cycle has to be moved to current store first, to be appended to
+        if !self.current.contains(key)? {
+            if let Some(prev) = self.stores_get(key)? {
+                self.current.put(key, &prev)?;
+            }
+        }
         self.current.merge(key, value)
     }
 
@@ -231,11 +240,52 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Send + Sync
         Ok(memory?.iter().sum::<usize>() + self.current.total_get_mem_usage()?)
     }
 
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        let mut size = self.current.disk_size()?;
+        for store in self.stores.read()?.iter() {
+            if let Some(store_size) = store.disk_size()? {
+                size = Some(size.unwrap_or(0) + store_size);
+            }
+        }
+        Ok(size)
+    }
+
     fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
         self.current.retain(predicate)
     }
 }
 
+impl<T: KeyValueStoreBackend<ContextKeyValueStoreSchema> + Flushable> Flushable
+    for MarkMoveGCed<T>
+{
+    fn flush(&self) -> Result<(), failure::Error> {
+        for store in self
+            .stores
+            .read()
+            .map_err(|e| failure::format_err!("{}", e))?
+            .iter()
+        {
+            store.flush()?;
+        }
+        self.current.flush()
+    }
+}
+
+impl<T: KeyValueStoreBackend<ContextKeyValueStoreSchema> + Persistable> Persistable
+    for MarkMoveGCed<T>
+{
+    fn is_persistent(&self) -> bool {
+        self.current.is_persistent()
+    }
+}
+
+impl<T: KeyValueStoreBackend<ContextKeyValueStoreSchema>> MultiInstanceable for MarkMoveGCed<T> {
+    fn supports_multiple_opened_instances(&self) -> bool {
+        // cycle stores are owned by this instance and its gc thread
+        false
+    }
+}
+
 /// Garbage collector main function
 fn kvstore_gc_thread_fn<T: KeyValueStoreBackend<ContextKeyValueStoreSchema>>(
     stores: Arc<RwLock<Vec<T>>>,
@@ -395,7 +445,7 @@ fn kvstore_gc_thread_fn<T: KeyValueStoreBackend<ContextKeyValueStoreSchema>>(
 impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Send + Sync + Default>
     GarbageCollector for MarkMoveGCed<T>
 {
-    fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
+    fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError> {
         self.new_cycle_started()
     }
 
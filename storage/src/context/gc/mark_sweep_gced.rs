@@ -3,29 +3,42 @@
 
 use std::collections::HashMap;
 use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use crypto::hash::HashType;
 
 use crate::context::gc::{
-    collect_hashes, fetch_entry_from_store, GarbageCollectionError, GarbageCollector,
+    collect_hashes, fetch_entry_from_store, verify_commit, GarbageCollectionError,
+    GarbageCollector, GcCycleStats, GcStats, VerifyReport,
 };
+use crate::context::kv_store::stats::StorageBackendStats;
 use crate::context::merkle::hash::EntryHash;
 use crate::context::merkle::Entry;
 use crate::context::{ContextKeyValueStoreSchema, ContextValue};
 use crate::persistent::database::DBError;
-use crate::persistent::KeyValueStoreBackend;
+use crate::persistent::{
+    Flushable, KeyValueStoreBackend, KeyValueStoreBackendIterator, MultiInstanceable, Persistable,
+};
 
 /// Garbage Collected Key Value Store
 pub struct MarkSweepGCed<T: KeyValueStoreBackend<ContextKeyValueStoreSchema>> {
-    // TODO: rework gc store must be passed as argument, and not to create default here
     store: T,
     cycles_limit: usize,
     cycles: VecDeque<HashSet<EntryHash>>,
     cache: HashMap<EntryHash, HashSet<EntryHash>>,
+    last_cycle_duration: Option<Duration>,
+    last_cycle: GcCycleStats,
 }
 
 impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default> MarkSweepGCed<T> {
     pub fn new(cycle_count: usize) -> Self {
+        Self::with_store(Default::default(), cycle_count)
+    }
+}
+
+impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema>> MarkSweepGCed<T> {
+    pub fn with_store(store: T, cycle_count: usize) -> Self {
         let mut cycles = VecDeque::new();
 
         for _ in 0..cycle_count + 1 {
@@ -34,11 +47,12 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default> Ma
         }
 
         Self {
-            // TODO: zrusti default
-            store: Default::default(),
+            store,
             cycles_limit: cycle_count + 1,
             cycles,
             cache: HashMap::new(),
+            last_cycle_duration: None,
+            last_cycle: GcCycleStats::default(),
         }
     }
 
@@ -48,7 +62,8 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default> Ma
         }
     }
 
-    pub fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
+    pub fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError> {
+        let started = Instant::now();
         self.cycles.push_back(HashSet::new());
 
         while self.cycles.len() > self.cycles_limit {
@@ -60,14 +75,65 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default> Ma
             entries_in_use.extend(entries);
         }
 
-        self.sweep_entries(entries_in_use)?;
+        self.last_cycle = self.sweep_entries(entries_in_use)?;
+        self.last_cycle_duration = Some(started.elapsed());
 
-        Ok(())
+        Ok(self.last_cycle)
+    }
+
+    fn sweep_entries(
+        &mut self,
+        todo: HashSet<EntryHash>,
+    ) -> Result<GcCycleStats, GarbageCollectionError> {
+        // reclaimed bytes are sizes of the values of the swept entries
+        let mut garbage = HashSet::new();
+        let mut reclaimed_bytes = 0;
+        for entry in self.store.iter()? {
+            let (key, value) = entry?;
+            if !todo.contains(&key) {
+                reclaimed_bytes += value.len();
+                garbage.insert(key);
+            }
+        }
+
+        Ok(GcCycleStats {
+            entries_collected: self.store.delete_batch(&garbage)?,
+            reclaimed_bytes,
+        })
+    }
+
+    pub fn gc_stats(&self) -> GcStats {
+        // entries marked only in the oldest cycle are swept, when the next cycle starts
+        let pending_to_collect = match self.cycles.front() {
+            Some(oldest) => oldest
+                .iter()
+                .filter(|k| !self.cycles.iter().skip(1).any(|cycle| cycle.contains(*k)))
+                .count(),
+            None => 0,
+        };
+
+        let storage = self
+            .cycles
+            .iter()
+            .fold(StorageBackendStats::default(), |acc, cycle| {
+                let mut stats = StorageBackendStats::default();
+                stats.update_reused_keys(cycle);
+                acc + stats
+            });
+
+        GcStats {
+            pending_to_collect,
+            last_cycle_duration: self.last_cycle_duration,
+            reclaimed_bytes: self.last_cycle.reclaimed_bytes,
+            entries_collected: self.last_cycle.entries_collected,
+            storage,
+        }
     }
 
-    fn sweep_entries(&mut self, todo: HashSet<EntryHash>) -> Result<(), GarbageCollectionError> {
-        self.retain(&|x| todo.contains(x))?;
-        Ok(())
+    /// Read-only integrity check of entries reachable from the given commit
+    /// (e.g. the last commit of the context), see [verify_commit]
+    pub fn verify(&self, commit: EntryHash) -> Result<VerifyReport, GarbageCollectionError> {
+        verify_commit(&self.store, commit)
     }
 
     fn store_entries_referenced_by_commit(
@@ -96,19 +162,23 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default> Ma
     }
 }
 
-impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default> GarbageCollector
+impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema>> GarbageCollector
     for MarkSweepGCed<T>
 {
-    fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
+    fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError> {
         self.new_cycle_started()
     }
 
     fn block_applied(&mut self, commit: EntryHash) -> Result<(), GarbageCollectionError> {
         self.store_entries_referenced_by_commit(commit)
     }
+
+    fn gc_stats(&self) -> GcStats {
+        self.gc_stats()
+    }
 }
 
-impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default>
+impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema>>
     KeyValueStoreBackend<ContextKeyValueStoreSchema> for MarkSweepGCed<T>
 {
     fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
@@ -155,13 +225,59 @@ impl<T: 'static + KeyValueStoreBackend<ContextKeyValueStoreSchema> + Default>
     fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
         self.store.retain(predicate)
     }
+
+    fn delete_batch(&self, keys: &HashSet<EntryHash>) -> Result<usize, DBError> {
+        self.store.delete_batch(keys)
+    }
+
+    fn len(&self) -> Result<usize, DBError> {
+        self.store.len()
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        self.store.disk_size()
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        self.store.checkpoint(path)
+    }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        self.store.iter()
+    }
+}
+
+impl<T: KeyValueStoreBackend<ContextKeyValueStoreSchema> + Flushable> Flushable
+    for MarkSweepGCed<T>
+{
+    fn flush(&self) -> Result<(), failure::Error> {
+        self.store.flush()
+    }
+}
+
+impl<T: KeyValueStoreBackend<ContextKeyValueStoreSchema> + Persistable> Persistable
+    for MarkSweepGCed<T>
+{
+    fn is_persistent(&self) -> bool {
+        self.store.is_persistent()
+    }
+}
+
+impl<T: KeyValueStoreBackend<ContextKeyValueStoreSchema>> MultiInstanceable for MarkSweepGCed<T> {
+    fn supports_multiple_opened_instances(&self) -> bool {
+        // gc state (marked cycles) is held only by this instance
+        false
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+    use std::sync::Arc;
+
     use crate::context::kv_store::in_memory_backend::InMemoryBackend;
     use crate::context::merkle::hash::hash_entry;
-    use crate::context::merkle::Entry;
+    use crate::context::merkle::{Commit, Entry, Node, NodeKind, Tree};
 
     use super::*;
 
@@ -419,4 +535,107 @@ mod tests {
             .unwrap()
             .is_empty());
     }
+
+    #[test]
+    fn test_verify() {
+        let blob_1 = Entry::Blob(vec![1]);
+        let blob_2 = Entry::Blob(vec![2]);
+        let hash_blob_1 = hash_entry(&blob_1).unwrap();
+        let hash_blob_2 = hash_entry(&blob_2).unwrap();
+
+        let leaf = |hash: EntryHash| {
+            Arc::new(Node {
+                node_kind: NodeKind::Leaf,
+                entry_hash: RefCell::new(Some(hash)),
+                entry: RefCell::new(None),
+            })
+        };
+        let tree = Entry::Tree(
+            Tree::new()
+                .update(Arc::new("a".to_string()), leaf(hash_blob_1))
+                .update(Arc::new("b".to_string()), leaf(hash_blob_2)),
+        );
+        let hash_tree = hash_entry(&tree).unwrap();
+        let commit = Entry::Commit(Commit {
+            parent_commit_hash: None,
+            root_hash: hash_tree,
+            time: 0,
+            author: "author".to_string(),
+            message: "message".to_string(),
+        });
+        let hash_commit = hash_entry(&commit).unwrap();
+
+        let store = MarkSweepGCed::<InMemoryBackend>::new(1);
+        // nothing stored yet, so the commit itself is missing
+        let report = store.verify(hash_commit).unwrap();
+        assert_eq!(vec![hash_commit], report.missing);
+
+        for (hash, entry) in &[
+            (hash_blob_1, &blob_1),
+            (hash_blob_2, &blob_2),
+            (hash_tree, &tree),
+            (hash_commit, &commit),
+        ] {
+            store
+                .put(hash, &bincode::serialize(entry).unwrap())
+                .unwrap();
+        }
+
+        let report = store.verify(hash_commit).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(4, report.checked_entries);
+
+        // both problems are reported, verification does not stop on the first one
+        store.delete(&hash_blob_1).unwrap();
+        store.put(&hash_blob_2, &vec![0xff]).unwrap();
+        let report = store.verify(hash_commit).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(vec![hash_blob_1], report.missing);
+        assert_eq!(vec![hash_blob_2], report.corrupted);
+    }
+
+    #[test]
+    fn test_mark_sweep_gc_stats() {
+        let value_1 = Entry::Blob(vec![1]);
+        let value_2 = Entry::Blob(vec![2]);
+        let hash_1 = hash_entry(&value_1).unwrap();
+        let hash_2 = hash_entry(&value_2).unwrap();
+
+        let mut store = MarkSweepGCed::<InMemoryBackend>::new(1);
+        assert!(store.gc_stats().last_cycle_duration.is_none());
+
+        // CYCLE 1 - value_1 is marked, value_2 is not reused at all
+        store
+            .put(&hash_1, &bincode::serialize(&value_1).unwrap())
+            .unwrap();
+        store
+            .put(&hash_2, &bincode::serialize(&value_2).unwrap())
+            .unwrap();
+        store.mark_reused(vec![hash_1].into_iter().collect::<HashSet<EntryHash>>());
+        let cycle = store.new_cycle_started().unwrap();
+        assert_eq!(1, cycle.entries_collected);
+        assert_eq!(
+            bincode::serialize(&value_2).unwrap().len(),
+            cycle.reclaimed_bytes
+        );
+
+        // value_2 was swept, value_1 is marked only in the oldest cycle now
+        let stats = store.gc_stats();
+        assert!(stats.last_cycle_duration.is_some());
+        assert_eq!(cycle.reclaimed_bytes, stats.reclaimed_bytes);
+        assert_eq!(1, stats.entries_collected);
+        assert!(stats.storage.reused_keys_bytes > 0);
+        assert_eq!(stats.pending_to_collect, 1);
+        assert!(store.get(&hash_2).unwrap().is_none());
+
+        // CYCLE 2 - value_1 was not reused again, so it is swept
+        store.new_cycle_started().unwrap();
+        let stats = store.gc_stats();
+        assert_eq!(
+            bincode::serialize(&value_1).unwrap().len(),
+            stats.reclaimed_bytes
+        );
+        assert_eq!(stats.pending_to_collect, 0);
+        assert!(store.get(&hash_1).unwrap().is_none());
+    }
 }
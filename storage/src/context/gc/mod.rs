@@ -5,13 +5,19 @@
 
 use std::array::TryFromSliceError;
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 use std::sync::PoisonError;
+use std::time::Duration;
 
 use blake2::digest::InvalidOutputSize;
 use failure::Fail;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
 
 use crypto::hash::{FromBytesError, HashType};
 
+use crate::context::kv_store::stats::StorageBackendStats;
 use crate::context::merkle::hash::{hash_entry, HashingError};
 use crate::context::merkle::Entry;
 use crate::context::{ContextKeyValueStoreSchema, EntryHash};
@@ -20,17 +26,172 @@ use crate::persistent::{DBError, KeyValueStoreBackend};
 pub mod mark_move_gced;
 pub mod mark_sweep_gced;
 
+/// How many cycles are preserved by garbage collected kv-stores (tezos `preserved_cycles`)
+pub const PRESERVED_CYCLES_COUNT: usize = 5;
+
+/// Selects garbage collection strategy, which runs on top of the context kv-store
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, EnumIter)]
+pub enum GcStrategy {
+    /// No garbage collection at all, `new_cycle_started` and `block_applied` are no-ops
+    /// and nothing is ever removed from the store (see [NotGarbageCollected])
+    None,
+    /// Entries reused in preserved cycles are marked, everything else is swept on new cycle
+    MarkSweep,
+    /// Reused entries are moved to the newer cycle stores on a background thread
+    /// and the oldest cycle store is dropped as a whole
+    MarkMove,
+}
+
+impl Default for GcStrategy {
+    fn default() -> Self {
+        GcStrategy::None
+    }
+}
+
+impl GcStrategy {
+    pub fn possible_values() -> Vec<&'static str> {
+        let mut possible_values = Vec::new();
+        for sp in GcStrategy::iter() {
+            possible_values.extend(sp.supported_values());
+        }
+        possible_values
+    }
+
+    fn supported_values(&self) -> Vec<&'static str> {
+        match self {
+            GcStrategy::None => vec!["none"],
+            GcStrategy::MarkSweep => vec!["mark_sweep"],
+            GcStrategy::MarkMove => vec!["mark_move"],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseGcStrategyError(String);
+
+impl FromStr for GcStrategy {
+    type Err = ParseGcStrategyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        for sp in GcStrategy::iter() {
+            if sp.supported_values().contains(&s.as_str()) {
+                return Ok(sp);
+            }
+        }
+
+        Err(ParseGcStrategyError(format!("Invalid variant name: {}", s)))
+    }
+}
+
 pub trait GarbageCollector {
-    fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError>;
+    /// Notifies gc about new cycle, returns what was collected from the cycle just closed
+    /// (zeros, if nothing is collected or collection runs asynchronously)
+    fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError>;
 
     fn block_applied(&mut self, commit: EntryHash) -> Result<(), GarbageCollectionError>;
+
+    /// Returns current progress/backlog of the garbage collection,
+    /// can be polled to detect, that gc is not keeping up with block application
+    fn gc_stats(&self) -> GcStats {
+        GcStats::default()
+    }
 }
 
+/// Garbage collection progress statistics
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct GcStats {
+    /// Count of entries, which will be collectable on the next cycle
+    pub pending_to_collect: usize,
+    /// How long the last `new_cycle_started` (sweep) took
+    pub last_cycle_duration: Option<Duration>,
+    /// Bytes reclaimed from the underlying store by the last sweep
+    pub reclaimed_bytes: usize,
+    /// Entries removed from the underlying store by the last sweep
+    pub entries_collected: usize,
+    /// Memory used by the gc itself, see [StorageBackendStats::reused_keys_bytes]
+    pub storage: StorageBackendStats,
+}
+
+/// What was collected, when the cycle was closed by [GarbageCollector::new_cycle_started]
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct GcCycleStats {
+    pub entries_collected: usize,
+    pub reclaimed_bytes: usize,
+}
+
+/// Result of the read-only integrity check of the context store (see [verify_commit]),
+/// all problems found are collected, the check does not stop on the first one
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct VerifyReport {
+    /// Count of distinct entries reachable from the commit, which were checked
+    pub checked_entries: usize,
+    /// Entries referenced by commit or tree, but missing in the store (dangling references)
+    pub missing: Vec<EntryHash>,
+    /// Entries present in the store, which cannot be deserialized or have no child hash
+    pub corrupted: Vec<EntryHash>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupted.is_empty()
+    }
+}
+
+/// Walks all entries reachable from `commit` (its root tree, not parent commits) in the `store`
+/// and reports missing or corrupted ones
+pub fn verify_commit(
+    store: &dyn KeyValueStoreBackend<ContextKeyValueStoreSchema>,
+    commit: EntryHash,
+) -> Result<VerifyReport, GarbageCollectionError> {
+    let mut report = VerifyReport::default();
+    let mut visited = HashSet::new();
+    let mut todo = vec![commit];
+
+    while let Some(hash) = todo.pop() {
+        if !visited.insert(hash) {
+            continue;
+        }
+        report.checked_entries += 1;
+
+        let entry_bytes = match store.get(&hash)? {
+            Some(entry_bytes) => entry_bytes,
+            None => {
+                report.missing.push(hash);
+                continue;
+            }
+        };
+
+        match bincode::deserialize::<Entry>(&entry_bytes) {
+            Ok(Entry::Blob(_)) => (),
+            Ok(Entry::Commit(commit)) => todo.push(commit.root_hash),
+            Ok(Entry::Tree(tree)) => {
+                for (_, child_node) in tree.iter() {
+                    match *child_node
+                        .entry_hash
+                        .try_borrow()
+                        .map_err(|_| HashingError::EntryBorrow)?
+                    {
+                        Some(child_hash) => todo.push(child_hash),
+                        None => report.corrupted.push(hash),
+                    }
+                }
+            }
+            Err(_) => report.corrupted.push(hash),
+        }
+    }
+
+    report.corrupted.dedup();
+    Ok(report)
+}
+
+/// Marker for kv-stores without garbage collection ([GcStrategy::None]),
+/// all [GarbageCollector] methods are explicit no-ops for them
 pub trait NotGarbageCollected {}
 
 impl<T: NotGarbageCollected> GarbageCollector for T {
-    fn new_cycle_started(&mut self) -> Result<(), GarbageCollectionError> {
-        Ok(())
+    fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError> {
+        Ok(GcCycleStats::default())
     }
 
     fn block_applied(&mut self, _commit: EntryHash) -> Result<(), GarbageCollectionError> {
@@ -205,3 +366,26 @@ impl slog::Value for GarbageCollectionError {
         serializer.emit_arguments(key, &format_args!("{}", self))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gc_strategy_from_str() {
+        assert_eq!(GcStrategy::None, "none".parse::<GcStrategy>().unwrap());
+        assert_eq!(
+            GcStrategy::MarkSweep,
+            "mark_sweep".parse::<GcStrategy>().unwrap()
+        );
+        assert_eq!(
+            GcStrategy::MarkMove,
+            "MARK_MOVE".parse::<GcStrategy>().unwrap()
+        );
+        assert!("reference_counting".parse::<GcStrategy>().is_err());
+        assert_eq!(
+            GcStrategy::iter().count(),
+            GcStrategy::possible_values().len()
+        );
+    }
+}
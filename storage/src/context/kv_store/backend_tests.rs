@@ -3,12 +3,13 @@
 
 use std::collections::HashSet;
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use storage::context::kv_store::test_support::{
+use crate::context::kv_store::test_support::{
     blob_serialized, entry_hash, TestContextKvStoreFactoryInstance,
 };
-use storage::context::kv_store::SupportedContextKeyValueStore;
+use crate::context::kv_store::SupportedContextKeyValueStore;
 
 fn test_put_get(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     let storage = kv_store_factory.create("test_put_get").unwrap();
@@ -34,6 +35,22 @@ fn test_put_twice(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     );
 }
 
+fn test_merge_appends(kv_store_factory: &TestContextKvStoreFactoryInstance) {
+    let storage = kv_store_factory.create("test_merge_appends").unwrap();
+
+    let key = entry_hash(&[1]);
+    storage.merge(&key, &vec![1, 2]).unwrap();
+    assert_eq!(vec![1, 2], storage.get(&key).unwrap().unwrap());
+
+    storage.merge(&key, &vec![3]).unwrap();
+    assert_eq!(vec![1, 2, 3], storage.get(&key).unwrap().unwrap());
+
+    // put still overrides merged value
+    storage.put(&key, &vec![4]).unwrap();
+    storage.merge(&key, &vec![5]).unwrap();
+    assert_eq!(vec![4, 5], storage.get(&key).unwrap().unwrap());
+}
+
 fn test_put_delete_get(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     let storage = kv_store_factory.create("test_put_delete_get").unwrap();
 
@@ -113,6 +130,23 @@ fn test_write_batch(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     );
 }
 
+fn test_write_batch_large(kv_store_factory: &TestContextKvStoreFactoryInstance) {
+    let storage = kv_store_factory.create("test_write_batch_large").unwrap();
+
+    let key = |i: u16| entry_hash(&i.to_be_bytes());
+    let batch = (0..10_000_u16)
+        .map(|i| (key(i), blob_serialized(i.to_be_bytes().to_vec())))
+        .collect::<Vec<_>>();
+    storage.write_batch(batch).unwrap();
+
+    for i in 0..10_000_u16 {
+        assert_eq!(
+            blob_serialized(i.to_be_bytes().to_vec()),
+            storage.get(&key(i)).unwrap().unwrap()
+        );
+    }
+}
+
 fn test_retain(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     let storage = kv_store_factory.create("test_retain").unwrap();
 
@@ -130,6 +164,96 @@ fn test_retain(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     assert!(storage.get(&entry_hash(&[2])).unwrap().is_none());
 }
 
+fn test_delete_batch(kv_store_factory: &TestContextKvStoreFactoryInstance) {
+    let storage = kv_store_factory.create("test_delete_batch").unwrap();
+
+    let batch = vec![
+        (entry_hash(&[1]), blob_serialized(vec![11])),
+        (entry_hash(&[2]), blob_serialized(vec![22])),
+        (entry_hash(&[3]), blob_serialized(vec![33])),
+        (entry_hash(&[4]), blob_serialized(vec![44])),
+    ];
+    storage.write_batch(batch).unwrap();
+
+    // key 5 does not exist, so it is not counted
+    let garbage = vec![entry_hash(&[2]), entry_hash(&[4]), entry_hash(&[5])]
+        .into_iter()
+        .collect::<HashSet<_>>();
+    assert_eq!(2, storage.delete_batch(&garbage).unwrap());
+
+    assert!(storage.get(&entry_hash(&[1])).unwrap().is_some());
+    assert!(storage.get(&entry_hash(&[2])).unwrap().is_none());
+    assert!(storage.get(&entry_hash(&[3])).unwrap().is_some());
+    assert!(storage.get(&entry_hash(&[4])).unwrap().is_none());
+    assert_eq!(0, storage.delete_batch(&garbage).unwrap());
+}
+
+fn test_iter(kv_store_factory: &TestContextKvStoreFactoryInstance) {
+    let storage = kv_store_factory.create("test_iter").unwrap();
+
+    let batch = vec![
+        (entry_hash(&[1]), blob_serialized(vec![11])),
+        (entry_hash(&[2]), blob_serialized(vec![22])),
+        (entry_hash(&[3]), blob_serialized(vec![33])),
+    ];
+    storage.write_batch(batch.clone()).unwrap();
+    storage.delete(&entry_hash(&[3])).unwrap();
+
+    let entries = storage
+        .iter()
+        .unwrap()
+        .collect::<Result<HashSet<_>, _>>()
+        .unwrap();
+    let expected = batch.into_iter().take(2).collect::<HashSet<_>>();
+    assert_eq!(expected, entries);
+}
+
+fn test_len(kv_store_factory: &TestContextKvStoreFactoryInstance) {
+    let storage = kv_store_factory.create("test_len").unwrap();
+    assert!(storage.is_empty().unwrap());
+
+    let batch = vec![
+        (entry_hash(&[1]), blob_serialized(vec![11])),
+        (entry_hash(&[2]), blob_serialized(vec![22])),
+        (entry_hash(&[3]), blob_serialized(vec![33])),
+    ];
+    storage.write_batch(batch).unwrap();
+    assert!(!storage.is_empty().unwrap());
+    assert_eq!(3, storage.len().unwrap());
+}
+
+fn test_checkpoint(kv_store_factory: &TestContextKvStoreFactoryInstance) {
+    let storage = kv_store_factory.create("test_checkpoint").unwrap();
+    storage
+        .put(&entry_hash(&[1]), &blob_serialized(vec![11]))
+        .unwrap();
+
+    let checkpoint_path = match kv_store_factory.path("test_checkpoint_copy") {
+        Some(checkpoint_path) => checkpoint_path,
+        None => {
+            // non-persistent storages cannot snapshot
+            assert!(storage.checkpoint(Path::new("test_checkpoint")).is_err());
+            return;
+        }
+    };
+    if checkpoint_path.exists() {
+        fs::remove_dir_all(&checkpoint_path).unwrap();
+    }
+    storage.checkpoint(&checkpoint_path).unwrap();
+
+    // writes after checkpoint are not visible in the copy
+    storage
+        .put(&entry_hash(&[2]), &blob_serialized(vec![22]))
+        .unwrap();
+
+    let restored = kv_store_factory.open("test_checkpoint_copy").unwrap();
+    assert_eq!(
+        blob_serialized(vec![11]),
+        restored.get(&entry_hash(&[1])).unwrap().unwrap()
+    );
+    assert!(restored.get(&entry_hash(&[2])).unwrap().is_none());
+}
+
 // TODO: TE-150 - real support mutliprocess
 fn test_multiple_open_instances(kv_store_factory: &TestContextKvStoreFactoryInstance) {
     if !kv_store_factory.supports_multiple_opened_instances() {
@@ -195,6 +319,10 @@ macro_rules! tests_with_storage {
                 super::test_put_twice($kv_store_factory)
             }
             #[test]
+            fn test_merge_appends() {
+                super::test_merge_appends($kv_store_factory)
+            }
+            #[test]
             fn test_put_delete_get() {
                 super::test_put_delete_get($kv_store_factory)
             }
@@ -219,10 +347,30 @@ macro_rules! tests_with_storage {
                 super::test_write_batch($kv_store_factory)
             }
             #[test]
+            fn test_write_batch_large() {
+                super::test_write_batch_large($kv_store_factory)
+            }
+            #[test]
             fn test_retain() {
                 super::test_retain($kv_store_factory)
             }
             #[test]
+            fn test_delete_batch() {
+                super::test_delete_batch($kv_store_factory)
+            }
+            #[test]
+            fn test_iter() {
+                super::test_iter($kv_store_factory)
+            }
+            #[test]
+            fn test_len() {
+                super::test_len($kv_store_factory)
+            }
+            #[test]
+            fn test_checkpoint() {
+                super::test_checkpoint($kv_store_factory)
+            }
+            #[test]
             fn test_multiple_open_instances() {
                 super::test_multiple_open_instances($kv_store_factory)
             }
@@ -231,7 +379,7 @@ macro_rules! tests_with_storage {
 }
 
 lazy_static::lazy_static! {
-    static ref SUPPORTED_KV_STORES: std::collections::HashMap<SupportedContextKeyValueStore, TestContextKvStoreFactoryInstance> = storage::context::kv_store::test_support::all_kv_stores(out_dir_path());
+    static ref SUPPORTED_KV_STORES: std::collections::HashMap<SupportedContextKeyValueStore, TestContextKvStoreFactoryInstance> = crate::context::kv_store::test_support::all_kv_stores(out_dir_path());
 }
 
 fn out_dir_path() -> PathBuf {
@@ -245,20 +393,20 @@ macro_rules! tests_with_all_kv_stores {
         tests_with_storage!(
             kv_store_inmemory_tests,
             super::SUPPORTED_KV_STORES
-                .get(&storage::context::kv_store::SupportedContextKeyValueStore::InMem)
+                .get(&crate::context::kv_store::SupportedContextKeyValueStore::InMem)
                 .unwrap()
         );
         tests_with_storage!(
             kv_store_btree_tests,
             super::SUPPORTED_KV_STORES
-                .get(&storage::context::kv_store::SupportedContextKeyValueStore::BTreeMap)
+                .get(&crate::context::kv_store::SupportedContextKeyValueStore::BTreeMap)
                 .unwrap()
         );
         tests_with_storage!(
             kv_store_rocksdb_tests,
             super::SUPPORTED_KV_STORES
                 .get(
-                    &storage::context::kv_store::SupportedContextKeyValueStore::RocksDB {
+                    &crate::context::kv_store::SupportedContextKeyValueStore::RocksDB {
                         path: super::out_dir_path()
                     }
                 )
@@ -268,7 +416,7 @@ macro_rules! tests_with_all_kv_stores {
             kv_store_sled_tests,
             super::SUPPORTED_KV_STORES
                 .get(
-                    &storage::context::kv_store::SupportedContextKeyValueStore::Sled {
+                    &crate::context::kv_store::SupportedContextKeyValueStore::Sled {
                         path: super::out_dir_path()
                     }
                 )
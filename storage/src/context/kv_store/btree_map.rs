@@ -10,7 +10,9 @@ use crate::context::kv_store::stats::StorageBackendStats;
 use crate::context::merkle::hash::EntryHash;
 use crate::context::{ContextKeyValueStoreSchema, ContextValue};
 use crate::persistent::database::DBError;
-use crate::persistent::{Flushable, KeyValueStoreBackend, MultiInstanceable, Persistable};
+use crate::persistent::{
+    Flushable, KeyValueStoreBackend, KeyValueStoreBackendIterator, MultiInstanceable, Persistable,
+};
 
 /// In Memory Key Value Store implemented with [BTreeMap](std::collections::BTreeMap)
 #[derive(Debug)]
@@ -68,11 +70,17 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for BTreeMapBackend<EntryH
     }
 
     fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        let mut map = self.kv_map.write()?;
+
+        let merged = match map.get(key) {
+            Some(prev) => [prev.as_slice(), value.as_slice()].concat(),
+            None => value.clone(),
+        };
         self.stats
             .write()?
             .deref_mut()
-            .add_assign(StorageBackendStats::from((key, value)));
-        if let Some(prev) = self.kv_map.write()?.insert(*key, value.clone()) {
+            .add_assign(StorageBackendStats::from((key, &merged)));
+        if let Some(prev) = map.insert(*key, merged) {
             self.stats
                 .write()?
                 .deref_mut()
@@ -102,6 +110,10 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for BTreeMapBackend<EntryH
         Ok(self.kv_map.read()?.contains_key(key))
     }
 
+    fn len(&self) -> Result<usize, DBError> {
+        Ok(self.kv_map.read()?.len())
+    }
+
     fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
         let garbage_keys: Vec<_> = self
             .kv_map
@@ -118,10 +130,20 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for BTreeMapBackend<EntryH
 
     fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
         for (k, v) in batch {
-            self.merge(&k, &v)?;
+            self.put(&k, &v)?;
         }
         Ok(())
     }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        let entries: Vec<_> = self
+            .kv_map
+            .read()?
+            .iter()
+            .map(|(k, v)| Ok((*k, v.clone())))
+            .collect();
+        Ok(Box::new(entries.into_iter()))
+    }
 }
 
 impl Flushable for BTreeMapBackend<EntryHash, ContextValue> {
@@ -168,7 +190,7 @@ mod tests {
         );
 
         // change value under key
-        storage.merge(&entry1, &value2).unwrap();
+        storage.put(&entry1, &value2).unwrap();
         assert_eq!(
             std::mem::size_of::<EntryHash>() + size_of_vec(&value2),
             storage.total_get_mem_usage().unwrap()
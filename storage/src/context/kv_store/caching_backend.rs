@@ -0,0 +1,420 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::mem;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::context::gc::{GarbageCollectionError, GarbageCollector, GcCycleStats, GcStats};
+use crate::context::kv_store::stats::StorageBackendStats;
+use crate::context::merkle::hash::EntryHash;
+use crate::context::{ContextKeyValueStoreSchema, ContextValue};
+use crate::persistent::database::DBError;
+use crate::persistent::{
+    Flushable, KeyValueStoreBackend, KeyValueStoreBackendIterator, MultiInstanceable, Persistable,
+};
+
+/// Least recently used cache of values with fixed capacity (count of entries)
+struct LruCache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<EntryHash, (ContextValue, u64)>,
+    /// tick of last usage -> key, the first one is the least recently used
+    order: BTreeMap<u64, EntryHash>,
+    stats: StorageBackendStats,
+    /// count of invalidations, value read from the inner store is cached just if there was
+    /// no invalidation since the read started, see [LruCache::insert_if_unchanged]
+    generation: u64,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            stats: StorageBackendStats::default(),
+            generation: 0,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, key: &EntryHash) -> Option<ContextValue> {
+        let tick = self.next_tick();
+        let (value, used) = self.entries.get_mut(key)?;
+        self.order.remove(&*used);
+        *used = tick;
+        self.order.insert(tick, *key);
+        Some(value.clone())
+    }
+
+    fn contains(&self, key: &EntryHash) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn insert(&mut self, key: EntryHash, value: ContextValue) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+
+        while self.entries.len() >= self.capacity {
+            match self.order.values().next().copied() {
+                Some(lru) => self.remove(&lru),
+                None => break,
+            }
+        }
+
+        let tick = self.next_tick();
+        self.stats += StorageBackendStats::from((&key, &value));
+        self.order.insert(tick, key);
+        self.entries.insert(key, (value, tick));
+    }
+
+    fn remove(&mut self, key: &EntryHash) {
+        if let Some((value, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+            self.stats -= StorageBackendStats::from((key, &value));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.stats = StorageBackendStats::default();
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Removes the modified `key`, so the value read before the modification is not cached
+    fn invalidate(&mut self, key: &EntryHash) {
+        self.generation += 1;
+        self.remove(key);
+    }
+
+    fn invalidate_all(&mut self) {
+        self.generation += 1;
+        self.clear();
+    }
+
+    /// Inserts the value read from the inner store, if there was no invalidation since `generation`
+    fn insert_if_unchanged(&mut self, generation: u64, key: EntryHash, value: ContextValue) {
+        if self.generation == generation {
+            self.insert(key, value);
+        }
+    }
+
+    fn total_as_bytes(&self) -> usize {
+        // cached keys and values + tick stored in entries and in order
+        self.stats.total_as_bytes()
+            + self.entries.len() * mem::size_of::<u64>()
+            + self.order.len() * (mem::size_of::<u64>() + mem::size_of::<EntryHash>())
+    }
+}
+
+/// Wraps any context kv-store with LRU cache of `get` results.
+///
+/// Cache is invalidated by every modification (put/delete/merge/write_batch/retain/gc) after
+/// the inner store is modified. The lock is not held while accessing the inner store, value read
+/// concurrently with a modification is not cached, so the cache cannot get stale.
+pub struct CachingBackend<B: KeyValueStoreBackend<ContextKeyValueStoreSchema>> {
+    inner: B,
+    cache: Mutex<LruCache>,
+}
+
+impl<B: KeyValueStoreBackend<ContextKeyValueStoreSchema>> CachingBackend<B> {
+    /// Creates wrapper, which caches at most `capacity` values
+    pub fn new(inner: B, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+
+impl<B: KeyValueStoreBackend<ContextKeyValueStoreSchema>>
+    KeyValueStoreBackend<ContextKeyValueStoreSchema> for CachingBackend<B>
+{
+    fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        self.inner.put(key, value)?;
+        self.cache.lock()?.invalidate(key);
+        Ok(())
+    }
+
+    fn delete(&self, key: &EntryHash) -> Result<(), DBError> {
+        self.inner.delete(key)?;
+        self.cache.lock()?.invalidate(key);
+        Ok(())
+    }
+
+    fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        self.inner.merge(key, value)?;
+        self.cache.lock()?.invalidate(key);
+        Ok(())
+    }
+
+    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, DBError> {
+        let generation = {
+            let mut cache = self.cache.lock()?;
+            if let Some(value) = cache.get(key) {
+                return Ok(Some(value));
+            }
+            cache.generation()
+        };
+
+        let value = self.inner.get(key)?;
+        if let Some(value) = &value {
+            self.cache
+                .lock()?
+                .insert_if_unchanged(generation, *key, value.clone());
+        }
+        Ok(value)
+    }
+
+    fn contains(&self, key: &EntryHash) -> Result<bool, DBError> {
+        if self.cache.lock()?.contains(key) {
+            return Ok(true);
+        }
+        self.inner.contains(key)
+    }
+
+    fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
+        let result = self.inner.retain(predicate);
+        // partially applied retain modifies the inner store too
+        self.cache.lock()?.invalidate_all();
+        result
+    }
+
+    fn delete_batch(&self, keys: &HashSet<EntryHash>) -> Result<usize, DBError> {
+        let result = self.inner.delete_batch(keys);
+        let mut cache = self.cache.lock()?;
+        for k in keys.iter() {
+            cache.invalidate(k);
+        }
+        result
+    }
+
+    fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
+        let keys: Vec<EntryHash> = batch.iter().map(|(k, _)| *k).collect();
+        let result = self.inner.write_batch(batch);
+        let mut cache = self.cache.lock()?;
+        for k in keys.iter() {
+            cache.invalidate(k);
+        }
+        result
+    }
+
+    fn total_get_mem_usage(&self) -> Result<usize, DBError> {
+        Ok(self.inner.total_get_mem_usage()? + self.cache.lock()?.total_as_bytes())
+    }
+
+    fn len(&self) -> Result<usize, DBError> {
+        self.inner.len()
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        self.inner.disk_size()
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        self.inner.checkpoint(path)
+    }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        self.inner.iter()
+    }
+}
+
+impl<B: KeyValueStoreBackend<ContextKeyValueStoreSchema> + GarbageCollector> GarbageCollector
+    for CachingBackend<B>
+{
+    fn new_cycle_started(&mut self) -> Result<GcCycleStats, GarbageCollectionError> {
+        let stats = self.inner.new_cycle_started();
+        self.cache.lock()?.invalidate_all();
+        stats
+    }
+
+    fn block_applied(&mut self, commit: EntryHash) -> Result<(), GarbageCollectionError> {
+        self.inner.block_applied(commit)
+    }
+
+    fn gc_stats(&self) -> GcStats {
+        self.inner.gc_stats()
+    }
+}
+
+impl<B: KeyValueStoreBackend<ContextKeyValueStoreSchema> + Flushable> Flushable
+    for CachingBackend<B>
+{
+    fn flush(&self) -> Result<(), failure::Error> {
+        self.inner.flush()
+    }
+}
+
+impl<B: KeyValueStoreBackend<ContextKeyValueStoreSchema> + Persistable> Persistable
+    for CachingBackend<B>
+{
+    fn is_persistent(&self) -> bool {
+        self.inner.is_persistent()
+    }
+}
+
+impl<B: KeyValueStoreBackend<ContextKeyValueStoreSchema>> MultiInstanceable for CachingBackend<B> {
+    fn supports_multiple_opened_instances(&self) -> bool {
+        // cache is not invalidated by writes from other instances
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::context::kv_store::in_memory_backend::InMemoryBackend;
+    use crate::context::kv_store::test_support::{blob_serialized, entry_hash};
+
+    use super::*;
+
+    /// Counts `get` calls, which reached the backend
+    #[derive(Default)]
+    struct CountingBackend {
+        inner: InMemoryBackend,
+        gets: AtomicUsize,
+    }
+
+    impl CountingBackend {
+        fn gets(&self) -> usize {
+            self.gets.load(Ordering::SeqCst)
+        }
+    }
+
+    impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for CountingBackend {
+        fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+            self.inner.put(key, value)
+        }
+
+        fn delete(&self, key: &EntryHash) -> Result<(), DBError> {
+            self.inner.delete(key)
+        }
+
+        fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+            self.inner.merge(key, value)
+        }
+
+        fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, DBError> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            self.inner.get(key)
+        }
+
+        fn contains(&self, key: &EntryHash) -> Result<bool, DBError> {
+            self.inner.contains(key)
+        }
+
+        fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
+            self.inner.retain(predicate)
+        }
+
+        fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
+            self.inner.write_batch(batch)
+        }
+
+        fn total_get_mem_usage(&self) -> Result<usize, DBError> {
+            self.inner.total_get_mem_usage()
+        }
+    }
+
+    #[test]
+    fn test_second_get_is_cached() {
+        let storage = CachingBackend::new(CountingBackend::default(), 10);
+        let key = entry_hash(&[1]);
+        let value = blob_serialized(vec![1]);
+        storage.put(&key, &value).unwrap();
+
+        assert_eq!(value, storage.get(&key).unwrap().unwrap());
+        assert_eq!(1, storage.inner.gets());
+        assert_eq!(value, storage.get(&key).unwrap().unwrap());
+        assert_eq!(1, storage.inner.gets());
+    }
+
+    #[test]
+    fn test_modification_invalidates_cache() {
+        let storage = CachingBackend::new(CountingBackend::default(), 10);
+        let key = entry_hash(&[1]);
+        storage.put(&key, &blob_serialized(vec![1])).unwrap();
+        storage.get(&key).unwrap();
+
+        storage.put(&key, &blob_serialized(vec![2])).unwrap();
+        assert_eq!(
+            blob_serialized(vec![2]),
+            storage.get(&key).unwrap().unwrap()
+        );
+        assert_eq!(2, storage.inner.gets());
+
+        storage.merge(&key, &vec![3]).unwrap();
+        assert_eq!(
+            [blob_serialized(vec![2]), vec![3]].concat(),
+            storage.get(&key).unwrap().unwrap()
+        );
+        assert_eq!(3, storage.inner.gets());
+
+        storage.delete(&key).unwrap();
+        assert!(storage.get(&key).unwrap().is_none());
+        assert_eq!(4, storage.inner.gets());
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let storage = CachingBackend::new(CountingBackend::default(), 2);
+        let keys = vec![entry_hash(&[1]), entry_hash(&[2]), entry_hash(&[3])];
+        for key in keys.iter() {
+            storage.put(key, &blob_serialized(key.to_vec())).unwrap();
+        }
+
+        storage.get(&keys[0]).unwrap();
+        storage.get(&keys[1]).unwrap();
+        // touch the first one, so the second one is evicted by the third one
+        storage.get(&keys[0]).unwrap();
+        storage.get(&keys[2]).unwrap();
+        assert_eq!(3, storage.inner.gets());
+
+        storage.get(&keys[0]).unwrap();
+        assert_eq!(3, storage.inner.gets());
+        storage.get(&keys[1]).unwrap();
+        assert_eq!(4, storage.inner.gets());
+    }
+
+    #[test]
+    fn test_value_read_before_modification_is_not_cached() {
+        let mut cache = LruCache::new(10);
+        let key = entry_hash(&[1]);
+
+        // key was modified, while the value was read from the inner store
+        let generation = cache.generation();
+        cache.invalidate(&key);
+        cache.insert_if_unchanged(generation, key, blob_serialized(vec![1]));
+        assert!(!cache.contains(&key));
+
+        let generation = cache.generation();
+        cache.insert_if_unchanged(generation, key, blob_serialized(vec![2]));
+        assert_eq!(Some(blob_serialized(vec![2])), cache.get(&key));
+    }
+
+    #[test]
+    fn test_mem_usage_includes_cache() {
+        let storage = CachingBackend::new(CountingBackend::default(), 10);
+        let key = entry_hash(&[1]);
+        storage.put(&key, &blob_serialized(vec![1])).unwrap();
+
+        let without_cache = storage.total_get_mem_usage().unwrap();
+        storage.get(&key).unwrap();
+        assert!(storage.total_get_mem_usage().unwrap() > without_cache);
+    }
+}
@@ -1,26 +1,38 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use failure::Error;
+use serde::{Deserialize, Serialize};
 
 use crate::context::gc::NotGarbageCollected;
 use crate::context::kv_store::stats::StorageBackendStats;
 use crate::context::merkle::hash::EntryHash;
 use crate::context::{ContextKeyValueStoreSchema, ContextValue};
 use crate::persistent::database::DBError;
-use crate::persistent::{Flushable, KeyValueStoreBackend, MultiInstanceable, Persistable};
+use crate::persistent::{
+    Flushable, KeyValueStoreBackend, KeyValueStoreBackendIterator, MultiInstanceable, Persistable,
+};
 
 #[derive(Default)]
 pub struct HashMapWithStats {
     inner: HashMap<EntryHash, ContextValue>,
     stats: StorageBackendStats,
+    /// expiration time of entries inserted with ttl, expired entries are treated as absent
+    expirations: HashMap<EntryHash, Instant>,
 }
 
 impl HashMapWithStats {
     pub fn insert(&mut self, key: EntryHash, value: ContextValue) -> Option<ContextValue> {
+        self.expirations.remove(&key);
         let stats = StorageBackendStats::from((&key, &value));
         match self.inner.insert(key, value) {
             Some(prev) => {
@@ -35,7 +47,28 @@ impl HashMapWithStats {
         }
     }
 
+    pub fn insert_with_ttl(
+        &mut self,
+        key: EntryHash,
+        value: ContextValue,
+        ttl: Duration,
+    ) -> Option<ContextValue> {
+        let prev = self.insert(key, value);
+        self.expirations.insert(key, Instant::now() + ttl);
+        prev
+    }
+
+    /// Appends the `value` to the existing value, or inserts it, if there is none
+    pub fn merge(&mut self, key: EntryHash, value: &[u8]) {
+        let merged = match self.get(&key) {
+            Some(prev) => [prev.as_slice(), value].concat(),
+            None => value.to_vec(),
+        };
+        self.insert(key, merged);
+    }
+
     pub fn remove(&mut self, key: &EntryHash) -> Option<ContextValue> {
+        self.expirations.remove(key);
         match self.inner.remove(key) {
             Some(prev) => {
                 self.stats -= StorageBackendStats::from((key, &prev));
@@ -45,16 +78,69 @@ impl HashMapWithStats {
         }
     }
 
+    /// Removes all expired entries, returns count of removed entries
+    pub fn remove_expired(&mut self) -> usize {
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .expirations
+            .iter()
+            .filter_map(|(k, expiration)| if *expiration <= now { Some(*k) } else { None })
+            .collect();
+
+        for k in expired.iter() {
+            self.remove(k);
+        }
+        expired.len()
+    }
+
+    pub fn is_expired(&self, key: &EntryHash) -> bool {
+        self.expired_at(key, Instant::now())
+    }
+
+    fn expired_at(&self, key: &EntryHash, now: Instant) -> bool {
+        match self.expirations.get(key) {
+            Some(expiration) => *expiration <= now,
+            None => false,
+        }
+    }
+
     pub fn get(&self, key: &EntryHash) -> Option<&ContextValue> {
+        if self.is_expired(key) {
+            return None;
+        }
         self.inner.get(key)
     }
 
     pub fn contains_key(&self, key: &EntryHash) -> bool {
-        self.inner.contains_key(key)
+        !self.is_expired(key) && self.inner.contains_key(key)
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<EntryHash, ContextValue> {
-        self.inner.iter()
+    pub fn iter(&self) -> impl Iterator<Item = (&EntryHash, &ContextValue)> {
+        let now = Instant::now();
+        self.inner
+            .iter()
+            .filter(move |(k, _)| !self.expired_at(k, now))
+    }
+
+    /// Entries inserted without ttl, just those are persisted by the [WriteAheadLog]
+    pub fn iter_without_ttl(&self) -> impl Iterator<Item = (&EntryHash, &ContextValue)> {
+        self.inner
+            .iter()
+            .filter(move |(k, _)| !self.expirations.contains_key(*k))
+    }
+
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        let expired = self
+            .expirations
+            .values()
+            .filter(|expiration| **expiration <= now)
+            .count();
+        self.inner.len() - expired
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     pub fn get_memory_usage(&self) -> StorageBackendStats {
@@ -62,15 +148,175 @@ impl HashMapWithStats {
     }
 }
 
+/// Write recorded in the [WriteAheadLog]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum WalRecord<'a> {
+    Put(EntryHash, Cow<'a, [u8]>),
+    Delete(EntryHash),
+    Merge(EntryHash, Cow<'a, [u8]>),
+}
+
+impl WalRecord<'_> {
+    fn apply(self, map: &mut HashMapWithStats) {
+        match self {
+            WalRecord::Put(key, value) => {
+                map.insert(key, value.into_owned());
+            }
+            WalRecord::Delete(key) => {
+                map.remove(&key);
+            }
+            WalRecord::Merge(key, value) => map.merge(key, &value),
+        }
+    }
+}
+
+/// Stored record starts with the length of the serialized record (u32, little endian)
+const WAL_RECORD_HEADER_SIZE: usize = mem::size_of::<u32>();
+
+/// Append-only log of the writes to the [InMemoryBackend], replayed on start to reconstruct the map
+///
+/// Entries with ttl are not persisted, their insert is recorded as delete,
+/// so the replay does not resurrect the value they replaced.
+struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl WriteAheadLog {
+    /// Opens (or creates) the log at `path` and replays it into the `map`,
+    /// partially written record at the end (e.g. after crash) is dropped
+    fn open(path: &Path, map: &mut HashMapWithStats) -> Result<Self, DBError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut reader = BufReader::new(&file);
+        let mut valid_len = 0;
+        while let Some((record, size)) = read_record(&mut reader)? {
+            record.apply(map);
+            valid_len += size as u64;
+        }
+        file.set_len(valid_len)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    fn append(&mut self, record: &WalRecord) -> Result<(), DBError> {
+        write_record(&mut self.file, record)
+    }
+
+    /// Rewrites the log as the snapshot of the `map`, the new log replaces the old one atomically
+    fn compact(&mut self, map: &HashMapWithStats) -> Result<(), DBError> {
+        let compacted_path = self.path.with_extension("compacted");
+        {
+            let mut writer = BufWriter::new(File::create(&compacted_path)?);
+            for (key, value) in map.iter_without_ttl() {
+                write_record(&mut writer, &WalRecord::Put(*key, Cow::Borrowed(value)))?;
+            }
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(&compacted_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<(), DBError> {
+        Ok(self.file.sync_data()?)
+    }
+
+    fn size(&self) -> Result<usize, DBError> {
+        Ok(self.file.metadata()?.len() as usize)
+    }
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &WalRecord) -> Result<(), DBError> {
+    let serialized = bincode::serialize(record).map_err(|e| DBError::IOError {
+        error: io::Error::new(io::ErrorKind::InvalidData, e),
+    })?;
+
+    // single write, so the record is not interleaved with the header
+    let mut buf = Vec::with_capacity(WAL_RECORD_HEADER_SIZE + serialized.len());
+    buf.extend_from_slice(&(serialized.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&serialized);
+    Ok(writer.write_all(&buf)?)
+}
+
+/// Reads next record and its stored size, `None` means end of the log (or its incomplete/corrupted tail)
+fn read_record<R: Read>(reader: &mut R) -> Result<Option<(WalRecord<'static>, usize)>, DBError> {
+    let mut header = [0; WAL_RECORD_HEADER_SIZE];
+    if !read_exact_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+
+    let mut serialized = vec![0; u32::from_le_bytes(header) as usize];
+    if !read_exact_or_eof(reader, &mut serialized)? {
+        return Ok(None);
+    }
+
+    // Cow values are always deserialized as owned, so the record does not borrow the buffer
+    match bincode::deserialize::<WalRecord<'static>>(&serialized) {
+        Ok(record) => Ok(Some((record, WAL_RECORD_HEADER_SIZE + serialized.len()))),
+        Err(_) => Ok(None),
+    }
+}
+
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, DBError> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[derive(Default)]
 pub struct InMemoryBackend {
     inner: Arc<RwLock<HashMapWithStats>>,
+    /// writes are recorded to the log, if present, to survive the restart
+    wal: Option<Mutex<WriteAheadLog>>,
 }
 
 impl InMemoryBackend {
     pub fn new() -> Self {
         InMemoryBackend {
             inner: Arc::new(RwLock::new(HashMapWithStats::default())),
+            wal: None,
+        }
+    }
+
+    /// Creates backend with the write-ahead log at `path`, existing log is replayed first,
+    /// so the entries written before the crash/restart are restored
+    pub fn with_wal<P: AsRef<Path>>(path: P) -> Result<Self, DBError> {
+        let mut map = HashMapWithStats::default();
+        let wal = WriteAheadLog::open(path.as_ref(), &mut map)?;
+        Ok(InMemoryBackend {
+            inner: Arc::new(RwLock::new(map)),
+            wal: Some(Mutex::new(wal)),
+        })
+    }
+
+    /// Rewrites the write-ahead log as the snapshot of the current entries to bound its size,
+    /// does nothing without the log
+    pub fn compact(&self) -> Result<(), DBError> {
+        if let Some(wal) = self.wal.as_ref() {
+            // writers are blocked by the write lock, so no write is lost during the rewrite
+            let w = self.inner.write()?;
+            wal.lock()?.compact(&w)?;
+        }
+        Ok(())
+    }
+
+    /// Must be called under the write lock of the map, so the records are in the same order as the writes
+    fn log(&self, record: &WalRecord) -> Result<(), DBError> {
+        match self.wal.as_ref() {
+            Some(wal) => wal.lock()?.append(record),
+            None => Ok(()),
         }
     }
 }
@@ -94,26 +340,47 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for InMemoryBackend {
 
     fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
         let mut w = self.inner.write()?;
+        self.log(&WalRecord::Put(*key, Cow::Borrowed(value)))?;
         w.insert(*key, value.clone());
         Ok(())
     }
 
     fn delete(&self, key: &EntryHash) -> Result<(), DBError> {
         let mut w = self.inner.write()?;
+        self.log(&WalRecord::Delete(*key))?;
         w.remove(key);
         Ok(())
     }
 
-    fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+    fn put_with_ttl(
+        &self,
+        key: &EntryHash,
+        value: &ContextValue,
+        ttl: Duration,
+    ) -> Result<(), DBError> {
         let mut w = self.inner.write()?;
+        // entry with ttl is not persisted, but the value it replaces must not be replayed
+        self.log(&WalRecord::Delete(*key))?;
+        w.insert_with_ttl(*key, value.clone(), ttl);
+        Ok(())
+    }
 
-        w.insert(*key, value.clone());
+    fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        let mut w = self.inner.write()?;
+        self.log(&WalRecord::Merge(*key, Cow::Borrowed(value)))?;
+        w.merge(*key, value);
         Ok(())
     }
 
     fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, DBError> {
         let db = self.inner.clone();
         let r = db.read()?;
+        if r.is_expired(key) {
+            drop(r);
+            // lazy sweep of expired entries on access
+            db.write()?.remove_expired();
+            return Ok(None);
+        }
 
         match r.get(key) {
             None => Ok(None),
@@ -124,12 +391,35 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for InMemoryBackend {
     fn contains(&self, key: &EntryHash) -> Result<bool, DBError> {
         let db = self.inner.clone();
         let r = db.read()?;
+        if r.is_expired(key) {
+            drop(r);
+            // lazy sweep of expired entries on access
+            db.write()?.remove_expired();
+            return Ok(false);
+        }
         Ok(r.contains_key(key))
     }
 
+    fn delete_batch(&self, keys: &HashSet<EntryHash>) -> Result<usize, DBError> {
+        // lock just once for the whole batch, expired entries are not counted as deleted
+        let mut w = self.inner.write()?;
+        let mut deleted = 0;
+        for k in keys {
+            self.log(&WalRecord::Delete(*k))?;
+            let present = w.contains_key(k);
+            if w.remove(k).is_some() && present {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
     fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
+        // lock just once for the whole batch, stats are updated per entry by insert
+        let mut w = self.inner.write()?;
         for (k, v) in batch {
-            self.merge(&k, &v)?;
+            self.log(&WalRecord::Put(k, Cow::Borrowed(&v)))?;
+            w.insert(k, v);
         }
         Ok(())
     }
@@ -138,10 +428,33 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for InMemoryBackend {
         let r = self.inner.read()?;
         Ok(r.get_memory_usage().total_as_bytes())
     }
+
+    fn len(&self) -> Result<usize, DBError> {
+        Ok(self.inner.read()?.len())
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        // just the write-ahead log is on disk, it shrinks on [InMemoryBackend::compact]
+        self.wal.as_ref().map(|wal| wal.lock()?.size()).transpose()
+    }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        // take a snapshot, so we dont hold the lock while iterating
+        let entries: Vec<_> = self
+            .inner
+            .read()?
+            .iter()
+            .map(|(k, v)| Ok((*k, v.clone())))
+            .collect();
+        Ok(Box::new(entries.into_iter()))
+    }
 }
 
 impl Flushable for InMemoryBackend {
     fn flush(&self) -> Result<(), Error> {
+        if let Some(wal) = self.wal.as_ref() {
+            wal.lock().map_err(DBError::from)?.sync()?;
+        }
         Ok(())
     }
 }
@@ -154,7 +467,7 @@ impl MultiInstanceable for InMemoryBackend {
 
 impl Persistable for InMemoryBackend {
     fn is_persistent(&self) -> bool {
-        false
+        self.wal.is_some()
     }
 }
 
@@ -164,7 +477,11 @@ mod tests {
     use crate::context::kv_store::stats::size_of_vec;
     use crate::context::kv_store::test_support::{blob_serialized, entry_hash};
     use crate::context::EntryHash;
-    use crate::persistent::KeyValueStoreBackend;
+    use crate::persistent::{KeyValueStoreBackend, Persistable};
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
 
     #[test]
     fn test_memory_consumption_in_memory() {
@@ -184,7 +501,7 @@ mod tests {
         );
 
         // change value under key
-        storage.merge(&entry1, &value2).unwrap();
+        storage.put(&entry1, &value2).unwrap();
         assert_eq!(
             std::mem::size_of::<EntryHash>() + size_of_vec(&value2),
             storage.total_get_mem_usage().unwrap()
@@ -207,4 +524,145 @@ mod tests {
         storage.delete(&entry2).unwrap();
         assert_eq!(0, storage.total_get_mem_usage().unwrap());
     }
+
+    #[test]
+    fn test_memory_consumption_write_batch() {
+        let batch = vec![
+            (entry_hash(&[1]), blob_serialized(vec![1, 2, 3, 3, 5])),
+            (entry_hash(&[2]), blob_serialized(vec![11, 22, 33])),
+        ];
+        let expected: usize = batch
+            .iter()
+            .map(|(_, value)| std::mem::size_of::<EntryHash>() + size_of_vec(value))
+            .sum();
+
+        let storage = InMemoryBackend::default();
+        storage.write_batch(batch).unwrap();
+        assert_eq!(expected, storage.total_get_mem_usage().unwrap());
+    }
+
+    #[test]
+    fn test_put_with_ttl() {
+        let entry1 = entry_hash(&[1]);
+        let entry2 = entry_hash(&[2]);
+        let value = blob_serialized(vec![1, 2, 3]);
+
+        let storage = InMemoryBackend::default();
+        storage
+            .put_with_ttl(&entry1, &value, Duration::from_millis(10))
+            .unwrap();
+        storage
+            .put_with_ttl(&entry2, &value, Duration::from_secs(3600))
+            .unwrap();
+        assert!(storage.contains(&entry1).unwrap());
+        assert_eq!(2, storage.len().unwrap());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!storage.contains(&entry1).unwrap());
+        assert!(storage.get(&entry1).unwrap().is_none());
+        assert!(storage.get(&entry2).unwrap().is_some());
+        assert_eq!(1, storage.len().unwrap());
+
+        // expired entry was swept
+        assert_eq!(
+            std::mem::size_of::<EntryHash>() + size_of_vec(&value),
+            storage.total_get_mem_usage().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_put_clears_ttl() {
+        let entry1 = entry_hash(&[1]);
+        let value = blob_serialized(vec![1, 2, 3]);
+
+        let storage = InMemoryBackend::default();
+        storage
+            .put_with_ttl(&entry1, &value, Duration::from_millis(10))
+            .unwrap();
+        storage.put(&entry1, &value).unwrap();
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(storage.get(&entry1).unwrap().is_some());
+    }
+
+    fn wal_path(name: &str) -> PathBuf {
+        let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not defined - check build.rs");
+        let path = Path::new(out_dir.as_str()).join(name);
+        if path.exists() {
+            fs::remove_file(&path).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_wal_replay_after_crash() {
+        let path = wal_path("__in_memory_wal_replay");
+        let entry1 = entry_hash(&[1]);
+        let entry2 = entry_hash(&[2]);
+        let entry3 = entry_hash(&[3]);
+
+        {
+            let storage = InMemoryBackend::with_wal(&path).unwrap();
+            storage.put(&entry1, &vec![1, 2]).unwrap();
+            storage.merge(&entry1, &vec![3]).unwrap();
+            storage.put(&entry2, &vec![4]).unwrap();
+            storage.delete(&entry2).unwrap();
+            storage
+                .write_batch(vec![(entry2, vec![5]), (entry3, vec![6])])
+                .unwrap();
+            // replaced by the entry with ttl, which is not persisted
+            storage
+                .put_with_ttl(&entry3, &vec![7], Duration::from_secs(3600))
+                .unwrap();
+            // dropped without flush
+        }
+
+        // simulate crash in the middle of the append
+        {
+            let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(&[100, 0, 0, 0, 1, 2]).unwrap();
+        }
+
+        let storage = InMemoryBackend::with_wal(&path).unwrap();
+        assert_eq!(Some(vec![1, 2, 3]), storage.get(&entry1).unwrap());
+        assert_eq!(Some(vec![5]), storage.get(&entry2).unwrap());
+        assert!(!storage.contains(&entry3).unwrap());
+        assert_eq!(2, storage.len().unwrap());
+        assert!(storage.is_persistent());
+
+        // the incomplete record was cut off, so the new records are replayed as well
+        storage.put(&entry3, &vec![8]).unwrap();
+        drop(storage);
+        let storage = InMemoryBackend::with_wal(&path).unwrap();
+        assert_eq!(Some(vec![8]), storage.get(&entry3).unwrap());
+    }
+
+    #[test]
+    fn test_wal_compact() {
+        let path = wal_path("__in_memory_wal_compact");
+        let entry1 = entry_hash(&[1]);
+        let entry2 = entry_hash(&[2]);
+
+        let storage = InMemoryBackend::with_wal(&path).unwrap();
+        for i in 0..100u8 {
+            storage.put(&entry1, &vec![i]).unwrap();
+        }
+        storage.put(&entry2, &vec![1]).unwrap();
+        storage.delete(&entry2).unwrap();
+        let size_before = storage.disk_size().unwrap().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len() as usize, size_before);
+
+        storage.compact().unwrap();
+        assert!(storage.disk_size().unwrap().unwrap() < size_before);
+        assert!(InMemoryBackend::new().disk_size().unwrap().is_none());
+
+        // log is appended after the compaction
+        storage.merge(&entry1, &vec![100]).unwrap();
+        drop(storage);
+
+        let storage = InMemoryBackend::with_wal(&path).unwrap();
+        assert_eq!(Some(vec![99, 100]), storage.get(&entry1).unwrap());
+        assert!(storage.get(&entry2).unwrap().is_none());
+        assert_eq!(1, storage.len().unwrap());
+    }
 }
@@ -0,0 +1,212 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use crate::context::gc::NotGarbageCollected;
+use crate::context::merkle::hash::EntryHash;
+use crate::context::{ContextKeyValueStore, ContextKeyValueStoreSchema, ContextValue};
+use crate::persistent::database::DBError;
+use crate::persistent::{Flushable, KeyValueStoreBackend, MultiInstanceable, Persistable};
+
+/// Read-through chain of kv-stores for tiered storage, ordered from the fastest (top) layer
+/// to the slowest (e.g. archival) one.
+///
+/// Reads consult layers in order until the first hit, values found in lower layers
+/// can be promoted (copied) into the top layer. Lower layers are read-only, writes, deletes
+/// and garbage collection (retain) are applied to the top layer only, so value deleted
+/// from the top layer is still resolved from a lower layer, which holds it.
+pub struct LayeredBackend {
+    layers: Vec<Box<ContextKeyValueStore>>,
+    promote_on_read: bool,
+}
+
+impl LayeredBackend {
+    /// Creates chain with `top` layer followed by `lower` layers (in order of consulting)
+    pub fn new(
+        top: Box<ContextKeyValueStore>,
+        lower: Vec<Box<ContextKeyValueStore>>,
+        promote_on_read: bool,
+    ) -> Self {
+        let mut layers = Vec::with_capacity(lower.len() + 1);
+        layers.push(top);
+        layers.extend(lower);
+        Self {
+            layers,
+            promote_on_read,
+        }
+    }
+
+    fn top(&self) -> &ContextKeyValueStore {
+        // there is always at least top layer, see [LayeredBackend::new]
+        self.layers[0].as_ref()
+    }
+}
+
+impl NotGarbageCollected for LayeredBackend {}
+
+impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for LayeredBackend {
+    fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        self.top().put(key, value)
+    }
+
+    fn delete(&self, key: &EntryHash) -> Result<(), DBError> {
+        self.top().delete(key)
+    }
+
+    fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        // value from lower layer has to be copied to the top layer first, to be appended to
+        if !self.top().contains(key)? {
+            for layer in self.layers.iter().skip(1) {
+                if let Some(prev) = layer.get(key)? {
+                    self.top().put(key, &prev)?;
+                    break;
+                }
+            }
+        }
+        self.top().merge(key, value)
+    }
+
+    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, DBError> {
+        for (index, layer) in self.layers.iter().enumerate() {
+            if let Some(value) = layer.get(key)? {
+                if index > 0 && self.promote_on_read {
+                    self.top().put(key, &value)?;
+                }
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    fn contains(&self, key: &EntryHash) -> Result<bool, DBError> {
+        for layer in self.layers.iter() {
+            if layer.contains(key)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
+        self.top().retain(predicate)
+    }
+
+    fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
+        self.top().write_batch(batch)
+    }
+
+    fn total_get_mem_usage(&self) -> Result<usize, DBError> {
+        let mut usage = 0;
+        for layer in self.layers.iter() {
+            usage += layer.total_get_mem_usage()?;
+        }
+        Ok(usage)
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        // in-memory layers do not count, `None` just if no layer is on disk
+        let mut size = None;
+        for layer in self.layers.iter() {
+            if let Some(layer_size) = layer.disk_size()? {
+                size = Some(size.unwrap_or(0) + layer_size);
+            }
+        }
+        Ok(size)
+    }
+}
+
+impl Flushable for LayeredBackend {
+    fn flush(&self) -> Result<(), failure::Error> {
+        for layer in self.layers.iter() {
+            layer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Persistable for LayeredBackend {
+    fn is_persistent(&self) -> bool {
+        self.layers.iter().any(|layer| layer.is_persistent())
+    }
+}
+
+impl MultiInstanceable for LayeredBackend {
+    fn supports_multiple_opened_instances(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::kv_store::in_memory_backend::InMemoryBackend;
+    use crate::context::kv_store::test_support::{blob_serialized, entry_hash};
+
+    use super::*;
+
+    /// Two in-memory layers, `layers[0]` is the top one, `layers[1]` the archive
+    fn layered(promote_on_read: bool) -> LayeredBackend {
+        LayeredBackend::new(
+            Box::new(InMemoryBackend::new()),
+            vec![Box::new(InMemoryBackend::new()) as Box<ContextKeyValueStore>],
+            promote_on_read,
+        )
+    }
+
+    #[test]
+    fn test_get_falls_through_to_lower_layer() {
+        let storage = layered(false);
+        let key = entry_hash(&[1]);
+        let value = blob_serialized(vec![11]);
+        storage.layers[1].put(&key, &value).unwrap();
+
+        assert!(storage.contains(&key).unwrap());
+        assert_eq!(Some(value), storage.get(&key).unwrap());
+        assert!(storage.get(&entry_hash(&[2])).unwrap().is_none());
+
+        // not promoted
+        assert!(!storage.layers[0].contains(&key).unwrap());
+    }
+
+    #[test]
+    fn test_get_promotes_to_top_layer() {
+        let storage = layered(true);
+        let key = entry_hash(&[1]);
+        let value = blob_serialized(vec![11]);
+        storage.layers[1].put(&key, &value).unwrap();
+
+        assert_eq!(Some(value.clone()), storage.get(&key).unwrap());
+        assert_eq!(Some(value), storage.layers[0].get(&key).unwrap());
+    }
+
+    #[test]
+    fn test_writes_go_to_top_layer() {
+        let storage = layered(false);
+        let key = entry_hash(&[1]);
+        storage.layers[1].put(&key, &vec![1, 2]).unwrap();
+
+        storage.merge(&key, &vec![3]).unwrap();
+        assert_eq!(Some(vec![1, 2, 3]), storage.layers[0].get(&key).unwrap());
+        assert_eq!(Some(vec![1, 2]), storage.layers[1].get(&key).unwrap());
+        assert_eq!(
+            storage.layers[0].total_get_mem_usage().unwrap()
+                + storage.layers[1].total_get_mem_usage().unwrap(),
+            storage.total_get_mem_usage().unwrap()
+        );
+
+        storage.delete(&key).unwrap();
+        assert!(!storage.layers[0].contains(&key).unwrap());
+        // lower layer is read-only
+        assert_eq!(Some(vec![1, 2]), storage.get(&key).unwrap());
+    }
+
+    #[test]
+    fn test_retain_keeps_lower_layers() {
+        let storage = layered(false);
+        let key = entry_hash(&[1]);
+        storage.layers[0].put(&key, &vec![1]).unwrap();
+        storage.layers[1].put(&key, &vec![1]).unwrap();
+
+        storage.retain(&|_| false).unwrap();
+        assert!(!storage.layers[0].contains(&key).unwrap());
+        assert!(storage.layers[1].contains(&key).unwrap());
+    }
+}
@@ -10,10 +10,16 @@ use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 pub mod btree_map;
+pub mod caching_backend;
 pub mod in_memory_backend;
+pub mod layered_backend;
 pub mod rocksdb_backend;
 pub mod sled_backend;
 pub mod stats;
+pub mod zstd_backend;
+
+#[cfg(test)]
+mod backend_tests;
 
 pub const ROCKSDB: &str = "rocksdb";
 
@@ -65,6 +71,8 @@ impl FromStr for SupportedContextKeyValueStore {
     }
 }
 
+/// Fixtures for the kv-store tests
+#[cfg(test)]
 pub mod test_support {
     use std::collections::HashMap;
     use std::convert::TryFrom;
@@ -170,6 +178,16 @@ pub mod test_support {
                 Err(failure::format_err!("not supported"))
             }
         }
+
+        /// Path of the storage with given name, `None` for non-persistent storages
+        fn path(&self, _: &str) -> Option<PathBuf> {
+            None
+        }
+
+        /// Just opens an existing storage (e.g. created by checkpoint), does not clean anything
+        fn open(&self, _: &str) -> Result<Box<ContextKeyValueStore>, TestKeyValueStoreError> {
+            Err(failure::format_err!("not supported"))
+        }
     }
 
     /// In-memory kv-store
@@ -249,7 +267,16 @@ pub mod test_support {
             db.clear()?;
             db.flush()?;
 
-            Ok(Box::new(SledBackend::new(db)))
+            Ok(Box::new(SledBackend::new(db)?))
+        }
+
+        fn path(&self, name: &str) -> Option<PathBuf> {
+            Some(self.db_path(name))
+        }
+
+        fn open(&self, name: &str) -> Result<Box<ContextKeyValueStore>, TestKeyValueStoreError> {
+            use crate::context::kv_store::sled_backend::SledBackend;
+            Ok(Box::new(SledBackend::new(self.db(name, false)?)?))
         }
     }
 
@@ -345,6 +372,16 @@ pub mod test_support {
             let db = self.db_readonly(name)?;
             Ok(Box::new(RocksDBBackend::new(Arc::new(db))))
         }
+
+        fn path(&self, name: &str) -> Option<PathBuf> {
+            Some(self.db_path(name))
+        }
+
+        fn open(&self, name: &str) -> Result<Box<ContextKeyValueStore>, TestKeyValueStoreError> {
+            use crate::context::kv_store::rocksdb_backend::RocksDBBackend;
+            let db = self.db(name, false)?;
+            Ok(Box::new(RocksDBBackend::new(Arc::new(db))))
+        }
     }
 
     impl MultiInstanceable for RocksDbBackendTestContextKvStoreFactory {
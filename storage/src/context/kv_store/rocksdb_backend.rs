@@ -1,22 +1,29 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::collections::HashSet;
+use std::mem;
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::Arc;
 
-use rocksdb::{Cache, ColumnFamilyDescriptor, DB};
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{Cache, ColumnFamilyDescriptor, MergeOperands, DB};
 use serde::{Deserialize, Serialize};
 
 use crate::context::gc::NotGarbageCollected;
+use crate::context::kv_store::stats::StorageBackendStats;
 use crate::context::merkle::hash::EntryHash;
 use crate::context::{
     ContextKeyValueStoreSchema, ContextKeyValueStoreSchemaKeyType, ContextValue,
     MerkleKeyValueStoreSchemaValueType,
 };
-use crate::persistent::database::{default_table_options, DBError, RocksDbKeyValueSchema};
+use crate::persistent::database::{
+    default_table_options, DBError, IteratorMode, RocksDbKeyValueSchema,
+};
 use crate::persistent::{
-    BincodeEncoded, Flushable, KeyValueSchema, KeyValueStoreBackend, MultiInstanceable,
-    MultiInstanceableSyncError, Persistable,
+    BincodeEncoded, Flushable, KeyValueSchema, KeyValueStoreBackend, KeyValueStoreBackendIterator,
+    KeyValueStoreWithSchemaIterator, MultiInstanceable, MultiInstanceableSyncError, Persistable,
 };
 
 impl BincodeEncoded for EntryHash {}
@@ -28,7 +35,12 @@ impl KeyValueSchema for RocksDBBackend {
 
 impl RocksDbKeyValueSchema for RocksDBBackend {
     fn descriptor(cache: &Cache) -> ColumnFamilyDescriptor {
-        let cf_opts = default_table_options(cache);
+        let mut cf_opts = default_table_options(cache);
+        cf_opts.set_merge_operator(
+            "merkle_storage_merge_operator",
+            concatenate_merge,
+            Some(concatenate_merge),
+        );
         ColumnFamilyDescriptor::new(Self::name(), cf_opts)
     }
 
@@ -38,6 +50,19 @@ impl RocksDbKeyValueSchema for RocksDBBackend {
     }
 }
 
+/// Associative merge operator, which appends operands to the existing value
+fn concatenate_merge(
+    _new_key: &[u8],
+    existing_val: Option<&[u8]>,
+    operands: &mut MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut result = existing_val.map(|v| v.to_vec()).unwrap_or_default();
+    for op in operands {
+        result.extend_from_slice(op);
+    }
+    Some(result)
+}
+
 pub struct RocksDBBackend {
     inner: Arc<DB>,
 }
@@ -47,12 +72,66 @@ impl RocksDBBackend {
         RocksDBBackend { inner: db }
     }
 
+    /// Stats estimated from the RocksDB properties of the column family, `value_bytes` is the raw
+    /// (uncompressed) size of the values in the SST files and `compressed_value_bytes` its share of
+    /// the (compressed) data blocks, so [StorageBackendStats::compression_ratio] reflects the block
+    /// compression done by RocksDB.
+    ///
+    /// Note: just estimates, data not flushed from memtables yet are not included
+    pub fn get_stats(&self) -> Result<StorageBackendStats, DBError> {
+        let cf = self
+            .inner
+            .cf_handle(Self::name())
+            .ok_or(DBError::MissingColumnFamily { name: Self::name() })?;
+
+        let entries = self
+            .inner
+            .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?
+            .unwrap_or(0) as usize;
+        let table_properties = self
+            .inner
+            .property_value_cf(cf, "rocksdb.aggregated-table-properties")?
+            .unwrap_or_default();
+        let property = |name: &str| table_property(&table_properties, name).unwrap_or(0);
+
+        let raw_key_bytes = property("raw key size");
+        let raw_value_bytes = property("raw value size");
+        let data_block_bytes = property("data block size");
+        // data blocks are compressed as a whole, the values take their raw share of it
+        let compressed_value_bytes = match raw_key_bytes + raw_value_bytes {
+            0 => 0,
+            raw_bytes => {
+                (data_block_bytes as u128 * raw_value_bytes as u128 / raw_bytes as u128) as usize
+            }
+        };
+
+        Ok(StorageBackendStats {
+            key_bytes: entries * mem::size_of::<EntryHash>(),
+            value_bytes: raw_value_bytes,
+            compressed_value_bytes,
+            reused_keys_bytes: 0,
+            entries,
+        })
+    }
+
     // TODO TE-437 - get rid of deref call
     fn merkle_ref(&self) -> &dyn KeyValueStoreBackend<RocksDBBackend> {
         self.inner.deref() as &dyn KeyValueStoreBackend<RocksDBBackend>
     }
 }
 
+/// Parses the `name` value from the RocksDB table properties string (`name1=value1; name2=value2; ...`)
+fn table_property(properties: &str, name: &str) -> Option<usize> {
+    properties.split(';').find_map(|property| {
+        let mut name_value = property.splitn(2, '=');
+        if name_value.next()?.trim() == name {
+            name_value.next()?.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RocksDBBackendStats {
     mem_table_total: u64,
@@ -88,6 +167,10 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for RocksDBBackend {
         self.merkle_ref().retain(predicate)
     }
 
+    fn delete_batch(&self, keys: &HashSet<EntryHash>) -> Result<usize, DBError> {
+        self.merkle_ref().delete_batch(keys)
+    }
+
     fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
         self.merkle_ref().write_batch(batch)
     }
@@ -95,6 +178,29 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for RocksDBBackend {
     fn total_get_mem_usage(&self) -> Result<usize, DBError> {
         self.merkle_ref().total_get_mem_usage()
     }
+
+    fn len(&self) -> Result<usize, DBError> {
+        self.merkle_ref().len()
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        self.merkle_ref().disk_size()
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        Checkpoint::new(&self.inner)?.create_checkpoint(path)?;
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        let iter = KeyValueStoreWithSchemaIterator::<RocksDBBackend>::iterator(
+            self.inner.deref(),
+            IteratorMode::Start,
+        )?;
+        Ok(Box::new(
+            iter.map(|(k, v)| -> Result<_, DBError> { Ok((k?, v?)) }),
+        ))
+    }
 }
 
 impl Flushable for RocksDBBackend {
@@ -127,3 +233,23 @@ impl Persistable for RocksDBBackend {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_property() {
+        let properties = "# data blocks=3; # entries=100; raw key size=3200; \
+                          raw average key size=32.000000; raw value size=10000; \
+                          raw average value size=100.000000; data block size=4096; ";
+
+        assert_eq!(Some(3200), table_property(properties, "raw key size"));
+        assert_eq!(Some(10000), table_property(properties, "raw value size"));
+        assert_eq!(Some(4096), table_property(properties, "data block size"));
+        assert_eq!(Some(100), table_property(properties, "# entries"));
+        assert_eq!(None, table_property(properties, "raw average key size"));
+        assert_eq!(None, table_property(properties, "index block size"));
+        assert_eq!(None, table_property("", "raw key size"));
+    }
+}
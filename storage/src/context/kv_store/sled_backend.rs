@@ -1,33 +1,176 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::io::Read;
+use std::mem;
 use std::ops::Deref;
+use std::path::Path;
+use std::sync::Mutex;
 
 use bytes::Buf;
 use failure::Error;
+use sled::transaction::{
+    ConflictableTransactionError, ConflictableTransactionResult, TransactionError,
+    TransactionalTree,
+};
 
 use crate::context::gc::NotGarbageCollected;
+use crate::context::kv_store::stats::StorageBackendStats;
 use crate::context::merkle::hash::EntryHash;
 use crate::context::{ContextKeyValueStoreSchema, ContextValue};
 use crate::persistent::database::DBError;
-use crate::persistent::{Flushable, KeyValueStoreBackend, MultiInstanceable, Persistable};
+use crate::persistent::{
+    Flushable, KeyValueStoreBackend, KeyValueStoreBackendIterator, MultiInstanceable, Persistable,
+    SchemaError,
+};
+
+/// How many times is the transaction retried on conflict with concurrent writes, before giving up
+const MAX_TRANSACTION_RETRIES: usize = 16;
+
+/// Tree with the metadata of the backend, separated from the entries tree
+const METADATA_TREE: &str = "metadata";
+/// Key of the persisted [StorageBackendStats] in the [METADATA_TREE]
+const STATS_KEY: &[u8] = b"stats";
+
+/// Reason of the aborted transaction
+#[derive(Debug)]
+enum TransactionAbort {
+    /// transaction conflicted more than [MAX_TRANSACTION_RETRIES] times
+    TooManyConflicts,
+    /// transaction was aborted on purpose by the test, see [SledBackend::abort_batch_on]
+    #[cfg(test)]
+    Aborted,
+}
 
 pub struct SledBackend {
     db: sled::Db,
     inner: sled::Tree,
+    metadata: sled::Tree,
+    stats: Mutex<StorageBackendStats>,
+    /// aborts the batch transaction before the insert of the key, so the atomicity can be tested
+    #[cfg(test)]
+    abort_batch_on: Option<EntryHash>,
 }
 
 impl SledBackend {
-    pub fn new(db: sled::Db) -> Self {
+    /// Opens the backend over the `db`, the stats of the already stored entries are loaded
+    /// from the metadata, the whole tree is scanned just if they are not stored yet
+    pub fn new(db: sled::Db) -> Result<Self, DBError> {
         // TODO TE-437 - get rid of deref call
-        SledBackend {
-            inner: db.deref().clone(),
+        let inner = db.deref().clone();
+        inner.set_merge_operator(concatenate_merge);
+        let metadata = db.open_tree(METADATA_TREE)?;
+
+        let stats = match metadata.get(STATS_KEY)? {
+            Some(stored) => bincode::deserialize(&stored).map_err(|_| SchemaError::DecodeError)?,
+            None => {
+                let mut stats = StorageBackendStats::default();
+                for entry in inner.iter() {
+                    let (_, value) = entry?;
+                    stats += entry_stats(&value);
+                }
+                store_stats(&metadata, &stats)?;
+                stats
+            }
+        };
+
+        Ok(SledBackend {
+            inner,
             db,
-        }
+            metadata,
+            stats: Mutex::new(stats),
+            #[cfg(test)]
+            abort_batch_on: None,
+        })
+    }
+
+    /// Stats of the stored entries
+    pub fn get_stats(&self) -> Result<StorageBackendStats, DBError> {
+        Ok(*self.stats.lock()?)
+    }
+
+    /// Applies `update` to the stats and stores them to the metadata.
+    ///
+    /// Stats are stored after the write of the entries, sled recovers writes in order,
+    /// so just the stats of the writes in progress can be lost on crash.
+    fn update_stats<F>(&self, update: F) -> Result<(), DBError>
+    where
+        F: FnOnce(&mut StorageBackendStats),
+    {
+        let mut stats = self.stats.lock()?;
+        update(&mut stats);
+        store_stats(&self.metadata, &stats)
+    }
+
+    /// Runs `f` in single transaction, so all its writes are committed together or none of them.
+    ///
+    /// Sled retries the conflicting transaction (calls `f` again) by itself,
+    /// here the retries are just bounded by [MAX_TRANSACTION_RETRIES].
+    fn transaction<R, F>(&self, operation: &str, f: F) -> Result<R, DBError>
+    where
+        F: Fn(&TransactionalTree) -> ConflictableTransactionResult<R, TransactionAbort>,
+    {
+        let attempts = Cell::new(0);
+        self.inner
+            .transaction(|tx| {
+                attempts.set(attempts.get() + 1);
+                if attempts.get() > MAX_TRANSACTION_RETRIES + 1 {
+                    return Err(ConflictableTransactionError::Abort(
+                        TransactionAbort::TooManyConflicts,
+                    ));
+                }
+                f(tx)
+            })
+            .map_err(|e| match e {
+                TransactionError::Storage(error) => DBError::SledDBError { error },
+                TransactionError::Abort(TransactionAbort::TooManyConflicts) => DBError::LockError {
+                    reason: format!(
+                        "{} transaction conflicted more than {} times",
+                        operation, MAX_TRANSACTION_RETRIES
+                    ),
+                },
+                #[cfg(test)]
+                TransactionError::Abort(TransactionAbort::Aborted) => DBError::SledDBError {
+                    error: sled::Error::Unsupported(format!("{} transaction aborted", operation)),
+                },
+            })
     }
 }
 
+/// Stores the `stats` to the `metadata` tree under [STATS_KEY]
+fn store_stats(metadata: &sled::Tree, stats: &StorageBackendStats) -> Result<(), DBError> {
+    let encoded = bincode::serialize(stats).map_err(|_| SchemaError::EncodeError)?;
+    metadata.insert(STATS_KEY, encoded)?;
+    Ok(())
+}
+
+/// Stats of the entry with stored `value`, counted from the value length (not capacity),
+/// so the stats are the same for the written and for the loaded value
+fn entry_stats(value: &[u8]) -> StorageBackendStats {
+    let value_bytes = mem::size_of::<ContextValue>() + value.len();
+    StorageBackendStats {
+        key_bytes: mem::size_of::<EntryHash>(),
+        value_bytes,
+        compressed_value_bytes: value_bytes,
+        reused_keys_bytes: 0,
+        entries: 1,
+    }
+}
+
+/// Merge operator, which appends merged bytes to the existing value
+fn concatenate_merge(
+    _key: &[u8],
+    existing_val: Option<&[u8]>,
+    merged_bytes: &[u8],
+) -> Option<Vec<u8>> {
+    let mut result = existing_val.map(|v| v.to_vec()).unwrap_or_default();
+    result.extend_from_slice(merged_bytes);
+    Some(result)
+}
+
 impl NotGarbageCollected for SledBackend {}
 
 impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for SledBackend {
@@ -56,18 +199,38 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for SledBackend {
     }
 
     fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
-        self.inner.insert(&key.as_ref()[..], value.clone())?;
-        Ok(())
+        let prev = self.inner.insert(&key.as_ref()[..], value.clone())?;
+
+        self.update_stats(|stats| {
+            *stats += entry_stats(value);
+            if let Some(prev) = prev {
+                *stats -= entry_stats(&prev);
+            }
+        })
     }
 
     fn delete(&self, key: &EntryHash) -> Result<(), DBError> {
-        self.inner.remove(&key.as_ref()[..])?;
-        Ok(())
+        match self.inner.remove(&key.as_ref()[..])? {
+            Some(prev) => self.update_stats(|stats| *stats -= entry_stats(&prev)),
+            None => Ok(()),
+        }
     }
 
     fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
-        self.inner.insert(&key.as_ref()[..], value.clone())?;
-        Ok(())
+        // merge operator does not return the previous value, so stats of concurrent merges
+        // of the same key may be off
+        let prev = self.inner.get(&key.as_ref()[..])?;
+        self.inner.merge(&key.as_ref()[..], value.clone())?;
+
+        self.update_stats(|stats| {
+            let mut merged = entry_stats(value);
+            if let Some(prev) = prev {
+                *stats -= entry_stats(&prev);
+                merged.value_bytes += prev.len();
+                merged.compressed_value_bytes += prev.len();
+            }
+            *stats += merged;
+        })
     }
 
     fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, DBError> {
@@ -78,11 +241,50 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for SledBackend {
         Ok(self.inner.contains_key(&key.as_ref()[..])?)
     }
 
+    fn delete_batch(&self, keys: &HashSet<EntryHash>) -> Result<usize, DBError> {
+        let (deleted, removed) = self.transaction("delete_batch", |tx| {
+            let mut deleted = 0;
+            let mut removed = StorageBackendStats::default();
+            for k in keys.iter() {
+                if let Some(prev) = tx.remove(&k.as_ref()[..])? {
+                    removed += entry_stats(&prev);
+                    deleted += 1;
+                }
+            }
+            Ok((deleted, removed))
+        })?;
+
+        self.update_stats(|stats| *stats -= removed)?;
+        Ok(deleted)
+    }
+
     fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
-        for (k, v) in batch {
-            self.merge(&k, &v)?;
-        }
-        Ok(())
+        // the whole batch is committed at once, so the commit is atomic and faster than inserts one by one
+        let (added, removed) = self.transaction("write_batch", |tx| {
+            let mut added = StorageBackendStats::default();
+            let mut removed = StorageBackendStats::default();
+            for (k, v) in batch.iter() {
+                #[cfg(test)]
+                {
+                    if self.abort_batch_on.as_ref() == Some(k) {
+                        return Err(ConflictableTransactionError::Abort(
+                            TransactionAbort::Aborted,
+                        ));
+                    }
+                }
+                if let Some(prev) = tx.insert(&k[..], &v[..])? {
+                    removed += entry_stats(&prev);
+                }
+                added += entry_stats(v);
+            }
+            Ok((added, removed))
+        })?;
+
+        // stats are updated from the whole batch just once it is committed
+        self.update_stats(|stats| {
+            *stats += added;
+            *stats -= removed;
+        })
     }
 
     fn total_get_mem_usage(&self) -> Result<usize, DBError> {
@@ -91,6 +293,30 @@ impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for SledBackend {
             .map(|size| size as usize)
             .map_err(|e| DBError::SledDBError { error: e })
     }
+
+    fn len(&self) -> Result<usize, DBError> {
+        Ok(self.inner.len())
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        Ok(Some(self.db.size_on_disk()? as usize))
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        self.db.flush()?;
+        let checkpoint = sled::Config::new().path(path).create_new(true).open()?;
+        checkpoint.import(self.db.export());
+        checkpoint.flush()?;
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        Ok(Box::new(self.inner.iter().map(|i| -> Result<_, DBError> {
+            let (k, v) = i?;
+            let key = EntryHash::try_from(k.as_ref()).map_err(|_| SchemaError::DecodeError)?;
+            Ok((key, v.to_vec()))
+        })))
+    }
 }
 
 impl Flushable for SledBackend {
@@ -116,3 +342,93 @@ impl Persistable for SledBackend {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::context::kv_store::test_support::entry_hash;
+
+    use super::*;
+
+    fn temporary_storage() -> SledBackend {
+        SledBackend::new(sled::Config::new().temporary(true).open().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_write_batch_is_atomic() {
+        let mut storage = temporary_storage();
+        let entry1 = entry_hash(&[1]);
+        let entry2 = entry_hash(&[2]);
+        let entry3 = entry_hash(&[3]);
+
+        storage
+            .write_batch(vec![(entry1, vec![1]), (entry2, vec![2])])
+            .unwrap();
+        assert_eq!(Some(vec![1]), storage.get(&entry1).unwrap());
+        assert_eq!(Some(vec![2]), storage.get(&entry2).unwrap());
+        let stats = storage.get_stats().unwrap();
+        assert_eq!(2, stats.entries);
+
+        // batch failing in the middle leaves no partial writes
+        storage.abort_batch_on = Some(entry2);
+        let result = storage.write_batch(vec![
+            (entry1, vec![4]),
+            (entry3, vec![3]),
+            (entry2, vec![5]),
+        ]);
+        assert!(result.is_err());
+        assert!(!storage.contains(&entry3).unwrap());
+        assert_eq!(Some(vec![1]), storage.get(&entry1).unwrap());
+        assert_eq!(Some(vec![2]), storage.get(&entry2).unwrap());
+        assert_eq!(2, storage.len().unwrap());
+        assert_eq!(stats.value_bytes, storage.get_stats().unwrap().value_bytes);
+        assert_eq!(2, storage.get_stats().unwrap().entries);
+    }
+
+    #[test]
+    fn test_stats_follow_writes() {
+        let storage = temporary_storage();
+        let entry1 = entry_hash(&[1]);
+        let entry2 = entry_hash(&[2]);
+
+        storage
+            .write_batch(vec![
+                (entry1, vec![1]),
+                (entry2, vec![2]),
+                (entry1, vec![3]),
+            ])
+            .unwrap();
+        storage.merge(&entry2, &vec![4, 5]).unwrap();
+        assert_eq!(Some(vec![2, 4, 5]), storage.get(&entry2).unwrap());
+
+        let expected = entry_stats(&[3]) + entry_stats(&[2, 4, 5]);
+        let stats = storage.get_stats().unwrap();
+        assert_eq!(expected.entries, stats.entries);
+        assert_eq!(expected.value_bytes, stats.value_bytes);
+
+        assert_eq!(
+            1,
+            storage
+                .delete_batch(&vec![entry1].into_iter().collect())
+                .unwrap()
+        );
+        assert_eq!(1, storage.get_stats().unwrap().entries);
+
+        // stats of the stored entries are loaded on open
+        assert!(storage.metadata.contains_key(STATS_KEY).unwrap());
+        let reopened = SledBackend::new(storage.db.clone()).unwrap();
+        assert_eq!(
+            storage.get_stats().unwrap().value_bytes,
+            reopened.get_stats().unwrap().value_bytes
+        );
+        assert_eq!(1, reopened.get_stats().unwrap().entries);
+
+        // stats are counted, if not stored yet
+        reopened.metadata.remove(STATS_KEY).unwrap();
+        let rescanned = SledBackend::new(storage.db.clone()).unwrap();
+        assert_eq!(
+            storage.get_stats().unwrap().value_bytes,
+            rescanned.get_stats().unwrap().value_bytes
+        );
+        assert_eq!(1, rescanned.get_stats().unwrap().entries);
+    }
+}
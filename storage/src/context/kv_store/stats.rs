@@ -4,16 +4,20 @@
 use std::collections::HashSet;
 use std::mem;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::context::merkle::hash::EntryHash;
 use crate::context::ContextValue;
 
-#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 pub struct StorageBackendStats {
     pub key_bytes: usize,
     pub value_bytes: usize,
+    /// Bytes occupied by values after compression, equal to `value_bytes` for backends,
+    /// which do not compress (see [StorageBackendStats::compression_ratio])
+    pub compressed_value_bytes: usize,
     pub reused_keys_bytes: usize,
+    pub entries: usize,
 }
 
 impl StorageBackendStats {
@@ -25,6 +29,20 @@ impl StorageBackendStats {
     pub fn total_as_bytes(&self) -> usize {
         self.key_bytes + self.value_bytes + self.reused_keys_bytes
     }
+
+    /// average size of value in bytes, `None` if there are no entries
+    pub fn avg_value_bytes(&self) -> Option<usize> {
+        self.value_bytes.checked_div(self.entries)
+    }
+
+    /// ratio of raw to compressed value bytes, `1.0` means no (or no effective) compression
+    pub fn compression_ratio(&self) -> f64 {
+        if self.compressed_value_bytes == 0 {
+            1.0
+        } else {
+            self.value_bytes as f64 / self.compressed_value_bytes as f64
+        }
+    }
 }
 
 impl<'a> std::ops::Add<&'a Self> for StorageBackendStats {
@@ -34,7 +52,9 @@ impl<'a> std::ops::Add<&'a Self> for StorageBackendStats {
         Self {
             key_bytes: self.key_bytes + other.key_bytes,
             value_bytes: self.value_bytes + other.value_bytes,
+            compressed_value_bytes: self.compressed_value_bytes + other.compressed_value_bytes,
             reused_keys_bytes: self.reused_keys_bytes + other.reused_keys_bytes,
+            entries: self.entries + other.entries,
         }
     }
 }
@@ -66,7 +86,9 @@ impl<'a> std::ops::Sub<&'a Self> for StorageBackendStats {
         Self {
             key_bytes: self.key_bytes - other.key_bytes,
             value_bytes: self.value_bytes - other.value_bytes,
+            compressed_value_bytes: self.compressed_value_bytes - other.compressed_value_bytes,
             reused_keys_bytes: self.reused_keys_bytes - other.reused_keys_bytes,
+            entries: self.entries - other.entries,
         }
     }
 }
@@ -99,10 +121,13 @@ impl<'a> std::iter::Sum<&'a StorageBackendStats> for StorageBackendStats {
 
 impl From<(&EntryHash, &ContextValue)> for StorageBackendStats {
     fn from((_, value): (&EntryHash, &ContextValue)) -> Self {
+        let value_bytes = size_of_vec(&value);
         StorageBackendStats {
             key_bytes: mem::size_of::<EntryHash>(),
-            value_bytes: size_of_vec(&value),
+            value_bytes,
+            compressed_value_bytes: value_bytes,
             reused_keys_bytes: 0,
+            entries: 1,
         }
     }
 }
@@ -110,3 +135,51 @@ impl From<(&EntryHash, &ContextValue)> for StorageBackendStats {
 pub fn size_of_vec<T>(v: &Vec<T>) -> usize {
     mem::size_of::<Vec<T>>() + mem::size_of::<T>() * v.capacity()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_and_avg_value_bytes() {
+        let key = [0_u8; 32];
+        let value_1: ContextValue = vec![1; 10];
+        let value_2: ContextValue = vec![2; 30];
+
+        let empty = StorageBackendStats::default();
+        assert_eq!(None, empty.avg_value_bytes());
+
+        let stats_1 = StorageBackendStats::from((&key, &value_1));
+        let stats_2 = StorageBackendStats::from((&key, &value_2));
+        let total: StorageBackendStats = vec![stats_1, stats_2].iter().sum();
+        assert_eq!(2, total.entries);
+        assert_eq!(
+            Some((size_of_vec(&value_1) + size_of_vec(&value_2)) / 2),
+            total.avg_value_bytes()
+        );
+
+        let mut stats = total;
+        stats -= stats_2;
+        assert_eq!(1, stats.entries);
+        assert_eq!(Some(size_of_vec(&value_1)), stats.avg_value_bytes());
+
+        stats += stats_2;
+        assert_eq!(2, stats.entries);
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        let key = [0_u8; 32];
+        let value: ContextValue = vec![1; 10];
+
+        assert!((StorageBackendStats::default().compression_ratio() - 1.0).abs() < f64::EPSILON);
+
+        // uncompressed backends report the same size
+        let mut stats = StorageBackendStats::from((&key, &value));
+        assert_eq!(stats.value_bytes, stats.compressed_value_bytes);
+        assert!((stats.compression_ratio() - 1.0).abs() < f64::EPSILON);
+
+        stats.compressed_value_bytes = stats.value_bytes / 2;
+        assert!(stats.compression_ratio() > 1.9);
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::convert::TryInto;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::sync::Mutex;
+
+use zstd::block::{Compressor, Decompressor};
+
+use crate::context::gc::NotGarbageCollected;
+use crate::context::kv_store::stats::StorageBackendStats;
+use crate::context::merkle::hash::EntryHash;
+use crate::context::{ContextKeyValueStore, ContextKeyValueStoreSchema, ContextValue};
+use crate::persistent::database::DBError;
+use crate::persistent::{
+    Flushable, KeyValueStoreBackend, KeyValueStoreBackendIterator, MultiInstanceable, Persistable,
+};
+
+/// Max size in bytes of the trained dictionary
+pub const DICTIONARY_MAX_SIZE: usize = 16 * 1024;
+
+/// Stored value starts with the length of the uncompressed value (u32, little endian)
+const RAW_LEN_HEADER_SIZE: usize = mem::size_of::<u32>();
+
+/// Wrapper, which compresses the values of the inner kv-store with zstd.
+///
+/// Context values are small and share a lot of structure, so a dictionary trained on the stored
+/// values (see [ZstdBackend::train_dictionary]) compresses them far better than plain zstd.
+/// The values can be read just with the same dictionary they were written with,
+/// so the dictionary has to be kept together with the store.
+pub struct ZstdBackend {
+    inner: Box<ContextKeyValueStore>,
+    level: i32,
+    compressor: Mutex<Compressor>,
+    decompressor: Mutex<Decompressor>,
+}
+
+impl ZstdBackend {
+    /// Compresses values without dictionary
+    pub fn new(inner: Box<ContextKeyValueStore>, level: i32) -> Self {
+        Self::with_dictionary(inner, level, Vec::new())
+    }
+
+    /// Compresses values with the `dictionary` (e.g. trained by [ZstdBackend::train_dictionary]),
+    /// empty dictionary means no dictionary
+    pub fn with_dictionary(
+        inner: Box<ContextKeyValueStore>,
+        level: i32,
+        dictionary: Vec<u8>,
+    ) -> Self {
+        Self {
+            inner,
+            level,
+            compressor: Mutex::new(Compressor::with_dict(dictionary.clone())),
+            decompressor: Mutex::new(Decompressor::with_dict(dictionary)),
+        }
+    }
+
+    /// Trains dictionary (of max [DICTIONARY_MAX_SIZE] bytes) on up to `sample_count` stored values,
+    /// to be used to open the (new) store with [ZstdBackend::with_dictionary].
+    ///
+    /// Note: zstd needs enough samples to train the dictionary, otherwise the training fails
+    pub fn train_dictionary(&self, sample_count: usize) -> Result<Vec<u8>, DBError> {
+        let mut samples = Vec::with_capacity(sample_count);
+        for entry in self.iter()?.take(sample_count) {
+            let (_, value) = entry?;
+            samples.push(value);
+        }
+        Ok(zstd::dict::from_samples(&samples[..], DICTIONARY_MAX_SIZE)?)
+    }
+
+    /// Stats of the stored values, `value_bytes` are uncompressed bytes and
+    /// `compressed_value_bytes` are bytes actually stored (see [StorageBackendStats::compression_ratio])
+    pub fn get_stats(&self) -> Result<StorageBackendStats, DBError> {
+        let mut stats = StorageBackendStats::default();
+        for entry in self.inner.iter()? {
+            let (_, stored) = entry?;
+            stats.key_bytes += mem::size_of::<EntryHash>();
+            stats.value_bytes += raw_len(&stored)?;
+            stats.compressed_value_bytes += stored.len();
+            stats.entries += 1;
+        }
+        Ok(stats)
+    }
+
+    fn compress(&self, value: &[u8]) -> Result<ContextValue, DBError> {
+        let raw_len: u32 = value.len().try_into().map_err(|_| DBError::IOError {
+            error: io::Error::new(io::ErrorKind::InvalidInput, "value is too big to compress"),
+        })?;
+        let compressed = self.compressor.lock()?.compress(value, self.level)?;
+
+        let mut stored = Vec::with_capacity(RAW_LEN_HEADER_SIZE + compressed.len());
+        stored.extend_from_slice(&raw_len.to_le_bytes());
+        stored.extend_from_slice(&compressed);
+        Ok(stored)
+    }
+
+    fn decompress(&self, stored: &[u8]) -> Result<ContextValue, DBError> {
+        let raw_len = raw_len(stored)?;
+        Ok(self
+            .decompressor
+            .lock()?
+            .decompress(&stored[RAW_LEN_HEADER_SIZE..], raw_len)?)
+    }
+}
+
+fn raw_len(stored: &[u8]) -> Result<usize, DBError> {
+    match stored.get(..RAW_LEN_HEADER_SIZE) {
+        Some(header) => {
+            let mut raw_len = [0; RAW_LEN_HEADER_SIZE];
+            raw_len.copy_from_slice(header);
+            Ok(u32::from_le_bytes(raw_len) as usize)
+        }
+        None => Err(DBError::IOError {
+            error: io::Error::new(io::ErrorKind::InvalidData, "compressed value is truncated"),
+        }),
+    }
+}
+
+impl NotGarbageCollected for ZstdBackend {}
+
+impl KeyValueStoreBackend<ContextKeyValueStoreSchema> for ZstdBackend {
+    fn put(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        self.inner.put(key, &self.compress(value)?)
+    }
+
+    fn delete(&self, key: &EntryHash) -> Result<(), DBError> {
+        self.inner.delete(key)
+    }
+
+    fn merge(&self, key: &EntryHash, value: &ContextValue) -> Result<(), DBError> {
+        // compressed values cannot be appended to, so the merged value is compressed again
+        let merged = match self.get(key)? {
+            Some(mut prev) => {
+                prev.extend_from_slice(value);
+                prev
+            }
+            None => value.clone(),
+        };
+        self.put(key, &merged)
+    }
+
+    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, DBError> {
+        self.inner
+            .get(key)?
+            .map(|stored| self.decompress(&stored))
+            .transpose()
+    }
+
+    fn contains(&self, key: &EntryHash) -> Result<bool, DBError> {
+        self.inner.contains(key)
+    }
+
+    fn retain(&self, predicate: &dyn Fn(&EntryHash) -> bool) -> Result<(), DBError> {
+        self.inner.retain(predicate)
+    }
+
+    fn write_batch(&self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), DBError> {
+        let batch = batch
+            .into_iter()
+            .map(|(key, value)| Ok((key, self.compress(&value)?)))
+            .collect::<Result<Vec<_>, DBError>>()?;
+        self.inner.write_batch(batch)
+    }
+
+    fn total_get_mem_usage(&self) -> Result<usize, DBError> {
+        self.inner.total_get_mem_usage()
+    }
+
+    fn len(&self) -> Result<usize, DBError> {
+        self.inner.len()
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        self.inner.disk_size()
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), DBError> {
+        self.inner.checkpoint(path)
+    }
+
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<ContextKeyValueStoreSchema>, DBError> {
+        Ok(Box::new(self.inner.iter()?.map(move |entry| {
+            let (key, stored) = entry?;
+            Ok((key, self.decompress(&stored)?))
+        })))
+    }
+}
+
+impl Flushable for ZstdBackend {
+    fn flush(&self) -> Result<(), failure::Error> {
+        self.inner.flush()
+    }
+}
+
+impl Persistable for ZstdBackend {
+    fn is_persistent(&self) -> bool {
+        self.inner.is_persistent()
+    }
+}
+
+impl MultiInstanceable for ZstdBackend {
+    fn supports_multiple_opened_instances(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::kv_store::in_memory_backend::InMemoryBackend;
+    use crate::context::kv_store::test_support::entry_hash;
+
+    use super::*;
+
+    /// Small values sharing structure, similar to the context values
+    fn sample_value(index: u32) -> ContextValue {
+        format!(
+            "{{\"level\":{},\"predecessor\":\"BL{:x}\",\"protocol\":\"PsCARTHAGazKbHtnKfLzQg3kms52kSRpgnDY982a9oYsSXRLQEb\"}}",
+            index,
+            index.wrapping_mul(2_654_435_761)
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_put_get_merge() {
+        let storage = ZstdBackend::new(Box::new(InMemoryBackend::new()), 3);
+        let key = entry_hash(&[1]);
+
+        storage.put(&key, &sample_value(1)).unwrap();
+        assert_eq!(Some(sample_value(1)), storage.get(&key).unwrap());
+        assert!(storage.get(&entry_hash(&[2])).unwrap().is_none());
+
+        storage.merge(&key, &vec![1, 2, 3]).unwrap();
+        let mut expected = sample_value(1);
+        expected.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(Some(expected), storage.get(&key).unwrap());
+
+        // stored compressed
+        assert_ne!(storage.get(&key).unwrap(), storage.inner.get(&key).unwrap());
+
+        storage.delete(&key).unwrap();
+        assert!(!storage.contains(&key).unwrap());
+    }
+
+    #[test]
+    fn test_train_dictionary() {
+        let storage = ZstdBackend::new(Box::new(InMemoryBackend::new()), 3);
+        for index in 0..2000 {
+            storage
+                .put(&entry_hash(&index.to_be_bytes()), &sample_value(index))
+                .unwrap();
+        }
+        let plain_ratio = storage.get_stats().unwrap().compression_ratio();
+
+        let dictionary = storage.train_dictionary(2000).unwrap();
+        assert!(!dictionary.is_empty());
+        assert!(dictionary.len() <= DICTIONARY_MAX_SIZE);
+
+        let storage = ZstdBackend::with_dictionary(Box::new(InMemoryBackend::new()), 3, dictionary);
+        for index in 0..2000 {
+            storage
+                .put(&entry_hash(&index.to_be_bytes()), &sample_value(index))
+                .unwrap();
+        }
+        assert_eq!(
+            Some(sample_value(7)),
+            storage.get(&entry_hash(&7u32.to_be_bytes())).unwrap()
+        );
+
+        let stats = storage.get_stats().unwrap();
+        assert_eq!(stats.entries, 2000);
+        assert!(stats.compression_ratio() > plain_ratio);
+    }
+}
@@ -54,7 +54,7 @@ use serde::Serialize;
 
 use crypto::hash::{FromBytesError, HashType};
 
-use crate::context::gc::GarbageCollectionError;
+use crate::context::gc::{GarbageCollectionError, GcCycleStats};
 use crate::context::merkle::hash::EntryHash;
 use crate::context::merkle::hash::{hash_commit, hash_entry, hash_tree, HashingError};
 use crate::context::merkle::merkle_storage_stats::{
@@ -748,8 +748,8 @@ impl MerkleStorage {
         rv
     }
 
-    /// Notify GC about new cycle
-    pub fn start_new_cycle(&mut self) -> Result<(), MerkleError> {
+    /// Notify GC about new cycle, returns what was collected from the previous cycle
+    pub fn start_new_cycle(&mut self) -> Result<GcCycleStats, MerkleError> {
         Ok(self.db.new_cycle_started()?)
     }
 
@@ -894,6 +894,7 @@ impl MerkleStorage {
         Ok(MerkleStoragePerfReport {
             perf_stats: self.stats.perf_stats.clone(),
             kv_store_stats: self.db.total_get_mem_usage()?,
+            kv_store_disk_size: self.db.disk_size()?,
         })
     }
 
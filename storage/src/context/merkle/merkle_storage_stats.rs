@@ -46,6 +46,8 @@ pub type PerPathOperationStats = HashMap<String, OperationLatencyStats>;
 pub struct MerkleStoragePerfReport {
     pub perf_stats: MerklePerfStats,
     pub kv_store_stats: usize,
+    /// Approximate bytes of the kv-store on disk, `None` for in-memory kv-store
+    pub kv_store_disk_size: Option<usize>,
 }
 
 #[derive(Serialize, Default, Debug, Clone)]
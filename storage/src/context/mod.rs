@@ -16,7 +16,7 @@ pub use merkle::hash::EntryHash;
 pub use tezedge_context::TezedgeContext;
 use tezos_context::channel::ContextAction;
 
-use crate::context::gc::GarbageCollector;
+use crate::context::gc::{GarbageCollector, GcCycleStats};
 use crate::context::merkle::merkle_storage::MerkleError;
 use crate::context::merkle::merkle_storage_stats::MerkleStoragePerfReport;
 use crate::persistent::{
@@ -131,7 +131,8 @@ pub trait ContextApi {
 
     fn block_applied(&self) -> Result<(), ContextError>;
 
-    fn cycle_started(&self) -> Result<(), ContextError>;
+    /// Notifies garbage collector about new cycle, see [GarbageCollector::new_cycle_started]
+    fn cycle_started(&self) -> Result<GcCycleStats, ContextError>;
 
     fn get_memory_usage(&self) -> Result<usize, ContextError>;
 
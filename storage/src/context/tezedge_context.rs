@@ -11,6 +11,7 @@ use crypto::hash::{BlockHash, ContextHash, FromBytesError};
 
 use crate::context::actions::context_action_storage::ContextAction;
 use crate::context::actions::{get_new_tree_hash, get_tree_id};
+use crate::context::gc::GcCycleStats;
 use crate::context::merkle::hash::EntryHash;
 use crate::context::merkle::merkle_storage::{MerkleError, MerkleStorage};
 use crate::context::merkle::merkle_storage_stats::MerkleStoragePerfReport;
@@ -192,7 +193,7 @@ impl ContextApi for TezedgeContext {
         Ok(merkle.block_applied()?)
     }
 
-    fn cycle_started(&self) -> Result<(), ContextError> {
+    fn cycle_started(&self) -> Result<GcCycleStats, ContextError> {
         let mut merkle = self.merkle.lock()?;
         Ok(merkle.start_new_cycle()?)
     }
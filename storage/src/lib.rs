@@ -135,6 +135,11 @@ pub enum StorageError {
     HashError { error: FromBytesError },
     #[fail(display = "Error decoding hash: {}", error)]
     HashDecodeError { error: FromBase58CheckError },
+    #[fail(
+        display = "Chain id mismatch, expected: {}, found: {}",
+        expected, found
+    )]
+    ChainIdMismatch { expected: String, found: String },
 }
 
 impl From<DBError> for StorageError {
@@ -413,13 +418,21 @@ pub mod initializer {
     use std::sync::Arc;
 
     use rocksdb::{Cache, ColumnFamilyDescriptor, DB};
-    use slog::{error, Logger};
+    use slog::{error, warn, Logger};
 
     use crypto::hash::ChainId;
 
+    use crate::context::gc::mark_move_gced::MarkMoveGCed;
+    use crate::context::gc::mark_sweep_gced::MarkSweepGCed;
+    use crate::context::gc::{GcStrategy, NotGarbageCollected, PRESERVED_CYCLES_COUNT};
     use crate::context::merkle::merkle_storage::MerkleStorage;
-    use crate::persistent::database::{open_kv, RocksDbKeyValueSchema};
-    use crate::persistent::{DBError, DbConfiguration};
+    use crate::context::{
+        ContextKeyValueStore, ContextKeyValueStoreSchema, ContextValue, EntryHash,
+    };
+    use crate::persistent::database::{list_column_families, open_kv, RocksDbKeyValueSchema};
+    use crate::persistent::{
+        DBError, DbConfiguration, Flushable, KeyValueStoreBackend, MultiInstanceable, Persistable,
+    };
     use crate::{StorageError, SystemStorage};
 
     // IMPORTANT: Cache object must live at least as long as DB (returned by open_kv)
@@ -490,6 +503,8 @@ pub mod initializer {
         pub db_path: PathBuf,
         pub columns: C,
         pub threads: Option<usize>,
+        /// see [DbConfiguration::create_missing_column_families]
+        pub create_missing_column_families: bool,
     }
 
     #[derive(Debug, Clone)]
@@ -506,15 +521,31 @@ pub mod initializer {
         config: &RocksDbConfig<Factory>,
         expected_main_chain: &MainChain,
     ) -> Result<Arc<DB>, DBError> {
+        let existing_column_families = list_column_families(&config.db_path);
         let db = open_kv(
             &config.db_path,
             config.columns.create(cache),
             &DbConfiguration {
                 max_threads: config.threads,
+                create_missing_column_families: config.create_missing_column_families,
             },
         )
         .map(Arc::new)?;
 
+        // report column families, which were not in the existing database and were created now
+        if let Some(existing_column_families) = existing_column_families {
+            let created_column_families = list_column_families(&config.db_path)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|cf| !existing_column_families.contains(cf))
+                .collect::<Vec<_>>();
+            if !created_column_families.is_empty() {
+                warn!(log, "Missing column families were created in the existing database";
+                           "column_families" => created_column_families.join(", "),
+                           "db_path" => config.db_path.as_path().display().to_string());
+            }
+        }
+
         match check_database_compatibility(
             db.clone(),
             config.expected_db_version,
@@ -604,6 +635,7 @@ pub mod initializer {
 
     pub fn initialize_merkle(
         context_kv_store: &ContextKvStoreConfiguration,
+        gc_strategy: &GcStrategy,
         expected_main_chain: &MainChain,
         log: &Logger,
         caches: &mut GlobalRocksDbCacheHolder,
@@ -616,25 +648,79 @@ pub mod initializer {
                     initialize_rocksdb(&log, &kv_context_cache, cfg, expected_main_chain)
                         .expect("Failed to create/initialize RocksDB database (db_context)");
                 caches.push(kv_context_cache);
-                Box::new(crate::context::kv_store::rocksdb_backend::RocksDBBackend::new(kv_context))
+                with_gc_strategy(
+                    crate::context::kv_store::rocksdb_backend::RocksDBBackend::new(kv_context),
+                    gc_strategy,
+                )?
             }
             ContextKvStoreConfiguration::Sled { path } => {
                 let sled = sled::Config::new()
                     .path(path)
                     .open()
                     .expect("Failed to create/initialize Sled database (db_context)");
-                Box::new(crate::context::kv_store::sled_backend::SledBackend::new(
-                    sled,
-                ))
-            }
-            ContextKvStoreConfiguration::InMem => {
-                Box::new(crate::context::kv_store::in_memory_backend::InMemoryBackend::new())
-            }
-            ContextKvStoreConfiguration::BTreeMap => {
-                Box::new(crate::context::kv_store::btree_map::BTreeMapBackend::new())
+                with_gc_strategy(
+                    crate::context::kv_store::sled_backend::SledBackend::new(sled)?,
+                    gc_strategy,
+                )?
             }
+            ContextKvStoreConfiguration::InMem => default_with_gc_strategy::<
+                crate::context::kv_store::in_memory_backend::InMemoryBackend,
+            >(gc_strategy),
+            ContextKvStoreConfiguration::BTreeMap => default_with_gc_strategy::<
+                crate::context::kv_store::btree_map::BTreeMapBackend<EntryHash, ContextValue>,
+            >(gc_strategy),
         }))
     }
+
+    /// Wraps already opened kv-store with garbage collection according to `gc_strategy`.
+    ///
+    /// [GcStrategy::MarkMove] needs to create new store for every cycle, so it is not supported here.
+    fn with_gc_strategy<T>(
+        store: T,
+        gc_strategy: &GcStrategy,
+    ) -> Result<Box<ContextKeyValueStore>, failure::Error>
+    where
+        T: 'static
+            + KeyValueStoreBackend<ContextKeyValueStoreSchema>
+            + NotGarbageCollected
+            + Flushable
+            + MultiInstanceable
+            + Persistable
+            + Send
+            + Sync,
+    {
+        match gc_strategy {
+            GcStrategy::None => Ok(Box::new(store)),
+            GcStrategy::MarkSweep => Ok(Box::new(MarkSweepGCed::with_store(
+                store,
+                PRESERVED_CYCLES_COUNT,
+            ))),
+            GcStrategy::MarkMove => Err(failure::format_err!(
+                "Garbage collection strategy {:?} is supported only for in-memory kv-stores",
+                gc_strategy
+            )),
+        }
+    }
+
+    /// Creates new (default) kv-store with garbage collection according to `gc_strategy`
+    fn default_with_gc_strategy<T>(gc_strategy: &GcStrategy) -> Box<ContextKeyValueStore>
+    where
+        T: 'static
+            + KeyValueStoreBackend<ContextKeyValueStoreSchema>
+            + NotGarbageCollected
+            + Flushable
+            + MultiInstanceable
+            + Persistable
+            + Send
+            + Sync
+            + Default,
+    {
+        match gc_strategy {
+            GcStrategy::None => Box::new(T::default()),
+            GcStrategy::MarkSweep => Box::new(MarkSweepGCed::<T>::new(PRESERVED_CYCLES_COUNT)),
+            GcStrategy::MarkMove => Box::new(MarkMoveGCed::<T>::new(PRESERVED_CYCLES_COUNT)),
+        }
+    }
 }
 
 #[derive(Clone)]
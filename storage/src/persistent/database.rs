@@ -4,6 +4,7 @@
 //! This module provides wrapper on RocksDB database.
 //! Everything related to RocksDB should be placed here.
 
+use std::collections::HashSet;
 use std::io;
 use std::marker::PhantomData;
 use std::path::Path;
@@ -31,7 +32,18 @@ where
     P: AsRef<Path>,
     I: IntoIterator<Item = ColumnFamilyDescriptor>,
 {
-    DB::open_cf_descriptors(&default_kv_options(cfg), path, cfs).map_err(DBError::from)
+    let mut db_opts = default_kv_options(cfg);
+    // new database is created with all the column families
+    if list_column_families(&path).is_none() {
+        db_opts.create_missing_column_families(true);
+    }
+    DB::open_cf_descriptors(&db_opts, path, cfs).map_err(DBError::from)
+}
+
+/// List column families of the existing RocksDB database at given path,
+/// returns `None`, if there is no database yet
+pub fn list_column_families<P: AsRef<Path>>(path: P) -> Option<Vec<String>> {
+    DB::list_cf(&Options::default(), path).ok()
 }
 
 /// Create default database configuration options,
@@ -39,7 +51,7 @@ where
 fn default_kv_options(cfg: &DbConfiguration) -> Options {
     // default db options
     let mut db_opts = Options::default();
-    db_opts.create_missing_column_families(true);
+    db_opts.create_missing_column_families(cfg.create_missing_column_families);
     db_opts.create_if_missing(true);
 
     // https://github.com/facebook/rocksdb/wiki/Setup-Options-and-Basic-Tuning#other-general-options
@@ -123,6 +135,8 @@ pub enum DBError {
     IOError { error: io::Error },
     #[fail(display = "MemoryStatisticsOverflow")]
     MemoryStatisticsOverflow,
+    #[fail(display = "Operation {} is not supported by this backend", operation)]
+    NotSupported { operation: &'static str },
 }
 
 impl From<SchemaError> for DBError {
@@ -293,13 +307,13 @@ impl<S: RocksDbKeyValueSchema> KeyValueStoreBackend<S> for DB {
 
     fn write_batch(&self, batch: Vec<(S::Key, S::Value)>) -> Result<(), DBError> {
         let mut rocksb_batch = WriteBatch::default(); // batch containing DB key values to persist
+        let cf = self
+            .cf_handle(S::name())
+            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
 
         for (k, v) in batch.iter() {
             let key = k.encode()?;
             let value = v.encode()?;
-            let cf = self
-                .cf_handle(S::name())
-                .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
             rocksb_batch.put_cf(cf, &key, &value);
         }
 
@@ -307,6 +321,25 @@ impl<S: RocksDbKeyValueSchema> KeyValueStoreBackend<S> for DB {
         Ok(())
     }
 
+    fn delete_batch(&self, keys: &HashSet<S::Key>) -> Result<usize, DBError> {
+        let mut rocksb_batch = WriteBatch::default(); // batch containing DB keys to delete
+        let cf = self
+            .cf_handle(S::name())
+            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
+
+        let mut deleted = 0;
+        for k in keys.iter() {
+            let key = k.encode()?;
+            if self.get_pinned_cf(cf, &key)?.is_some() {
+                rocksb_batch.delete_cf(cf, &key);
+                deleted += 1;
+            }
+        }
+
+        self.write_opt(rocksb_batch, &default_write_options())?;
+        Ok(deleted)
+    }
+
     fn total_get_mem_usage(&self) -> Result<usize, DBError> {
         let memory_usage_stats = rocksdb::perf::get_memory_usage_stats(Some(&[&self]), None)?;
         let mut usage: usize = 0;
@@ -326,6 +359,24 @@ impl<S: RocksDbKeyValueSchema> KeyValueStoreBackend<S> for DB {
         Ok(usage)
     }
 
+    fn len(&self) -> Result<usize, DBError> {
+        let cf = self
+            .cf_handle(S::name())
+            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
+        Ok(self
+            .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?
+            .unwrap_or(0) as usize)
+    }
+
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        let cf = self
+            .cf_handle(S::name())
+            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
+        Ok(self
+            .property_int_value_cf(cf, "rocksdb.live-sst-files-size")?
+            .map(|size| size as usize))
+    }
+
     fn retain(&self, predicate: &dyn Fn(&S::Key) -> bool) -> Result<(), DBError> {
         let garbage: Vec<_> = (self as &dyn KeyValueStoreWithSchemaIterator<S>)
             .iterator(IteratorMode::Start)?
@@ -1,7 +1,9 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::collections::HashSet;
 use std::path::Path;
+use std::time::Duration;
 
 use derive_builder::Builder;
 
@@ -18,10 +20,14 @@ pub mod sequence;
 
 /// Rocksdb database system configuration
 /// - [max_num_of_threads] - if not set, num of cpus is used
+/// - [create_missing_column_families] - if set, column families missing in the existing database
+///   (e.g. added by newer version) are created on open, otherwise open fails
 #[derive(Builder, Debug, Clone)]
 pub struct DbConfiguration {
     #[builder(default = "None")]
     pub max_threads: Option<usize>,
+    #[builder(default = "true")]
+    pub create_missing_column_families: bool,
 }
 
 impl Default for DbConfiguration {
@@ -80,6 +86,12 @@ impl MultiInstanceableSyncError {
     }
 }
 
+/// Boxed iterator over all key value pairs of a kv-store
+pub type KeyValueStoreBackendIterator<'a, S> = Box<
+    dyn Iterator<Item = Result<(<S as KeyValueSchema>::Key, <S as KeyValueSchema>::Value), DBError>>
+        + 'a,
+>;
+
 /// Custom trait to unify any kv-store schema access
 pub trait KeyValueStoreBackend<S: KeyValueSchema> {
     /// Insert new key value pair into the database.
@@ -89,6 +101,24 @@ pub trait KeyValueStoreBackend<S: KeyValueSchema> {
     /// * `value` - Value to be inserted associated with given key, specified by schema
     fn put(&self, key: &S::Key, value: &S::Value) -> Result<(), DBError>;
 
+    /// Insert new key value pair into the database, which expires after `ttl`,
+    /// expired entries are treated as absent. Plain [KeyValueStoreBackend::put] means no expiry.
+    ///
+    /// # Arguments
+    /// * `key` - Value of key specified by schema
+    /// * `value` - Value to be inserted associated with given key, specified by schema
+    /// * `ttl` - Time to live of the entry
+    fn put_with_ttl(
+        &self,
+        _key: &S::Key,
+        _value: &S::Value,
+        _ttl: Duration,
+    ) -> Result<(), DBError> {
+        Err(DBError::NotSupported {
+            operation: "put_with_ttl",
+        })
+    }
+
     /// Delete existing value associated with given key from the database.
     ///
     /// # Arguments
@@ -107,11 +137,15 @@ pub trait KeyValueStoreBackend<S: KeyValueSchema> {
         Ok(v)
     }
 
-    /// Insert key value pair into the database, overriding existing value if exists.
+    /// Merge value with the existing value associated with given key.
+    ///
+    /// RocksDB schemas define merge by their own merge operator. For context kv-stores
+    /// ([ContextKeyValueStoreSchema](crate::context::ContextKeyValueStoreSchema)) merge appends
+    /// `value` bytes to the existing value, creating it if absent.
     ///
     /// # Arguments
     /// * `key` - Value of key specified by schema
-    /// * `value` - Value to be inserted associated with given key, specified by schema
+    /// * `value` - Value to be merged with value associated with given key, specified by schema
     fn merge(&self, key: &S::Key, value: &S::Value) -> Result<(), DBError>;
 
     /// Read value associated with given key, if exists.
@@ -132,6 +166,24 @@ pub trait KeyValueStoreBackend<S: KeyValueSchema> {
     /// * `predicate` - functor used for assessment
     fn retain(&self, predicate: &dyn Fn(&S::Key) -> bool) -> Result<(), DBError>;
 
+    /// Removes all given keys, returns count of keys which were actually present and deleted.
+    ///
+    /// Complement to [KeyValueStoreBackend::retain], when the set of garbage keys is already known
+    /// (e.g. by garbage collector), so there is no need to iterate the whole database.
+    ///
+    /// # Arguments
+    /// * `keys` - keys (specified by schema) to be deleted
+    fn delete_batch(&self, keys: &HashSet<S::Key>) -> Result<usize, DBError> {
+        let mut deleted = 0;
+        for key in keys {
+            if self.contains(key)? {
+                self.delete(key)?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
     /// Write batch into DB atomically
     ///
     /// # Arguments
@@ -141,4 +193,42 @@ pub trait KeyValueStoreBackend<S: KeyValueSchema> {
     /// Return memory usage statistics
     ///
     fn total_get_mem_usage(&self) -> Result<usize, DBError>;
+
+    /// Return count of entries stored in the database.
+    ///
+    /// Note: RocksDB based backends report just an estimate (see `rocksdb.estimate-num-keys`).
+    fn len(&self) -> Result<usize, DBError> {
+        Err(DBError::NotSupported { operation: "len" })
+    }
+
+    /// Check, if database contains no entries, see [KeyValueStoreBackend::len]
+    fn is_empty(&self) -> Result<bool, DBError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Return count of bytes the database occupies on disk, `None` for purely in-memory databases.
+    ///
+    /// Note: the figure may be approximate, e.g. RocksDB reports just the size of the live SST files
+    /// (see `rocksdb.live-sst-files-size`), without the WAL and the files awaiting deletion.
+    fn disk_size(&self) -> Result<Option<usize>, DBError> {
+        Ok(None)
+    }
+
+    /// Create consistent point-in-time copy of the database at given `path`,
+    /// which can be opened later as a new database.
+    ///
+    /// # Arguments
+    /// * `path` - Target directory for the copy, must not exist
+    fn checkpoint(&self, _path: &Path) -> Result<(), DBError> {
+        Err(DBError::NotSupported {
+            operation: "checkpoint",
+        })
+    }
+
+    /// Iterate over all key value pairs stored in the database (read-only).
+    ///
+    /// Backends, which cannot enumerate their entries, return [DBError::NotSupported].
+    fn iter(&self) -> Result<KeyValueStoreBackendIterator<S>, DBError> {
+        Err(DBError::NotSupported { operation: "iter" })
+    }
 }
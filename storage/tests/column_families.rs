@@ -0,0 +1,99 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+use rocksdb::{Cache, Options, DB};
+
+use storage::persistent::database::{list_column_families, open_kv, RocksDbKeyValueSchema};
+use storage::persistent::sequence::Sequences;
+use storage::persistent::{DbConfiguration, DbConfigurationBuilder};
+use storage::SystemStorage;
+
+#[test]
+fn test_open_kv_with_missing_column_family() -> Result<(), Error> {
+    let path = out_dir_path("__open_kv_missing_cf");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    let cache = Cache::new_lru_cache(32 * 1024 * 1024).unwrap();
+
+    // create db just with one column family
+    {
+        let _db = open_kv(
+            &path,
+            vec![Sequences::descriptor(&cache)],
+            &DbConfiguration::default(),
+        )?;
+    }
+    assert!(!list_column_families(&path)
+        .unwrap()
+        .contains(&SystemStorage::name().to_string()));
+
+    // reopen with the new column family, without creating it - fails
+    let cfg = DbConfigurationBuilder::default()
+        .create_missing_column_families(false)
+        .build()
+        .unwrap();
+    assert!(open_kv(
+        &path,
+        vec![
+            Sequences::descriptor(&cache),
+            SystemStorage::descriptor(&cache)
+        ],
+        &cfg,
+    )
+    .is_err());
+    assert!(!list_column_families(&path)
+        .unwrap()
+        .contains(&SystemStorage::name().to_string()));
+
+    // reopen with the new column family, creating it
+    {
+        let _db = open_kv(
+            &path,
+            vec![
+                Sequences::descriptor(&cache),
+                SystemStorage::descriptor(&cache),
+            ],
+            &DbConfiguration::default(),
+        )?;
+    }
+    let column_families = list_column_families(&path).unwrap();
+    assert!(column_families.contains(&Sequences::name().to_string()));
+    assert!(column_families.contains(&SystemStorage::name().to_string()));
+
+    assert!(DB::destroy(&Options::default(), &path).is_ok());
+    Ok(())
+}
+
+#[test]
+fn test_open_kv_new_db_without_creating_missing_column_families() -> Result<(), Error> {
+    let path = out_dir_path("__open_kv_new_db");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+    let cache = Cache::new_lru_cache(32 * 1024 * 1024).unwrap();
+
+    // new database is always created with all the column families
+    let cfg = DbConfigurationBuilder::default()
+        .create_missing_column_families(false)
+        .build()
+        .unwrap();
+    {
+        let _db = open_kv(&path, vec![Sequences::descriptor(&cache)], &cfg)?;
+    }
+    assert!(list_column_families(&path)
+        .unwrap()
+        .contains(&Sequences::name().to_string()));
+
+    assert!(DB::destroy(&Options::default(), &path).is_ok());
+    Ok(())
+}
+
+fn out_dir_path(dir_name: &str) -> PathBuf {
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not defined");
+    Path::new(out_dir.as_str()).join(Path::new(dir_name))
+}
@@ -240,6 +240,10 @@ fn init() -> HashMap<TezosEnvironment, TezosEnvironmentConfiguration> {
                         "PtEdoTezd3RHSC31mpxxo1npxFjoWWcFgQtxapi51Z8TLu6v6Uq".to_string(),
                         "PtEdo2ZkT9oKpimTah6x2embF25oss54njMuPzkJTEi5RqfdZFA".to_string(),
                     ),
+                    (
+                        "PsFLorBArSaXjuy9oP76Qv1v2FRYnUs7TFtteK5GkRBC24JvbdE".to_string(),
+                        "PsFLorenaUUuikDWvMDr6fGBRG8kt3e3D3fHoXK1j1BFRxeSH4i4".to_string(),
+                    ),
                 ],
             },
             enable_testchain: false,
@@ -931,4 +935,25 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn test_mainnet_configuration() -> Result<(), failure::Error> {
+        let tezos_env: &TezosEnvironmentConfiguration = TEZOS_ENV
+            .get(&TezosEnvironment::Mainnet)
+            .expect("no tezos environment configured for Mainnet");
+
+        assert_eq!(
+            tezos_env.main_chain_id()?,
+            ChainId::from_base58_check("NetXdQprcVkpaWU")?
+        );
+        assert_eq!(
+            tezos_env.genesis_protocol()?,
+            ProtocolHash::from_base58_check("Ps9mPmXaRzmzk35gbAYNCAw6UXdE2qoABTHbN2oEEc1qM7CwT9P")?
+        );
+        assert_eq!("TEZOS_MAINNET", tezos_env.version);
+        assert!(!tezos_env.enable_testchain);
+        assert!(tezos_env.patch_context_genesis_parameters.is_none());
+
+        Ok(())
+    }
 }
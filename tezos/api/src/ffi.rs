@@ -9,6 +9,7 @@ use derive_builder::Builder;
 use failure::Fail;
 use serde::{Deserialize, Serialize};
 
+use crypto::base58::FromBase58CheckError;
 use crypto::hash::{
     BlockHash, BlockMetadataHash, ChainId, ContextHash, FromBytesError, OperationHash,
     OperationMetadataHash, OperationMetadataListListHash, ProtocolHash,
@@ -323,6 +324,8 @@ pub struct InitProtocolContextResult {
     pub supported_protocol_hashes: Vec<ProtocolHash>,
     /// Presents only if was genesis commited to context
     pub genesis_commit_hash: Option<ContextHash>,
+    /// Protocol activated for genesis
+    pub protocol_hash: ProtocolHash,
 }
 
 impl fmt::Debug for InitProtocolContextResult {
@@ -338,8 +341,10 @@ impl fmt::Debug for InitProtocolContextResult {
             .collect::<Vec<String>>();
         write!(
             f,
-            "genesis_commit_hash: {}, supported_protocol_hashes: {:?}",
-            &genesis_commit_hash, &supported_protocol_hashes
+            "genesis_commit_hash: {}, protocol_hash: {}, supported_protocol_hashes: {:?}",
+            &genesis_commit_hash,
+            self.protocol_hash.to_base58_check(),
+            &supported_protocol_hashes
         )
     }
 }
@@ -429,6 +434,14 @@ impl From<FromBytesError> for TezosStorageInitError {
     }
 }
 
+impl From<FromBase58CheckError> for TezosStorageInitError {
+    fn from(error: FromBase58CheckError) -> Self {
+        TezosStorageInitError::InitializeError {
+            message: format!("Error constructing hash from base58check: {:?}", error),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Fail)]
 pub enum GetDataError {
     #[fail(display = "OCaml failed to get data, message: {}!", message)]
@@ -201,6 +201,11 @@ pub fn apply_block_operations_metadata(
     )
 }
 
+/// Starts the OCaml runtime, so the first protocol call does not pay for the runtime startup
+pub fn start_runtime() {
+    ffi::start()
+}
+
 /// Shutdown the OCaml runtime
 pub fn shutdown_runtime() {
     ffi::shutdown()
@@ -3,7 +3,7 @@
 
 use std::convert::TryFrom;
 
-use crypto::hash::ProtocolHash;
+use crypto::hash::{ChainId, ContextHash, ProtocolHash};
 use serial_test::serial;
 use tezos_api::environment::{self, TezosEnvironment};
 use tezos_api::ffi::{InitProtocolContextResult, TezosRuntimeConfiguration};
@@ -32,6 +32,7 @@ fn test_init_protocol_context() {
     let InitProtocolContextResult {
         genesis_commit_hash,
         supported_protocol_hashes,
+        ..
     } = prepare_protocol_context(storage_dir, &tezos_env, true);
 
     // check
@@ -47,6 +48,7 @@ fn test_init_protocol_context() {
     let InitProtocolContextResult {
         genesis_commit_hash,
         supported_protocol_hashes,
+        ..
     } = prepare_protocol_context(storage_dir, &tezos_env, false);
 
     // check
@@ -54,6 +56,46 @@ fn test_init_protocol_context() {
     assert!(genesis_commit_hash.is_none());
 }
 
+#[test]
+#[serial]
+fn test_init_protocol_context_for_mainnet() -> Result<(), failure::Error> {
+    // change cfg
+    ffi::change_runtime_configuration(TezosRuntimeConfiguration {
+        debug_mode: false,
+        compute_context_action_tree_hashes: false,
+        log_enabled: common::is_ocaml_log_enabled(),
+    })
+    .unwrap();
+
+    let storage_dir = "test_storage_mainnet_01";
+    let tezos_env = TezosEnvironment::Mainnet;
+
+    // init empty storage for test WITH commit genesis
+    let InitProtocolContextResult {
+        genesis_commit_hash,
+        supported_protocol_hashes,
+        ..
+    } = prepare_protocol_context(storage_dir, &tezos_env, true);
+
+    // check
+    assert!(!supported_protocol_hashes.is_empty());
+    assert_eq!(
+        genesis_commit_hash.expect("Expected genesis commit hash"),
+        ContextHash::from_base58_check("CoV8SQumiVU9saiu3FVNeDNewJaJH8yWdsGF3WLdsRr2P9S7MzCj")?,
+    );
+
+    // check chain_id
+    let cfg = environment::TEZOS_ENV
+        .get(&tezos_env)
+        .expect("no tezos environment configured");
+    assert_eq!(
+        cfg.main_chain_id()?,
+        ChainId::from_base58_check("NetXdQprcVkpaWU")?
+    );
+
+    Ok(())
+}
+
 #[test]
 #[serial]
 fn test_assert_encoding_for_protocol_data() {
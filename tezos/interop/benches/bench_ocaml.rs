@@ -8,7 +8,9 @@ use test::Bencher;
 
 use ocaml_interop::{ocaml, OCamlRuntime, ToOCaml};
 
-use tezos_interop::runtime::{self, OCamlCallResult};
+use tezos_interop::runtime::{self, OCamlBlockPanic, OCamlCallResult};
+
+const BATCH_SIZE: usize = 100;
 
 ocaml! {
     pub fn echo(value: String) -> String;
@@ -23,9 +25,37 @@ fn ocaml_fn_echo(arg: String) -> OCamlCallResult<String> {
 
 #[bench]
 fn bench_ocaml_echo(b: &mut Bencher) {
-    // Run one dummy task so ocaml runtime is started. We do not want to measure
-    // runtime startup time but only a time of a method call.
-    futures::executor::block_on(ocaml_fn_echo("__dummy__".into())).unwrap();
+    // We do not want to measure runtime startup time but only a time of a method call.
+    runtime::ensure_started();
 
     b.iter(|| futures::executor::block_on(ocaml_fn_echo("Hello world!".into())));
 }
+
+#[bench]
+fn bench_ocaml_echo_individual_calls(b: &mut Bencher) {
+    runtime::ensure_started();
+
+    b.iter(|| {
+        for _ in 0..BATCH_SIZE {
+            futures::executor::block_on(ocaml_fn_echo("Hello world!".into())).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn bench_ocaml_echo_batched_call(b: &mut Bencher) {
+    runtime::ensure_started();
+
+    b.iter(|| {
+        let items = vec![String::from("Hello world!"); BATCH_SIZE];
+        futures::executor::block_on(runtime::spawn_batch(
+            items,
+            |rt: &mut OCamlRuntime, arg: String| -> Result<String, OCamlBlockPanic> {
+                let value = arg.to_boxroot(rt);
+                Ok(echo(rt, &value).to_rust(rt))
+            },
+        ))
+        .unwrap()
+        .unwrap()
+    });
+}
@@ -0,0 +1,136 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![allow(unsafe_code)]
+
+//! Calls of the OCaml functions registered with `Callback.register`.
+//!
+//! `ocaml-interop` turns an exception raised by the called OCaml function into a panic with the formatted message only,
+//! so the exception constructor is lost. Functions declared with [ocaml_exn] panic with [OCamlException] payload instead,
+//! which is read from the raised exception value, before it can be collected by the OCaml GC.
+
+use std::ffi::CString;
+use std::slice;
+
+use ocaml_interop::{BoxRoot, OCaml, OCamlRuntime, RawOCaml};
+use ocaml_sys::{
+    caml_callbackN_exn, caml_named_value, caml_string_length, extract_exception, field, is_block,
+    is_exception_result, string_val, tag_val, CLOSURE, OBJECT, STRING,
+};
+
+use crate::runtime::OCamlException;
+
+/// Tag of the exception, which constructor cannot be read
+const UNKNOWN_EXCEPTION_TAG: &str = "<unknown>";
+
+/// Declares rust functions calling the OCaml functions registered with `Callback.register` under the same name.
+///
+/// Declaration is the same as for `ocaml_interop::ocaml!`, but the exception raised by the OCaml function
+/// is propagated as a panic with [OCamlException] payload, see [OCamlBlockPanic::exception](crate::runtime::OCamlBlockPanic::exception).
+#[macro_export]
+macro_rules! ocaml_exn {
+    () => ();
+
+    (@return) => { () };
+    (@return $rtyp:ty) => { $rtyp };
+
+    ($vis:vis fn $name:ident(
+        $($arg:ident: $typ:ty),+ $(,)?
+    ) $(-> $rtyp:ty)?; $($t:tt)*) => {
+        #[allow(unsafe_code)]
+        $vis fn $name<'a>(
+            cr: &'a mut ::ocaml_interop::OCamlRuntime,
+            $($arg: ::ocaml_interop::OCamlRef<$typ>),+
+        ) -> ::ocaml_interop::BoxRoot<$crate::ocaml_exn!(@return $($rtyp)?)> {
+            static mut CLOSURE: Option<$crate::closure::OCamlNamedClosure> = None;
+            static INIT: ::std::sync::Once = ::std::sync::Once::new();
+            let closure = unsafe {
+                INIT.call_once(|| {
+                    CLOSURE = Some($crate::closure::OCamlNamedClosure::named(stringify!($name)));
+                });
+                CLOSURE.unwrap()
+            };
+            closure.call_n(cr, &mut [$(unsafe { $arg.get_raw() }),+])
+        }
+
+        $crate::ocaml_exn!($($t)*);
+    };
+}
+
+/// OCaml function registered with `Callback.register`
+#[derive(Copy, Clone)]
+pub struct OCamlNamedClosure(*const RawOCaml);
+
+// registered values are global roots, which are never removed
+unsafe impl Send for OCamlNamedClosure {}
+unsafe impl Sync for OCamlNamedClosure {}
+
+impl OCamlNamedClosure {
+    /// Resolves the OCaml function registered under the `name`
+    ///
+    /// # Panics
+    ///
+    /// If there is no function registered under the `name`
+    pub fn named(name: &str) -> Self {
+        let closure = match CString::new(name) {
+            Ok(c_name) => unsafe { caml_named_value(c_name.as_ptr()) },
+            Err(_) => std::ptr::null(),
+        };
+        if closure.is_null() || unsafe { tag_val(*closure) } != CLOSURE {
+            panic!("OCaml closure with name '{}' not registered", name)
+        }
+        Self(closure)
+    }
+
+    /// Calls the OCaml function, if it raises an exception, it unwinds with [OCamlException] payload
+    pub fn call_n<R>(&self, cr: &mut OCamlRuntime, args: &mut [RawOCaml]) -> BoxRoot<R> {
+        let result = unsafe { caml_callbackN_exn(*self.0, args.len(), args.as_mut_ptr()) };
+        if is_exception_result(result) {
+            let exception = unsafe { read_exception(extract_exception(result)) };
+            // payload is caught at the OCaml block boundary, see [crate::runtime::spawn]
+            std::panic::resume_unwind(Box::new(exception))
+        }
+        BoxRoot::new(unsafe { OCaml::new(cr, result) })
+    }
+}
+
+/// Reads the constructor name (tag) and the message of the raised exception.
+///
+/// Exception without arguments is the constructor itself (block with [OBJECT] tag, which first field is the name),
+/// exception with arguments is a block with the constructor in the first field, followed by the arguments.
+unsafe fn read_exception(exception: RawOCaml) -> OCamlException {
+    if !is_block(exception) {
+        return OCamlException {
+            tag: String::from(UNKNOWN_EXCEPTION_TAG),
+            message: None,
+        };
+    }
+
+    if tag_val(exception) == OBJECT {
+        OCamlException {
+            tag: read_string(*field(exception, 0))
+                .unwrap_or_else(|| String::from(UNKNOWN_EXCEPTION_TAG)),
+            message: None,
+        }
+    } else {
+        let constructor = *field(exception, 0);
+        let tag = if is_block(constructor) && tag_val(constructor) == OBJECT {
+            read_string(*field(constructor, 0))
+        } else {
+            None
+        };
+        OCamlException {
+            tag: tag.unwrap_or_else(|| String::from(UNKNOWN_EXCEPTION_TAG)),
+            message: read_string(*field(exception, 1)),
+        }
+    }
+}
+
+/// Copies the OCaml string, returns None for any other value
+unsafe fn read_string(value: RawOCaml) -> Option<String> {
+    if is_block(value) && tag_val(value) == STRING {
+        let bytes = slice::from_raw_parts(string_val(value), caml_string_length(value));
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    } else {
+        None
+    }
+}
@@ -18,7 +18,7 @@ use crate::runtime;
 type TzResult<T> = Result<T, TezosErrorTrace>;
 
 mod tezos_ffi {
-    use ocaml_interop::{ocaml, OCamlBytes, OCamlInt, OCamlInt32, OCamlList};
+    use ocaml_interop::{OCamlBytes, OCamlInt, OCamlInt32, OCamlList};
 
     use tezos_api::ocaml_conv::FfiPath;
     use tezos_api::{
@@ -33,8 +33,9 @@ mod tezos_ffi {
     use tezos_messages::p2p::encoding::operation::Operation;
 
     use super::TzResult;
+    use crate::ocaml_exn;
 
-    ocaml! {
+    ocaml_exn! {
         pub fn apply_block(
             apply_block_request: ApplyBlockRequest
         ) -> TzResult<ApplyBlockResponse>;
@@ -114,6 +115,10 @@ pub fn setup() -> OCamlRuntime {
     ocaml_runtime
 }
 
+pub fn start() {
+    runtime::ensure_started()
+}
+
 pub fn shutdown() {
     runtime::shutdown()
 }
@@ -146,6 +151,8 @@ pub fn init_protocol_context(
     turn_off_context_raw_inspector: bool,
     patch_context: Option<PatchContext>,
 ) -> Result<InitProtocolContextResult, TezosStorageInitError> {
+    let protocol_hash = ProtocolHash::from_base58_check(&genesis.protocol)?;
+
     runtime::execute(move |rt: &mut OCamlRuntime| {
         // genesis configuration
         let genesis_tuple = (genesis.time, genesis.block, genesis.protocol).to_boxroot(rt);
@@ -196,6 +203,7 @@ pub fn init_protocol_context(
                 Ok(InitProtocolContextResult {
                     supported_protocol_hashes,
                     genesis_commit_hash,
+                    protocol_hash,
                 })
             }
             Err(e) => Err(TezosStorageInitError::from(e.to_rust::<TezosErrorTrace>())),
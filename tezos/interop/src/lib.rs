@@ -1,7 +1,8 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 
+pub mod closure;
 pub mod ffi;
 /// This modules will allow you to call OCaml code:
 ///
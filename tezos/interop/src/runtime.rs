@@ -1,15 +1,18 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::any::Any;
+use std::collections::BTreeMap;
 use std::error;
 use std::fmt;
 use std::future::Future;
 use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
-use std::sync::mpsc::{channel, Receiver, SendError, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, SendError, Sender};
+use std::sync::{Arc, Mutex, Once};
 use std::task::{Context, Poll, Waker};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use futures::executor::LocalPool;
 
@@ -22,22 +25,108 @@ lazy_static! {
     /// Because OCaml runtime should be accessed only by a single thread
     /// we will create the `OCAML_ENV` singleton.
     static ref OCAML_ENV: OCamlEnvironment = initialize_environment();
+
+    /// Single timer thread shared by all [OCamlCallTimeoutResult]s, see [start_timer]
+    static ref OCAML_CALL_TIMER: Mutex<Sender<TimerEntry>> = start_timer();
+}
+
+/// Guards the one-time startup of the OCaml runtime, see [ensure_started]
+static OCAML_RUNTIME_STARTED: Once = Once::new();
+
+/// OCaml exception raised by the OCaml function called from the OCaml block, see [crate::ocaml_exn]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OCamlException {
+    /// Name of the exception constructor, e.g. `Invalid_argument`
+    pub tag: String,
+    /// The first argument of the exception, if it is a string
+    pub message: Option<String>,
+}
+
+impl fmt::Display for OCamlException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match &self.message {
+            Some(message) => write!(f, "OCaml exception {}, message: {}", self.tag, message),
+            None => write!(f, "OCaml exception {}", self.tag),
+        }
+    }
 }
 
 /// OCaml execution error
-pub struct OCamlBlockPanic;
+pub struct OCamlBlockPanic {
+    /// Exception raised by the OCaml side, None means, that it was a rust panic
+    exception: Option<OCamlException>,
+}
+
+impl OCamlBlockPanic {
+    /// Creates error from the panic payload caught at the OCaml block boundary
+    fn from_panic_payload(payload: Box<dyn Any + Send>) -> Self {
+        Self {
+            exception: payload
+                .downcast::<OCamlException>()
+                .ok()
+                .map(|exception| *exception),
+        }
+    }
+
+    /// Returns exception raised by the OCaml side, if it was the cause of the panic
+    pub fn exception(&self) -> Option<&OCamlException> {
+        self.exception.as_ref()
+    }
+}
 
 impl error::Error for OCamlBlockPanic {}
 
 impl fmt::Display for OCamlBlockPanic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "Panic during the execution of an OCaml block")
+        match &self.exception {
+            Some(exception) => write!(
+                f,
+                "Panic during the execution of an OCaml block, reason: {}",
+                exception
+            ),
+            None => write!(f, "Panic during the execution of an OCaml block"),
+        }
     }
 }
 
 impl fmt::Debug for OCamlBlockPanic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "Panic during the execution of an OCaml block")
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// OCaml call error, when the call is bounded by a timeout
+pub enum OCamlCallError {
+    /// Panic during the execution of an OCaml block
+    Panic(OCamlBlockPanic),
+    /// OCaml block did not complete within the timeout
+    Timeout { timeout: Duration },
+}
+
+impl error::Error for OCamlCallError {}
+
+impl fmt::Display for OCamlCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            OCamlCallError::Panic(panic) => write!(f, "{}", panic),
+            OCamlCallError::Timeout { timeout } => write!(
+                f,
+                "Execution of an OCaml block did not complete within {:?}",
+                timeout
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for OCamlCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<OCamlBlockPanic> for OCamlCallError {
+    fn from(panic: OCamlBlockPanic) -> Self {
+        OCamlCallError::Panic(panic)
     }
 }
 
@@ -77,6 +166,93 @@ where
     }
 }
 
+/// The future for the result received from OCaml side, which is resolved with [OCamlCallError::Timeout],
+/// if the result is not available before the deadline.
+pub struct OCamlCallTimeoutResult<T>
+where
+    T: Send,
+{
+    call: OCamlCallResult<T>,
+    timeout: Duration,
+    deadline: Instant,
+    /// shared timer thread wakes the caller at the deadline, see [OCAML_CALL_TIMER]
+    timer_registered: bool,
+}
+
+impl<T> Future for OCamlCallTimeoutResult<T>
+where
+    T: Send,
+{
+    type Output = Result<T, OCamlCallError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(result) = Pin::new(&mut self.call).poll(cx) {
+            return Poll::Ready(result.map_err(OCamlCallError::from));
+        }
+
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(Err(OCamlCallError::Timeout {
+                timeout: self.timeout,
+            }));
+        }
+
+        if !self.timer_registered {
+            self.timer_registered = true;
+            OCAML_CALL_TIMER
+                .lock()
+                .unwrap()
+                .send((self.deadline, self.call.state.clone()))
+                .expect("OCaml call timer thread is not running");
+        }
+        Poll::Pending
+    }
+}
+
+/// Deadline and the state of the call, which waker should be woken at the deadline
+type TimerEntry = (Instant, Arc<Mutex<SharedState>>);
+
+/// Starts the timer thread, which wakes the callers (so they can resolve the timeout) at the registered deadlines
+fn start_timer() -> Mutex<Sender<TimerEntry>> {
+    let (timer_tx, timer_rx) = channel::<TimerEntry>();
+
+    thread::spawn(move || {
+        // sequence number keeps the entries with the same deadline
+        let mut deadlines: BTreeMap<(Instant, u64), Arc<Mutex<SharedState>>> = BTreeMap::new();
+        let mut sequence: u64 = 0;
+
+        loop {
+            let now = Instant::now();
+            while let Some(&(deadline, seq)) = deadlines.keys().next() {
+                if deadline > now {
+                    break;
+                }
+                let state = deadlines.remove(&(deadline, seq)).unwrap();
+                // wake the caller, so it can resolve the timeout (if not already woken by the result)
+                if let Some(waker) = state.lock().unwrap().waker.take() {
+                    waker.wake()
+                }
+            }
+
+            let received = match deadlines.keys().next() {
+                Some(&(deadline, _)) => {
+                    timer_rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => timer_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok((deadline, state)) => {
+                    sequence += 1;
+                    deadlines.insert((deadline, sequence), state);
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    });
+
+    Mutex::new(timer_tx)
+}
+
 /// OCaml task is executed by `OCamlThreadExecutor`. Task holds future responsible
 /// for executing OCaml function(s) and passing the result back to rust.
 struct OCamlTask {
@@ -108,7 +284,9 @@ impl OCamlTask {
                 let mut result = f_result_holder.lock().unwrap();
                 match std::panic::catch_unwind(AssertUnwindSafe(|| f(rt))) {
                     Ok(f_result) => *result = Some(Ok(f_result)),
-                    Err(_) => *result = Some(Err(OCamlBlockPanic)),
+                    Err(payload) => {
+                        *result = Some(Err(OCamlBlockPanic::from_panic_payload(payload)))
+                    }
                 }
             }),
             state: shared_state,
@@ -195,6 +373,30 @@ fn initialize_environment() -> OCamlEnvironment {
     OCamlEnvironment { spawner }
 }
 
+/// Starts the OCaml runtime (if not started yet) and blocks until it is ready to execute tasks.
+///
+/// This is idempotent and could be called at startup, so the runtime startup cost is not paid by the first real OCaml call.
+/// If not called explicitly, it is called by the first [spawn].
+pub fn ensure_started() {
+    OCAML_RUNTIME_STARTED.call_once(|| {
+        let (started_tx, started_rx) = channel();
+        let task = OCamlTask::new(
+            move |_rt: &mut OCamlRuntime| {
+                let _ = started_tx.send(());
+            },
+            Arc::new(Mutex::new(None)),
+            Arc::new(Mutex::new(SharedState { waker: None })),
+        );
+        OCAML_ENV
+            .spawner
+            .spawn(task)
+            .expect("Failed to spawn OCaml task");
+
+        // the first task is executed just after the runtime is initialized
+        let _ = started_rx.recv();
+    });
+}
+
 /// Run a function in OCaml runtime and return a result future.
 ///
 /// # Arguments
@@ -205,6 +407,8 @@ where
     F: FnOnce(&mut OCamlRuntime) -> T + 'static + Send,
     T: 'static + Send,
 {
+    ensure_started();
+
     let result = Arc::new(Mutex::new(None));
     let state = Arc::new(Mutex::new(SharedState { waker: None }));
     let result_future = OCamlCallResult {
@@ -220,6 +424,48 @@ where
     result_future
 }
 
+/// Run a function for every item in OCaml runtime within a single OCaml task and return a result future.
+///
+/// This amortizes the cost of passing the task to the OCaml thread, when calling many small functions.
+/// Results are in the same order as `items`, the first error stops the processing of the remaining items and is returned.
+///
+/// # Arguments
+///
+/// * `items` - inputs for `f`
+/// * `f` - the function will be executed in OCaml thread context for every item
+pub fn spawn_batch<I, O, E, F>(items: Vec<I>, f: F) -> OCamlCallResult<Result<Vec<O>, E>>
+where
+    F: Fn(&mut OCamlRuntime, I) -> Result<O, E> + 'static + Send,
+    I: 'static + Send,
+    O: 'static + Send,
+    E: 'static + Send,
+{
+    spawn(move |rt: &mut OCamlRuntime| items.into_iter().map(|item| f(rt, item)).collect())
+}
+
+/// Run a function in OCaml runtime and return a result future, which fails with [OCamlCallError::Timeout],
+/// if the function does not complete within `timeout`.
+///
+/// The OCaml computation is NOT cancelled on timeout, it is just abandoned: it keeps running
+/// in the OCaml thread (so the following calls wait for it) and its result is dropped.
+///
+/// # Arguments
+///
+/// * `timeout` - max duration to wait for the result
+/// * `f` - the function will be executed in OCaml thread context
+pub fn spawn_timeout<F, T>(timeout: Duration, f: F) -> OCamlCallTimeoutResult<T>
+where
+    F: FnOnce(&mut OCamlRuntime) -> T + 'static + Send,
+    T: 'static + Send,
+{
+    OCamlCallTimeoutResult {
+        call: spawn(f),
+        timeout,
+        deadline: Instant::now() + timeout,
+        timer_registered: false,
+    }
+}
+
 /// Synchronously execute provided function
 ///
 /// # Arguments
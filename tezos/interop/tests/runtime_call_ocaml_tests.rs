@@ -1,5 +1,20 @@
-use ocaml_interop::OCamlRuntime;
-use tezos_interop::runtime::{self, OCamlBlockPanic};
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use crypto::hash::{HashType, OperationHash};
+use ocaml_interop::{OCamlRuntime, ToOCaml};
+use tezos_interop::runtime::{self, OCamlBlockPanic, OCamlCallError, OCamlException};
+
+mod tezos_ffi {
+    use ocaml_interop::OCamlBytes;
+
+    use tezos_api::ocaml_conv::OCamlOperationHash;
+    use tezos_interop::ocaml_exn;
+
+    ocaml_exn! {
+        pub fn construct_and_compare_hash(operation_hash: OCamlOperationHash, hash_bytes: OCamlBytes) -> bool;
+    }
+}
 
 #[test]
 fn can_complete_future_with_return_value() -> Result<(), OCamlBlockPanic> {
@@ -13,5 +28,80 @@ fn can_complete_future_with_error() {
     let res = runtime::execute(|_rt: &mut OCamlRuntime| {
         panic!("Error occurred");
     });
-    assert!(res.is_err())
+    assert!(res.is_err());
+    assert!(res.unwrap_err().exception().is_none());
+}
+
+#[test]
+fn can_complete_future_with_ocaml_exception() {
+    let operation_hash = OperationHash::try_from(vec![0; HashType::OperationHash.size()]).unwrap();
+
+    // OCaml side cannot construct hash from the bytes of wrong length, so it raises Invalid_argument
+    let res = runtime::execute(move |rt: &mut OCamlRuntime| -> bool {
+        let hash_bytes = vec![1_u8, 2, 3].to_boxroot(rt);
+        let operation_hash = operation_hash.to_boxroot(rt);
+        tezos_ffi::construct_and_compare_hash(rt, &operation_hash, &hash_bytes).to_rust(rt)
+    });
+
+    let exception = res
+        .unwrap_err()
+        .exception()
+        .cloned()
+        .expect("Expected OCaml exception");
+    assert_eq!("Invalid_argument", exception.tag);
+    assert!(exception.message.is_some());
+}
+
+#[test]
+fn can_ensure_started_repeatedly() -> Result<(), OCamlBlockPanic> {
+    runtime::ensure_started();
+    runtime::ensure_started();
+    let ocaml_result = runtime::execute(|_rt: &mut OCamlRuntime| "Hello runtime!")?;
+    assert_eq!("Hello runtime!", ocaml_result);
+    Ok(())
+}
+
+#[test]
+fn can_complete_future_with_timeout() {
+    let res = futures::executor::block_on(runtime::spawn_timeout(
+        Duration::from_millis(10),
+        |_rt: &mut OCamlRuntime| {
+            std::thread::sleep(Duration::from_millis(200));
+            "Hello runtime!"
+        },
+    ));
+    assert!(matches!(res, Err(OCamlCallError::Timeout { .. })));
+
+    // abandoned call does not break the following ones
+    let res = futures::executor::block_on(runtime::spawn_timeout(
+        Duration::from_secs(10),
+        |_rt: &mut OCamlRuntime| "Hello runtime!",
+    ));
+    assert_eq!("Hello runtime!", res.unwrap());
+}
+
+#[test]
+fn can_complete_batch_in_order() -> Result<(), OCamlBlockPanic> {
+    let res = futures::executor::block_on(runtime::spawn_batch(
+        vec![1, 2, 3],
+        |_rt: &mut OCamlRuntime, item: i32| -> Result<i32, String> { Ok(item * 10) },
+    ))?;
+    assert_eq!(Ok(vec![10, 20, 30]), res);
+    Ok(())
+}
+
+#[test]
+fn can_complete_batch_with_first_error() -> Result<(), OCamlBlockPanic> {
+    let res = futures::executor::block_on(runtime::spawn_batch(
+        vec![1, 2, 3, 4],
+        |_rt: &mut OCamlRuntime, item: i32| {
+            if item % 2 == 0 {
+                Err(format!("Error on item: {}", item))
+            } else {
+                Ok(item)
+            }
+        },
+    ))?;
+    assert_eq!(Err("Error on item: 2".to_string()), res);
+    Ok(())
 }
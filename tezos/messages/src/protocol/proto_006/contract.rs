@@ -1,14 +1,47 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::cmp::Ordering;
+use std::fmt;
+
+use failure::Fail;
 use getset::Getters;
-use serde::Serialize;
+use num_bigint::{BigInt, Sign};
+use serde::{Deserialize, Serialize};
+
+use nom::combinator::map;
+use tezos_encoding::{
+    encoding::HasEncoding,
+    nom::{bounded, zarith, NomInput, NomReader, NomResult},
+    types::Zarith,
+};
+
+/// Max size in bytes of the Zarith-encoded counter accepted by the parser.
+///
+/// Real counters fit into a few bytes, the limit only guards against
+/// unbounded decoding of malicious input.
+pub const COUNTER_MAX_ZARITH_SIZE: usize = 128;
+
+#[derive(Debug, Fail, PartialEq)]
+pub enum CounterParseError {
+    #[fail(display = "Invalid counter, not a decimal number: {}", value)]
+    NotNumeric { value: String },
+
+    #[fail(display = "Invalid counter, negative value: {}", value)]
+    Negative { value: String },
 
-use tezos_encoding::{encoding::HasEncoding, nom::NomReader, types::Zarith};
+    #[fail(display = "Invalid counter, not a JSON string: {}", value)]
+    NotString { value: String },
+}
 
-#[derive(Serialize, Debug, Clone, Getters, HasEncoding, NomReader)]
+/// Counter of the contract operations.
+///
+/// Counter is backed by arbitrary precision [Zarith], so it is unbounded,
+/// it is never truncated to a fixed size integer and never wraps.
+#[derive(Serialize, Deserialize, Debug, Clone, Getters, HasEncoding, NomReader)]
 pub struct Counter {
     #[get = "pub"]
+    #[encoding(bounded = "COUNTER_MAX_ZARITH_SIZE")]
     counter: Zarith,
 }
 
@@ -16,4 +49,290 @@ impl Counter {
     pub fn to_numeric_string(&self) -> String {
         self.counter.0.to_str_radix(10)
     }
+
+    /// Parses counter from decimal string (inverse of [Counter::to_numeric_string])
+    pub fn from_decimal_str(value: &str) -> Result<Counter, CounterParseError> {
+        // BigInt parser accepts also a sign and '_' separators, we want just plain digits
+        if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit() || c == '-') {
+            return Err(CounterParseError::NotNumeric {
+                value: value.to_string(),
+            });
+        }
+
+        let parsed = BigInt::parse_bytes(value.as_bytes(), 10).ok_or_else(|| {
+            CounterParseError::NotNumeric {
+                value: value.to_string(),
+            }
+        })?;
+        if parsed.sign() == Sign::Minus {
+            return Err(CounterParseError::Negative {
+                value: value.to_string(),
+            });
+        }
+        Ok(Counter::from(parsed))
+    }
+
+    /// Counter in the form used by the tezos node rpc, JSON string with decimal number (e.g. `"12345"`)
+    pub fn to_rpc_json(&self) -> serde_json::Value {
+        serde_json::Value::String(self.to_numeric_string())
+    }
+
+    /// Parses counter from the tezos node rpc form (inverse of [Counter::to_rpc_json])
+    pub fn from_rpc_json(value: &serde_json::Value) -> Result<Counter, CounterParseError> {
+        match value {
+            serde_json::Value::String(value) => Counter::from_decimal_str(value),
+            other => Err(CounterParseError::NotString {
+                value: other.to_string(),
+            }),
+        }
+    }
+
+    /// Returns parser that reads counter, checking that its Zarith encoding
+    /// does not exceed `max` bytes (see [COUNTER_MAX_ZARITH_SIZE] for the default limit)
+    pub fn nom_read_bounded<'a>(max: usize) -> impl FnMut(NomInput<'a>) -> NomResult<'a, Counter> {
+        map(bounded(max, zarith), Counter::from)
+    }
+
+    /// Numeric value of the counter
+    pub fn value(&self) -> &BigInt {
+        &self.counter.0
+    }
+
+    /// Returns the next counter (value + 1), which never overflows (see [Counter])
+    pub fn increment(&self) -> Counter {
+        Counter {
+            counter: Zarith(&self.counter.0 + 1),
+        }
+    }
+}
+
+impl From<BigInt> for Counter {
+    fn from(value: BigInt) -> Self {
+        Counter {
+            counter: Zarith(value),
+        }
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.counter.0)
+    }
+}
+
+impl PartialEq for Counter {
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl Eq for Counter {}
+
+impl PartialOrd for Counter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Counter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value().cmp(other.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::binary_message::{BinaryRead, BinaryWrite};
+
+    use super::*;
+
+    #[test]
+    fn test_from_decimal_str_roundtrip() {
+        for value in &[
+            "0",
+            "1",
+            "42",
+            "2147483648",
+            "9223372036854775807",
+            "9223372036854775808",
+            "123456789012345678901234567890",
+        ] {
+            assert_eq!(
+                *value,
+                Counter::from_decimal_str(value)
+                    .unwrap()
+                    .to_numeric_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_decimal_str_invalid() {
+        assert_eq!(
+            Err(CounterParseError::Negative {
+                value: "-1".to_string()
+            }),
+            Counter::from_decimal_str("-1")
+        );
+        for value in &["", "abc", "12a", "+1", "1_000", " 1", "1.5", "-", "1-"] {
+            assert_eq!(
+                Err(CounterParseError::NotNumeric {
+                    value: value.to_string()
+                }),
+                Counter::from_decimal_str(value)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rpc_json_roundtrip() {
+        // operation contents as returned by the tezos node rpc
+        let operation: serde_json::Value = serde_json::from_str(
+            r#"{
+                "kind": "transaction",
+                "source": "tz1Tg9ezBuxGsdCmfhz3xWyVEWbYzJnS8j4e",
+                "fee": "1420",
+                "counter": "623613",
+                "gas_limit": "10307",
+                "storage_limit": "0",
+                "amount": "1000000",
+                "destination": "tz1MRXUaS1YFVG7wG7vRXGPmZAKGTXVnZhBx"
+            }"#,
+        )
+        .unwrap();
+
+        let counter = Counter::from_rpc_json(&operation["counter"]).unwrap();
+        assert_eq!(&BigInt::from(623613), counter.value());
+        assert_eq!(operation["counter"], counter.to_rpc_json());
+
+        for value in &["0", "9223372036854775808", "123456789012345678901234567890"] {
+            let json = serde_json::Value::String(value.to_string());
+            let counter = Counter::from_rpc_json(&json).unwrap();
+            assert_eq!(json, counter.to_rpc_json());
+            assert_eq!(format!("\"{}\"", value), counter.to_rpc_json().to_string());
+        }
+    }
+
+    #[test]
+    fn test_from_rpc_json_invalid() {
+        assert_eq!(
+            Err(CounterParseError::NotString {
+                value: "623613".to_string()
+            }),
+            Counter::from_rpc_json(&serde_json::json!(623613))
+        );
+        assert_eq!(
+            Err(CounterParseError::NotString {
+                value: "null".to_string()
+            }),
+            Counter::from_rpc_json(&serde_json::Value::Null)
+        );
+        assert_eq!(
+            Err(CounterParseError::NotNumeric {
+                value: "abc".to_string()
+            }),
+            Counter::from_rpc_json(&serde_json::json!("abc"))
+        );
+        assert_eq!(
+            Err(CounterParseError::Negative {
+                value: "-1".to_string()
+            }),
+            Counter::from_rpc_json(&serde_json::json!("-1"))
+        );
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        for value in &["0", "42", "123456789012345678901234567890"] {
+            let counter = Counter::from_decimal_str(value).unwrap();
+
+            let json = serde_json::to_string(&counter).unwrap();
+            let deserialized: Counter = serde_json::from_str(&json).unwrap();
+            assert_eq!(counter, deserialized);
+            assert_eq!(*value, deserialized.to_numeric_string());
+            assert_eq!(json, serde_json::to_string(&deserialized).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_increment() {
+        let counter = Counter::from(BigInt::from(i64::MAX));
+        let next = counter.increment();
+
+        assert_eq!(&(BigInt::from(i64::MAX) + 1), next.value());
+        assert_eq!("9223372036854775808", next.to_numeric_string());
+        assert_eq!(&BigInt::from(i64::MAX), counter.value());
+    }
+
+    #[test]
+    fn test_increment_beyond_i64() {
+        // 2^63 does not fit into i64 anymore
+        let two_pow_63 = BigInt::from(1) << 63;
+        let counter = Counter::from(two_pow_63.clone());
+        let next = counter.increment();
+
+        assert_eq!(&(two_pow_63 + 1), next.value());
+        assert_eq!("9223372036854775809", next.to_numeric_string());
+
+        // not truncated by the binary encoding either
+        let bytes = next.as_bytes().unwrap();
+        assert_eq!(next, Counter::from_bytes(bytes).unwrap());
+    }
+
+    #[test]
+    fn test_display() {
+        for value in &[
+            "0",
+            "42",
+            "9223372036854775808",
+            "123456789012345678901234567890",
+        ] {
+            let counter = Counter::from_decimal_str(value).unwrap();
+            assert_eq!(counter.to_numeric_string(), counter.to_string());
+            assert_eq!(format!("counter={}", value), format!("counter={}", counter));
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        let counter = Counter::from(BigInt::from(9));
+        let next = counter.increment();
+
+        // numeric ordering, not the string one ("10" < "9")
+        assert!(counter < next);
+        assert_eq!(Counter::from(BigInt::from(10)), next);
+        assert_eq!(Some(&next), vec![next.clone(), counter].iter().max());
+    }
+
+    #[test]
+    fn test_nom_read_binary_roundtrip() {
+        let counter = Counter::from_decimal_str("123456789012345678901234567890").unwrap();
+        let bytes = counter.as_bytes().unwrap();
+        assert_eq!(counter, Counter::from_bytes(bytes).unwrap());
+    }
+
+    #[test]
+    fn test_nom_read_oversized_zarith() {
+        // every byte has the continuation bit set, so the varint never ends
+        let mut bytes = vec![0xff; 10 * 1024 * 1024];
+        bytes.push(0x01);
+
+        assert!(Counter::from_bytes(&bytes).is_err());
+
+        // just over the limit
+        let mut bytes = vec![0xff; COUNTER_MAX_ZARITH_SIZE];
+        bytes.push(0x01);
+        assert!(Counter::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_nom_read_bounded() {
+        let bytes = Counter::from(BigInt::from(i64::MAX)).as_bytes().unwrap();
+
+        let (rest, counter) = Counter::nom_read_bounded(bytes.len())(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(Counter::from(BigInt::from(i64::MAX)), counter);
+
+        assert!(Counter::nom_read_bounded(bytes.len() - 1)(&bytes).is_err());
+    }
 }
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use tezos_encoding::{encoding::HasEncoding, nom::NomReader, types::Zarith};
 
+/// Counter of the contract operations, unbounded (backed by arbitrary precision [Zarith])
 #[derive(Serialize, Deserialize, Debug, Clone, Getters, HasEncoding, NomReader)]
 pub struct Counter {
     #[get = "pub"]
@@ -16,4 +17,11 @@ impl Counter {
     pub fn to_string_representation(&self) -> String {
         self.counter.0.to_str_radix(10)
     }
+
+    /// Returns the next counter (value + 1), which never overflows
+    pub fn increment(&self) -> Counter {
+        Counter {
+            counter: Zarith(&self.counter.0 + 1),
+        }
+    }
 }